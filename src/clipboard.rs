@@ -0,0 +1,59 @@
+//! The primary selection that X11 and Wayland paste with the middle mouse
+//! button, which iced's clipboard doesn't reach. Other platforms have none,
+//! so the last text selected in this program stands in for it.
+
+use std::sync::Mutex;
+
+static LAST_SELECTION: Mutex<Option<String>> = Mutex::new(None);
+
+#[cfg(target_os = "linux")]
+mod system {
+    use std::sync::Mutex;
+
+    use arboard::{Clipboard, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+
+    // The selection is only served while the clipboard that set it exists.
+    static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+    fn with_clipboard<T>(f: impl FnOnce(&mut Clipboard) -> Option<T>) -> Option<T> {
+        let mut clipboard = CLIPBOARD.lock().unwrap();
+        if clipboard.is_none() {
+            *clipboard = Clipboard::new().ok();
+        }
+        f(clipboard.as_mut()?)
+    }
+
+    pub fn set(text: String) {
+        with_clipboard(|clipboard| {
+            clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text)
+                .ok()
+        });
+    }
+
+    pub fn get() -> Option<String> {
+        with_clipboard(|clipboard| {
+            clipboard
+                .get()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text()
+                .ok()
+        })
+    }
+}
+
+pub fn set_primary(text: String) {
+    #[cfg(target_os = "linux")]
+    system::set(text.clone());
+    *LAST_SELECTION.lock().unwrap() = Some(text);
+}
+
+pub fn primary() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    if let Some(text) = system::get() {
+        return Some(text);
+    }
+    LAST_SELECTION.lock().unwrap().clone()
+}
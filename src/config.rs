@@ -0,0 +1,389 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::keybindings::Keybindings;
+use crate::palette::{self, Palette};
+use crate::settings::TabBarPosition;
+use crate::ssh::Keepalive;
+use crate::terminal::DEFAULT_SCROLLBACK;
+use crate::terminal_view::DEFAULT_FONT_SIZE;
+
+/// Bump this whenever the layout of the file changes in a way old files
+/// can't be read as, and upgrade older files in `parse`.
+pub const CURRENT_VERSION: u32 = 2;
+
+const FILE_NAME: &str = "settings.toml";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Appearance {
+    pub dark_mode: bool,
+    /// `#rrggbb`
+    pub primary_color: String,
+    pub tab_bar_position: TabBarPosition,
+    pub tab_bar_theme: String,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance {
+            dark_mode: false,
+            primary_color: String::from("#7340a1"),
+            tab_bar_position: TabBarPosition::default(),
+            tab_bar_theme: String::from("default"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub sidebar_width: Option<u16>,
+    pub vertical_divider: Option<u16>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            sidebar_width: Some(200),
+            vertical_divider: None,
+        }
+    }
+}
+
+/// Defaults for SSH connections; ssh_config settings for a host win.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Connection {
+    /// Seconds between keepalive requests, 0 for none.
+    pub keepalive_interval: u64,
+    /// Unanswered keepalives before the server is given up on.
+    pub keepalive_max: usize,
+    pub auto_reconnect: bool,
+    /// Longest wait between reconnect attempts, in seconds.
+    pub reconnect_max_delay: u64,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Connection {
+            keepalive_interval: 30,
+            keepalive_max: 3,
+            auto_reconnect: true,
+            reconnect_max_delay: 60,
+        }
+    }
+}
+
+impl Connection {
+    pub fn keepalive(&self) -> Keepalive {
+        Keepalive {
+            interval: (self.keepalive_interval > 0)
+                .then(|| Duration::from_secs(self.keepalive_interval)),
+            max: self.keepalive_max,
+        }
+    }
+
+    /// The backoff ceiling, or `None` if dropped sessions wait for the user.
+    pub fn reconnect_max_delay(&self) -> Option<Duration> {
+        self.auto_reconnect
+            .then(|| Duration::from_secs(self.reconnect_max_delay.max(1)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Terminal {
+    /// Lines kept after they scroll off the top of a terminal.
+    pub scrollback_lines: usize,
+    /// The name of a built-in or imported colour scheme.
+    pub color_scheme: String,
+    pub imported_schemes: Vec<Palette>,
+    pub font_size: f32,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Terminal {
+            scrollback_lines: DEFAULT_SCROLLBACK,
+            color_scheme: String::from(palette::DEFAULT_NAME),
+            imported_schemes: Vec::new(),
+            font_size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl Terminal {
+    pub fn palette(&self) -> Palette {
+        Palette::find(&self.color_scheme, &self.imported_schemes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    pub appearance: Appearance,
+    pub layout: Layout,
+    pub connection: Connection,
+    pub terminal: Terminal,
+    pub keybindings: Keybindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CURRENT_VERSION,
+            appearance: Appearance::default(),
+            layout: Layout::default(),
+            connection: Connection::default(),
+            terminal: Terminal::default(),
+            keybindings: Keybindings::default(),
+        }
+    }
+}
+
+/// What `load` found: the configuration to start with and, if the file
+/// couldn't be used, a warning for the user.
+pub struct Loaded {
+    pub config: Config,
+    pub warning: Option<String>,
+}
+
+/// `$XDG_CONFIG_HOME/my-ssh` (or the platform equivalent).
+pub fn dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("my-ssh"))
+}
+
+pub fn path() -> Option<PathBuf> {
+    dir().map(|dir| dir.join(FILE_NAME))
+}
+
+fn parse(contents: &str) -> Result<Config, String> {
+    let table: Table = contents
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+    let version = match table.get("version") {
+        None => CURRENT_VERSION,
+        Some(Value::Integer(version)) => {
+            u32::try_from(*version).map_err(|_| format!("invalid settings version {}", version))?
+        }
+        Some(_) => return Err(String::from("the settings version is not a number")),
+    };
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "it was written by a newer version of this program (settings version {})",
+            version
+        ));
+    }
+    Config::deserialize(Value::Table(table)).map_err(|e| e.to_string())
+}
+
+pub fn load() -> Loaded {
+    match path() {
+        Some(path) => load_from(&path),
+        None => Loaded {
+            config: Config::default(),
+            warning: None,
+        },
+    }
+}
+
+fn load_from(path: &Path) -> Loaded {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Loaded {
+                config: Config::default(),
+                warning: None,
+            }
+        }
+        Err(e) => {
+            return Loaded {
+                config: Config::default(),
+                warning: Some(format!(
+                    "Could not read {}: {}. Using default settings.",
+                    path.display(),
+                    e
+                )),
+            }
+        }
+    };
+
+    match parse(&contents) {
+        Ok(config) => Loaded {
+            config,
+            warning: None,
+        },
+        Err(e) => {
+            // Keep the broken file around rather than overwriting it with
+            // the defaults on the next change.
+            let backup = path.with_extension("toml.corrupt");
+            let kept = match fs::rename(path, &backup) {
+                Ok(()) => format!(" The old file was moved to {}.", backup.display()),
+                Err(_) => String::new(),
+            };
+            Loaded {
+                config: Config::default(),
+                warning: Some(format!(
+                    "Settings file {} could not be loaded: {}. Using default settings.{}",
+                    path.display(),
+                    e,
+                    kept
+                )),
+            }
+        }
+    }
+}
+
+pub fn save(config: &Config) -> Result<(), String> {
+    let path = path().ok_or_else(|| String::from("No configuration directory"))?;
+    save_to(&path, config)
+}
+
+fn save_to(path: &Path, config: &Config) -> Result<(), String> {
+    let contents = toml::to_string_pretty(config).map_err(|e| e.to_string())?;
+    write_atomic(path, contents.as_bytes())
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so
+/// a crash mid-write never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = dir.join(temp_name);
+
+    let result = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::{Action, Chord};
+
+    /// A fresh directory for one test, removed again when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("my-ssh-config-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn changed_config() -> Config {
+        let mut config = Config::default();
+        config.appearance.dark_mode = true;
+        config.appearance.tab_bar_position = TabBarPosition::Bottom;
+        config.layout.vertical_divider = Some(640);
+        config.layout.sidebar_width = Some(320);
+        config.connection.keepalive_interval = 0;
+        config.terminal.scrollback_lines = 500;
+        config.terminal.font_size = 13.5;
+        let mut scheme = palette::builtins().swap_remove(1);
+        scheme.name = String::from("Imported");
+        config.terminal.color_scheme = scheme.name.clone();
+        config.terminal.imported_schemes.push(scheme);
+        config
+            .keybindings
+            .set(Action::Find, Chord::parse("Ctrl+Alt+F"))
+            .unwrap();
+        config.keybindings.set(Action::ClosePane, None).unwrap();
+        config
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(parse(""), Ok(Config::default()));
+
+        // Anything left out keeps its default.
+        let config = parse("version = 2\n[terminal]\nscrollback_lines = 42\n").unwrap();
+        assert_eq!(config.terminal.scrollback_lines, 42);
+        assert_eq!(config.terminal.font_size, DEFAULT_FONT_SIZE);
+        assert_eq!(config.layout, Layout::default());
+
+        let config = parse("[keybindings]\nfind = \"ctrl+shift+g\"\ncopy = \"\"\n").unwrap();
+        assert_eq!(
+            config.keybindings.chord(Action::Find),
+            Chord::parse("Ctrl+Shift+G")
+        );
+        assert_eq!(config.keybindings.chord(Action::Copy), None);
+
+        assert!(parse("version = 3\n")
+            .unwrap_err()
+            .contains("newer version"));
+        assert!(parse("version = -1\n").is_err());
+        assert!(parse("version = \"2\"\n").is_err());
+        assert!(parse("[appearance]\ndark_mode = \"yes\"\n").is_err());
+        assert!(parse("[appearance\n").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = changed_config();
+        let contents = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(parse(&contents), Ok(config.clone()));
+
+        let dir = TempDir::new("round-trip");
+        let path = dir.0.join("nested").join(FILE_NAME);
+        save_to(&path, &config).unwrap();
+        let loaded = load_from(&path);
+        assert_eq!(loaded.warning, None);
+        assert_eq!(loaded.config, config);
+        // Nothing is left behind by the atomic write.
+        let files: Vec<_> = fs::read_dir(path.parent().unwrap()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn missing_file() {
+        let dir = TempDir::new("missing");
+        let loaded = load_from(&dir.0.join(FILE_NAME));
+        assert_eq!(loaded.config, Config::default());
+        assert_eq!(loaded.warning, None);
+    }
+
+    /// A broken file is moved aside so the defaults don't overwrite it.
+    #[test]
+    fn corrupt_file() {
+        let dir = TempDir::new("corrupt");
+        let path = dir.0.join(FILE_NAME);
+        fs::write(&path, "[appearance\n").unwrap();
+        let loaded = load_from(&path);
+        assert_eq!(loaded.config, Config::default());
+        let warning = loaded.warning.unwrap();
+        assert!(warning.contains("could not be loaded"), "{}", warning);
+        assert!(warning.contains("settings.toml.corrupt"), "{}", warning);
+        assert!(!path.exists());
+        let backup = dir.0.join("settings.toml.corrupt");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "[appearance\n");
+    }
+}
@@ -0,0 +1,664 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use russh::client;
+use russh::Channel;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::socks;
+use crate::ssh::SshSession;
+use crate::ssh_config::{self, ForwardSpec};
+
+/// How long a SOCKS client gets to say where it wants to go.
+const SOCKS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait after a failed accept, which is most often the process
+/// running out of file descriptors, before trying again.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardKind {
+    /// `-L`: we listen, the server connects.
+    Local,
+    /// `-R`: the server listens, we connect.
+    Remote,
+    /// `-D`: we listen as a SOCKS proxy, the server connects wherever each
+    /// client asks.
+    Dynamic,
+}
+
+impl ForwardKind {
+    pub fn flag(self) -> &'static str {
+        match self {
+            ForwardKind::Local => "-L",
+            ForwardKind::Remote => "-R",
+            ForwardKind::Dynamic => "-D",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForwardState {
+    Starting,
+    Listening,
+    Failed(String),
+    Stopped,
+}
+
+/// A snapshot of one forward for display.
+#[derive(Debug, Clone)]
+pub struct ForwardStatus {
+    pub kind: ForwardKind,
+    pub spec: ForwardSpec,
+    pub state: ForwardState,
+    /// Bytes received from the far end and written to local clients.
+    pub bytes_in: u64,
+    /// Bytes read from local clients and sent through the tunnel.
+    pub bytes_out: u64,
+    /// The connections being carried, as `client -> target`.
+    pub connections: Vec<String>,
+}
+
+struct Stats {
+    state: Mutex<ForwardState>,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    next_connection: AtomicU64,
+    connections: Mutex<Vec<(u64, String)>>,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            state: Mutex::new(ForwardState::Stopped),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            next_connection: AtomicU64::new(0),
+            connections: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn set_state(&self, state: ForwardState) {
+        *self.state.lock().unwrap() = state;
+    }
+}
+
+/// Lists a tunnelled connection for as long as it is alive, including when
+/// its task is aborted.
+struct Active<'a> {
+    stats: &'a Stats,
+    id: u64,
+}
+
+impl<'a> Active<'a> {
+    fn new(stats: &'a Stats, description: String) -> Self {
+        let id = stats.next_connection.fetch_add(1, Ordering::Relaxed);
+        stats.connections.lock().unwrap().push((id, description));
+        Active { stats, id }
+    }
+}
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        self.stats
+            .connections
+            .lock()
+            .unwrap()
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+type ForwardedChannel = Channel<client::Msg>;
+
+struct Forward {
+    kind: ForwardKind,
+    spec: ForwardSpec,
+    stats: Arc<Stats>,
+    task: Option<JoinHandle<()>>,
+    /// Where the client handler hands over connections the server accepted
+    /// on a remote forward.
+    incoming: Option<mpsc::UnboundedSender<ForwardedChannel>>,
+}
+
+impl Forward {
+    fn running(&self) -> bool {
+        self.task.as_ref().is_some_and(|task| !task.is_finished())
+    }
+}
+
+/// The forwards configured on one SSH connection. A running forward is a
+/// task that stops, along with every connection it carries, when aborted.
+#[derive(Clone, Default)]
+pub struct Forwards {
+    forwards: Arc<Mutex<Vec<Forward>>>,
+}
+
+impl Forwards {
+    /// Registers a forward without starting it and returns its index.
+    pub(crate) fn add(&self, kind: ForwardKind, spec: ForwardSpec) -> usize {
+        let mut forwards = self.forwards.lock().unwrap();
+        forwards.push(Forward {
+            kind,
+            spec,
+            stats: Arc::new(Stats::new()),
+            task: None,
+            incoming: None,
+        });
+        forwards.len() - 1
+    }
+
+    pub(crate) fn start(&self, session: &SshSession, index: usize) {
+        let mut forwards = self.forwards.lock().unwrap();
+        let Some(forward) = forwards.get_mut(index) else {
+            return;
+        };
+        if forward.running() {
+            return;
+        }
+        forward.stats.set_state(ForwardState::Starting);
+        let (session, spec, stats) = (session.clone(), forward.spec.clone(), forward.stats.clone());
+        forward.task = Some(match forward.kind {
+            ForwardKind::Local => tokio::spawn(run_local(session, spec, stats)),
+            ForwardKind::Dynamic => tokio::spawn(run_dynamic(session, spec, stats)),
+            ForwardKind::Remote => {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                forward.incoming = Some(sender);
+                tokio::spawn(run_remote(session, spec, stats, receiver))
+            }
+        });
+    }
+
+    pub(crate) fn stop(&self, session: &SshSession, index: usize) {
+        let mut forwards = self.forwards.lock().unwrap();
+        let Some(forward) = forwards.get_mut(index) else {
+            return;
+        };
+        let Some(task) = forward.task.take() else {
+            return;
+        };
+        task.abort();
+        forward.incoming = None;
+        if forward.kind == ForwardKind::Remote
+            && forward.stats.state.lock().unwrap().clone() == ForwardState::Listening
+        {
+            let session = session.clone();
+            let spec = forward.spec.clone();
+            tokio::spawn(async move {
+                let _ = session
+                    .cancel_remote_forward(remote_bind_address(&spec), spec.bind_port)
+                    .await;
+            });
+        }
+        forward.stats.set_state(ForwardState::Stopped);
+    }
+
+    /// Stops everything without telling the server, for when the
+    /// connection is going away anyway.
+    pub(crate) fn stop_all(&self) {
+        for forward in self.forwards.lock().unwrap().iter_mut() {
+            if let Some(task) = forward.task.take() {
+                task.abort();
+            }
+            forward.incoming = None;
+            forward.stats.set_state(ForwardState::Stopped);
+        }
+    }
+
+    /// Passes a connection the server accepted on `port` to the remote
+    /// forward listening there, or gives it back if there is none.
+    pub(crate) fn forwarded(
+        &self,
+        port: u32,
+        channel: ForwardedChannel,
+    ) -> Result<(), ForwardedChannel> {
+        let forwards = self.forwards.lock().unwrap();
+        let incoming = forwards.iter().find_map(|forward| match &forward.incoming {
+            Some(incoming)
+                if forward.kind == ForwardKind::Remote
+                    && u32::from(forward.spec.bind_port) == port =>
+            {
+                Some(incoming)
+            }
+            _ => None,
+        });
+        match incoming {
+            Some(incoming) => incoming.send(channel).map_err(|e| e.0),
+            None => Err(channel),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forwards.lock().unwrap().is_empty()
+    }
+
+    pub fn statuses(&self) -> Vec<ForwardStatus> {
+        self.forwards
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|forward| ForwardStatus {
+                kind: forward.kind,
+                spec: forward.spec.clone(),
+                state: forward.stats.state.lock().unwrap().clone(),
+                bytes_in: forward.stats.bytes_in.load(Ordering::Relaxed),
+                bytes_out: forward.stats.bytes_out.load(Ordering::Relaxed),
+                connections: forward
+                    .stats
+                    .connections
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(_, description)| description.clone())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// `ssh` listens on loopback only unless told otherwise; `*` means every
+/// interface.
+fn listen_address(spec: &ForwardSpec) -> &str {
+    match spec.bind_address.as_deref() {
+        None | Some("localhost") => "127.0.0.1",
+        Some("" | "*") => "0.0.0.0",
+        Some(address) => address,
+    }
+}
+
+async fn listen(spec: &ForwardSpec, stats: &Stats) -> Option<TcpListener> {
+    match TcpListener::bind((listen_address(spec), spec.bind_port)).await {
+        Ok(listener) => {
+            stats.set_state(ForwardState::Listening);
+            Some(listener)
+        }
+        Err(e) => {
+            stats.set_state(ForwardState::Failed(format!(
+                "Could not listen on port {}: {}",
+                spec.bind_port, e
+            )));
+            None
+        }
+    }
+}
+
+fn target(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+async fn run_local(session: SshSession, spec: ForwardSpec, stats: Arc<Stats>) {
+    let Some(listener) = listen(&spec, &stats).await else {
+        return;
+    };
+
+    // Owning the connections here means aborting this task closes them too.
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((socket, peer)) = accepted else {
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                };
+                let session = session.clone();
+                let spec = spec.clone();
+                let stats = stats.clone();
+                connections.spawn(async move {
+                    let channel = session.open_direct_tcpip(&spec.host, spec.host_port, peer);
+                    let Ok(channel) = channel.await else {
+                        return;
+                    };
+                    let target = target(&spec.host, spec.host_port);
+                    let description = format!("{} -> {}", peer, target);
+                    tunnel(socket, channel.into_stream(), &stats, description).await;
+                });
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn run_dynamic(session: SshSession, spec: ForwardSpec, stats: Arc<Stats>) {
+    let Some(listener) = listen(&spec, &stats).await else {
+        return;
+    };
+
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((socket, peer)) = accepted else {
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                };
+                let session = session.clone();
+                let stats = stats.clone();
+                connections.spawn(socks_connection(session, socket, peer, stats));
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn socks_connection(
+    session: SshSession,
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    stats: Arc<Stats>,
+) {
+    let request = tokio::time::timeout(SOCKS_HANDSHAKE_TIMEOUT, socks::accept(&mut socket));
+    let Ok(Ok(request)) = request.await else {
+        return;
+    };
+    let channel = session
+        .open_direct_tcpip(&request.host, request.port, peer)
+        .await;
+    let success = channel.is_ok();
+    if socks::reply(&mut socket, request.version, success)
+        .await
+        .is_err()
+    {
+        return;
+    }
+    if let Ok(channel) = channel {
+        let description = format!("{} -> {}", peer, target(&request.host, request.port));
+        tunnel(socket, channel.into_stream(), &stats, description).await;
+    }
+}
+
+async fn tunnel<L, R>(local: L, remote: R, stats: &Stats, description: String)
+where
+    L: AsyncRead + AsyncWrite,
+    R: AsyncRead + AsyncWrite,
+{
+    let _active = Active::new(stats, description);
+    let (mut local_read, mut local_write) = tokio::io::split(local);
+    let (mut remote_read, mut remote_write) = tokio::io::split(remote);
+    let _ = tokio::join!(
+        pump(&mut local_read, &mut remote_write, &stats.bytes_out),
+        pump(&mut remote_read, &mut local_write, &stats.bytes_in),
+    );
+}
+
+/// The address asked of the server for a remote forward. Like `ssh`, it
+/// listens on loopback only unless told otherwise.
+fn remote_bind_address(spec: &ForwardSpec) -> &str {
+    spec.bind_address.as_deref().unwrap_or("localhost")
+}
+
+async fn run_remote(
+    session: SshSession,
+    spec: ForwardSpec,
+    stats: Arc<Stats>,
+    mut incoming: mpsc::UnboundedReceiver<ForwardedChannel>,
+) {
+    let address = remote_bind_address(&spec);
+    if let Err(e) = session
+        .request_remote_forward(address, spec.bind_port)
+        .await
+    {
+        stats.set_state(ForwardState::Failed(e));
+        return;
+    }
+    stats.set_state(ForwardState::Listening);
+
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            channel = incoming.recv() => {
+                let Some(channel) = channel else {
+                    break;
+                };
+                let spec = spec.clone();
+                let stats = stats.clone();
+                connections.spawn(async move {
+                    match TcpStream::connect((spec.host.as_str(), spec.host_port)).await {
+                        Ok(socket) => {
+                            let description = format!(
+                                "remote port {} -> {}",
+                                spec.bind_port,
+                                target(&spec.host, spec.host_port)
+                            );
+                            tunnel(socket, channel.into_stream(), &stats, description).await
+                        }
+                        Err(_) => {
+                            let _ = channel.close().await;
+                        }
+                    }
+                });
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+/// Copies until EOF, then passes the EOF on.
+async fn pump<R, W>(reader: &mut R, writer: &mut W, counter: &AtomicU64) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = [0; 16 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            return writer.shutdown().await;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        counter.fetch_add(read as u64, Ordering::Relaxed);
+    }
+}
+
+/// Parses a list of `-L`/`-R` style forwards separated by commas or spaces.
+pub fn parse_forward_list(text: &str) -> Result<Vec<ForwardSpec>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            ssh_config::parse_forward_arg(arg).ok_or_else(|| {
+                format!(
+                    "Invalid forward {}, expected [bind_address:]port:host:hostport",
+                    arg
+                )
+            })
+        })
+        .collect()
+}
+
+/// Parses a list of `-D` style `[bind_address:]port` entries.
+pub fn parse_dynamic_list(text: &str) -> Result<Vec<ForwardSpec>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            ssh_config::parse_dynamic_arg(arg).ok_or_else(|| {
+                format!(
+                    "Invalid SOCKS forward {}, expected [bind_address:]port",
+                    arg
+                )
+            })
+        })
+        .collect()
+}
+
+/// Parses `-L spec`, `-R spec` or `-D [bind_address:]port`; a bare spec is
+/// a local forward.
+pub fn parse_forward_flag(text: &str) -> Result<(ForwardKind, ForwardSpec), String> {
+    let text = text.trim();
+    if let Some(arg) = text.strip_prefix("-D") {
+        let spec = ssh_config::parse_dynamic_arg(arg.trim())
+            .ok_or_else(|| format!("Invalid forward {}, expected -D [bind_address:]port", text))?;
+        return Ok((ForwardKind::Dynamic, spec));
+    }
+    let (kind, arg) = if let Some(arg) = text.strip_prefix("-R") {
+        (ForwardKind::Remote, arg.trim())
+    } else {
+        (
+            ForwardKind::Local,
+            text.strip_prefix("-L").unwrap_or(text).trim(),
+        )
+    };
+    let spec = ssh_config::parse_forward_arg(arg).ok_or_else(|| {
+        format!(
+            "Invalid forward {}, expected -L or -R [bind_address:]port:host:hostport",
+            text
+        )
+    })?;
+    Ok((kind, spec))
+}
+
+pub fn format_forward_list(forwards: &[ForwardSpec]) -> String {
+    forwards
+        .iter()
+        .map(ForwardSpec::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    fn spec(bind_address: Option<&str>, bind_port: u16, host: &str, host_port: u16) -> ForwardSpec {
+        ForwardSpec {
+            bind_address: bind_address.map(String::from),
+            bind_port,
+            host: host.to_string(),
+            host_port,
+        }
+    }
+
+    #[test]
+    fn forward_lists() {
+        assert_eq!(
+            parse_forward_list("8080:web:80, 127.0.0.1:5432:db:5432  [::1]:2222:[2001:db8::1]:22,"),
+            Ok(vec![
+                spec(None, 8080, "web", 80),
+                spec(Some("127.0.0.1"), 5432, "db", 5432),
+                spec(Some("::1"), 2222, "2001:db8::1", 22),
+            ])
+        );
+        assert_eq!(parse_forward_list(" , "), Ok(Vec::new()));
+        for bad in [
+            "8080",
+            "8080:web",
+            "0:web:80",
+            "8080::80",
+            "8080:web:http",
+            "a:1:b:2:3",
+        ] {
+            let error = parse_forward_list(&format!("8080:web:80 {}", bad)).unwrap_err();
+            assert!(
+                error.starts_with(&format!("Invalid forward {},", bad)),
+                "{}",
+                error
+            );
+        }
+
+        assert_eq!(
+            parse_dynamic_list("1080 *:1081,[::1]:1082"),
+            Ok(vec![
+                spec(None, 1080, "", 0),
+                spec(Some("*"), 1081, "", 0),
+                spec(Some("::1"), 1082, "", 0),
+            ])
+        );
+        assert!(parse_dynamic_list("1080 socks").is_err());
+        assert!(parse_dynamic_list("70000").is_err());
+    }
+
+    #[test]
+    fn forward_flags() {
+        assert_eq!(
+            parse_forward_flag("-L 8080:web:80"),
+            Ok((ForwardKind::Local, spec(None, 8080, "web", 80)))
+        );
+        assert_eq!(
+            parse_forward_flag("  8080:web:80 "),
+            Ok((ForwardKind::Local, spec(None, 8080, "web", 80)))
+        );
+        assert_eq!(
+            parse_forward_flag("-R*:9000:localhost:3000"),
+            Ok((
+                ForwardKind::Remote,
+                spec(Some("*"), 9000, "localhost", 3000)
+            ))
+        );
+        assert_eq!(
+            parse_forward_flag("-D 1080"),
+            Ok((ForwardKind::Dynamic, spec(None, 1080, "", 0)))
+        );
+        assert!(parse_forward_flag("-D 1080:web:80").is_err());
+        assert!(parse_forward_flag("-R 9000").is_err());
+        assert!(parse_forward_flag("").is_err());
+    }
+
+    #[test]
+    fn formatting_round_trips() {
+        let forwards = vec![
+            spec(None, 8080, "web", 80),
+            spec(Some("::1"), 2222, "2001:db8::1", 22),
+        ];
+        let text = format_forward_list(&forwards);
+        assert_eq!(text, "8080:web:80, [::1]:2222:[2001:db8::1]:22");
+        assert_eq!(parse_forward_list(&text), Ok(forwards));
+
+        let dynamic = vec![spec(None, 1080, "", 0), spec(Some("*"), 1081, "", 0)];
+        let text = format_forward_list(&dynamic);
+        assert_eq!(text, "1080, *:1081");
+        assert_eq!(parse_dynamic_list(&text), Ok(dynamic));
+    }
+
+    #[test]
+    fn listen_addresses() {
+        assert_eq!(listen_address(&spec(None, 1, "h", 1)), "127.0.0.1");
+        assert_eq!(
+            listen_address(&spec(Some("localhost"), 1, "h", 1)),
+            "127.0.0.1"
+        );
+        assert_eq!(listen_address(&spec(Some("*"), 1, "h", 1)), "0.0.0.0");
+        assert_eq!(listen_address(&spec(Some(""), 1, "h", 1)), "0.0.0.0");
+        assert_eq!(listen_address(&spec(Some("::1"), 1, "h", 1)), "::1");
+        assert_eq!(remote_bind_address(&spec(None, 1, "h", 1)), "localhost");
+        assert_eq!(remote_bind_address(&spec(Some("*"), 1, "h", 1)), "*");
+    }
+
+    /// Bytes are counted each way, and the connection is listed only while
+    /// it is carried.
+    #[tokio::test]
+    async fn tunnels() {
+        let stats = Stats::new();
+        let (local, mut client) = duplex(1024);
+        let (remote, mut server) = duplex(1024);
+        let carried = async {
+            client.write_all(b"hello").await.unwrap();
+            client.shutdown().await.unwrap();
+            let mut request = Vec::new();
+            server.read_to_end(&mut request).await.unwrap();
+            assert_eq!(request, b"hello");
+            assert_eq!(
+                *stats.connections.lock().unwrap(),
+                [(0, String::from("a -> b"))]
+            );
+            server.write_all(b"hi there").await.unwrap();
+            server.shutdown().await.unwrap();
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).await.unwrap();
+            assert_eq!(response, b"hi there");
+        };
+        tokio::join!(
+            tunnel(local, remote, &stats, String::from("a -> b")),
+            carried
+        );
+        assert_eq!(stats.bytes_out.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.bytes_in.load(Ordering::Relaxed), 8);
+        assert!(stats.connections.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use iced_native::keyboard::{KeyCode, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// Something a shortcut can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    Copy,
+    Paste,
+    Find,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    SplitRight,
+    SplitDown,
+    ClosePane,
+    MaximizePane,
+    FocusLeft,
+    FocusRight,
+    FocusUp,
+    FocusDown,
+}
+
+impl Action {
+    pub const ALL: [Action; 18] = [
+        Action::NewTab,
+        Action::CloseTab,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::Copy,
+        Action::Paste,
+        Action::Find,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ZoomReset,
+        Action::SplitRight,
+        Action::SplitDown,
+        Action::ClosePane,
+        Action::MaximizePane,
+        Action::FocusLeft,
+        Action::FocusRight,
+        Action::FocusUp,
+        Action::FocusDown,
+    ];
+
+    /// The name it is saved under.
+    fn name(self) -> &'static str {
+        match self {
+            Action::NewTab => "new-tab",
+            Action::CloseTab => "close-tab",
+            Action::NextTab => "next-tab",
+            Action::PreviousTab => "previous-tab",
+            Action::Copy => "copy",
+            Action::Paste => "paste",
+            Action::Find => "find",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::ZoomReset => "zoom-reset",
+            Action::SplitRight => "split-right",
+            Action::SplitDown => "split-down",
+            Action::ClosePane => "close-pane",
+            Action::MaximizePane => "maximize-pane",
+            Action::FocusLeft => "focus-left",
+            Action::FocusRight => "focus-right",
+            Action::FocusUp => "focus-up",
+            Action::FocusDown => "focus-down",
+        }
+    }
+
+    fn default_chord(self) -> Chord {
+        // Ctrl+Shift leaves the plain Ctrl chords to the session. Tab
+        // switching keeps the usual Ctrl+PageUp/PageDown, which shells and
+        // editors hardly ever bind.
+        let shifted = |key_code| Chord {
+            key_code,
+            control: true,
+            alt: false,
+            shift: true,
+            logo: false,
+        };
+        let control = |key_code| Chord {
+            shift: false,
+            ..shifted(key_code)
+        };
+        match self {
+            Action::NewTab => shifted(KeyCode::T),
+            Action::CloseTab => shifted(KeyCode::W),
+            Action::NextTab => control(KeyCode::PageDown),
+            Action::PreviousTab => control(KeyCode::PageUp),
+            Action::Copy => shifted(KeyCode::C),
+            Action::Paste => shifted(KeyCode::V),
+            Action::Find => shifted(KeyCode::F),
+            Action::ZoomIn => shifted(KeyCode::Equals),
+            Action::ZoomOut => shifted(KeyCode::Minus),
+            Action::ZoomReset => shifted(KeyCode::Key0),
+            Action::SplitRight => shifted(KeyCode::E),
+            Action::SplitDown => shifted(KeyCode::O),
+            Action::ClosePane => shifted(KeyCode::Q),
+            Action::MaximizePane => shifted(KeyCode::X),
+            Action::FocusLeft => shifted(KeyCode::Left),
+            Action::FocusRight => shifted(KeyCode::Right),
+            Action::FocusUp => shifted(KeyCode::Up),
+            Action::FocusDown => shifted(KeyCode::Down),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::NewTab => "New tab",
+            Action::CloseTab => "Close tab",
+            Action::NextTab => "Next tab",
+            Action::PreviousTab => "Previous tab",
+            Action::Copy => "Copy",
+            Action::Paste => "Paste",
+            Action::Find => "Find",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::ZoomReset => "Reset zoom",
+            Action::SplitRight => "Split pane right",
+            Action::SplitDown => "Split pane down",
+            Action::ClosePane => "Close pane",
+            Action::MaximizePane => "Maximize or restore pane",
+            Action::FocusLeft => "Focus pane on the left",
+            Action::FocusRight => "Focus pane on the right",
+            Action::FocusUp => "Focus pane above",
+            Action::FocusDown => "Focus pane below",
+        })
+    }
+}
+
+const KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Minus, "-"),
+    (KeyCode::Equals, "="),
+    // Spelled out, since `+` separates the parts of a chord.
+    (KeyCode::Plus, "Plus"),
+    (KeyCode::Comma, ","),
+    (KeyCode::Period, "."),
+    (KeyCode::Slash, "/"),
+    (KeyCode::Backslash, "\\"),
+    (KeyCode::Semicolon, ";"),
+    (KeyCode::Apostrophe, "'"),
+    (KeyCode::LBracket, "["),
+    (KeyCode::RBracket, "]"),
+    (KeyCode::Grave, "`"),
+    (KeyCode::NumpadAdd, "NumPlus"),
+    (KeyCode::NumpadSubtract, "NumMinus"),
+    (KeyCode::NumpadMultiply, "NumMultiply"),
+    (KeyCode::NumpadDivide, "NumDivide"),
+    (KeyCode::NumpadEnter, "NumEnter"),
+];
+
+/// A key with the modifiers held down, e.g. `Ctrl+Shift+T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub key_code: KeyCode,
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+}
+
+impl Chord {
+    /// The chord for a key press, or `None` for a modifier or a key with
+    /// no name.
+    pub fn new(key_code: KeyCode, modifiers: Modifiers) -> Option<Chord> {
+        KEY_NAMES
+            .iter()
+            .any(|&(known, _)| known == key_code)
+            .then(|| Chord {
+                key_code,
+                control: modifiers.control(),
+                alt: modifiers.alt(),
+                shift: modifiers.shift(),
+                logo: modifiers.logo(),
+            })
+    }
+
+    pub fn parse(text: &str) -> Option<Chord> {
+        let (modifiers, key) = text.trim().rsplit_once('+').unwrap_or(("", text.trim()));
+        let key_code = KEY_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(key))?
+            .0;
+        let mut chord = Chord {
+            key_code,
+            control: false,
+            alt: false,
+            shift: false,
+            logo: false,
+        };
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => chord.control = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                "super" => chord.logo = true,
+                _ => return None,
+            }
+        }
+        Some(chord)
+    }
+
+    /// Whether the key would type into the session instead: only Ctrl, Alt
+    /// or Super, or a function key, make a shortcut.
+    pub fn types_text(&self) -> bool {
+        let function_key = matches!(
+            self.key_code,
+            KeyCode::F1
+                | KeyCode::F2
+                | KeyCode::F3
+                | KeyCode::F4
+                | KeyCode::F5
+                | KeyCode::F6
+                | KeyCode::F7
+                | KeyCode::F8
+                | KeyCode::F9
+                | KeyCode::F10
+                | KeyCode::F11
+                | KeyCode::F12
+        );
+        !(self.control || self.alt || self.logo || function_key)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.control, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+            (self.logo, "Super+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|&&(key_code, _)| key_code == self.key_code)
+            .map_or("?", |&(_, name)| name);
+        f.write_str(name)
+    }
+}
+
+/// The chord for each action, if it has one. Saved as a table of action
+/// names to chords, where an empty chord means none; actions missing from
+/// it keep their default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct Keybindings {
+    /// In `Action::ALL` order.
+    chords: Vec<(Action, Option<Chord>)>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keybindings {
+            chords: Action::ALL
+                .iter()
+                .map(|&action| (action, Some(action.default_chord())))
+                .collect(),
+        }
+    }
+}
+
+impl From<BTreeMap<String, String>> for Keybindings {
+    fn from(saved: BTreeMap<String, String>) -> Self {
+        let mut keybindings = Keybindings::default();
+        for (action, chord) in &mut keybindings.chords {
+            match saved.get(action.name()).map(|chord| chord.trim()) {
+                Some("") => *chord = None,
+                Some(saved) => {
+                    if let Some(saved) = Chord::parse(saved) {
+                        *chord = Some(saved);
+                    }
+                }
+                None => {}
+            }
+        }
+        keybindings
+    }
+}
+
+impl From<Keybindings> for BTreeMap<String, String> {
+    fn from(keybindings: Keybindings) -> Self {
+        keybindings
+            .chords
+            .iter()
+            .map(|(action, chord)| {
+                let chord = chord.map(|chord| chord.to_string()).unwrap_or_default();
+                (action.name().to_string(), chord)
+            })
+            .collect()
+    }
+}
+
+impl Keybindings {
+    pub fn chord(&self, action: Action) -> Option<Chord> {
+        self.chords
+            .iter()
+            .find(|&&(other, _)| other == action)
+            .and_then(|&(_, chord)| chord)
+    }
+
+    /// What `chord` is bound to. If a hand-edited file bound it twice, the
+    /// first action wins.
+    pub fn action(&self, chord: Chord) -> Option<Action> {
+        self.chords
+            .iter()
+            .find(|&&(_, other)| other == Some(chord))
+            .map(|&(action, _)| action)
+    }
+
+    /// Another action bound to the same chord as `action`.
+    pub fn conflict(&self, action: Action) -> Option<Action> {
+        let chord = self.chord(action)?;
+        self.chords
+            .iter()
+            .find(|&&(other, other_chord)| other != action && other_chord == Some(chord))
+            .map(|&(other, _)| other)
+    }
+
+    /// Binds `action` to `chord`, or unbinds it, unless the chord is taken
+    /// or can't be a shortcut.
+    pub fn set(&mut self, action: Action, chord: Option<Chord>) -> Result<(), String> {
+        if let Some(chord) = chord {
+            if chord.types_text() {
+                return Err(format!(
+                    "{} types text; use Ctrl, Alt or Super, or a function key",
+                    chord
+                ));
+            }
+            if let Some(other) = self.action(chord).filter(|&other| other != action) {
+                return Err(format!("{} is already used for {}", chord, other));
+            }
+        }
+        for (other, other_chord) in &mut self.chords {
+            if *other == action {
+                *other_chord = chord;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn reset(&mut self, action: Action) -> Result<(), String> {
+        self.set(action, Some(action.default_chord()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> Chord {
+        Chord::parse(text).unwrap()
+    }
+
+    fn saved(entries: &[(&str, &str)]) -> Keybindings {
+        entries
+            .iter()
+            .map(|&(action, chord)| (action.to_string(), chord.to_string()))
+            .collect::<BTreeMap<_, _>>()
+            .into()
+    }
+
+    #[test]
+    fn chord_round_trip() {
+        for text in [
+            "Ctrl+Shift+T",
+            "Ctrl+PageDown",
+            "Alt+F4",
+            "F11",
+            "Ctrl+Alt+Shift+Super+Plus",
+            "Super+,",
+            "Ctrl+=",
+            "Ctrl+NumMinus",
+        ] {
+            assert_eq!(chord(text).to_string(), text);
+        }
+        for action in Action::ALL {
+            let default = action.default_chord();
+            assert_eq!(Chord::parse(&default.to_string()), Some(default));
+        }
+    }
+
+    /// Modifiers may come in any order and case, and are shown in one.
+    #[test]
+    fn chord_spelling() {
+        assert_eq!(chord("shift+ctrl+t"), chord("Ctrl+Shift+T"));
+        assert_eq!(chord("Shift+Ctrl+T").to_string(), "Ctrl+Shift+T");
+        assert_eq!(chord("SUPER+alt+pageup").to_string(), "Alt+Super+PageUp");
+        assert_eq!(chord(" Ctrl+Plus ").key_code, KeyCode::Plus);
+        assert_eq!(
+            chord("q"),
+            Chord {
+                key_code: KeyCode::Q,
+                control: false,
+                alt: false,
+                shift: false,
+                logo: false,
+            }
+        );
+
+        assert_eq!(Chord::parse(""), None);
+        assert_eq!(Chord::parse("Ctrl+"), None);
+        assert_eq!(Chord::parse("Ctrl++"), None);
+        assert_eq!(Chord::parse("Hyper+T"), None);
+        assert_eq!(Chord::parse("Ctrl+Shift"), None);
+        assert_eq!(Chord::parse("Ctrl+NoSuchKey"), None);
+    }
+
+    #[test]
+    fn chords_for_keys() {
+        let modifiers = Modifiers::CTRL | Modifiers::SHIFT;
+        assert_eq!(
+            Chord::new(KeyCode::T, modifiers),
+            Some(chord("Ctrl+Shift+T"))
+        );
+        assert_eq!(Chord::new(KeyCode::LShift, modifiers), None);
+
+        assert!(chord("Shift+T").types_text());
+        assert!(chord("Space").types_text());
+        assert!(!chord("Ctrl+T").types_text());
+        assert!(!chord("Super+T").types_text());
+        assert!(!chord("Shift+F5").types_text());
+    }
+
+    #[test]
+    fn conflicts() {
+        let keybindings = Keybindings::default();
+        for action in Action::ALL {
+            assert_eq!(keybindings.conflict(action), None, "{}", action);
+            assert_eq!(keybindings.action(action.default_chord()), Some(action));
+        }
+
+        // Only a hand-edited file can bind a chord twice.
+        let keybindings = saved(&[("find", "Ctrl+Shift+C")]);
+        assert_eq!(keybindings.conflict(Action::Find), Some(Action::Copy));
+        assert_eq!(keybindings.conflict(Action::Copy), Some(Action::Find));
+        assert_eq!(
+            keybindings.action(chord("Ctrl+Shift+C")),
+            Some(Action::Copy)
+        );
+        assert_eq!(keybindings.conflict(Action::Paste), None);
+
+        // Unbound actions conflict with nothing.
+        let keybindings = saved(&[("find", ""), ("copy", "")]);
+        assert_eq!(keybindings.chord(Action::Find), None);
+        assert_eq!(keybindings.conflict(Action::Find), None);
+    }
+
+    #[test]
+    fn setting() {
+        let mut keybindings = Keybindings::default();
+        let error = keybindings
+            .set(Action::Find, Some(chord("Ctrl+Shift+C")))
+            .unwrap_err();
+        assert_eq!(error, "Ctrl+Shift+C is already used for Copy");
+        assert_eq!(keybindings.chord(Action::Find), Some(chord("Ctrl+Shift+F")));
+
+        let error = keybindings
+            .set(Action::Find, Some(chord("Shift+F")))
+            .unwrap_err();
+        assert!(error.contains("types text"), "{}", error);
+
+        // Setting an action's own chord again is fine.
+        keybindings
+            .set(Action::Copy, Some(chord("Ctrl+Shift+C")))
+            .unwrap();
+
+        keybindings.set(Action::Copy, None).unwrap();
+        keybindings
+            .set(Action::Find, Some(chord("Ctrl+Shift+C")))
+            .unwrap();
+        assert_eq!(
+            keybindings.action(chord("Ctrl+Shift+C")),
+            Some(Action::Find)
+        );
+
+        // The default is taken now.
+        assert!(keybindings.reset(Action::Copy).is_err());
+        keybindings.reset(Action::Find).unwrap();
+        keybindings.reset(Action::Copy).unwrap();
+        assert_eq!(keybindings, Keybindings::default());
+    }
+
+    #[test]
+    fn saving() {
+        let mut keybindings = Keybindings::default();
+        keybindings
+            .set(Action::Find, Some(chord("Ctrl+Alt+F")))
+            .unwrap();
+        keybindings.set(Action::Paste, None).unwrap();
+        let map = BTreeMap::from(keybindings.clone());
+        assert_eq!(map["find"], "Ctrl+Alt+F");
+        assert_eq!(map["paste"], "");
+        assert_eq!(map.len(), Action::ALL.len());
+        assert_eq!(Keybindings::from(map), keybindings);
+
+        // Unreadable chords and unknown actions are skipped.
+        let keybindings = saved(&[("find", "Ctrl+Nope"), ("frobnicate", "Ctrl+Alt+X")]);
+        assert_eq!(keybindings, Keybindings::default());
+    }
+}
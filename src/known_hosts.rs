@@ -0,0 +1,529 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    Trusted,
+    Unknown,
+    Changed { path: PathBuf, line: usize },
+    Revoked { path: PathBuf, line: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    None,
+    Revoked,
+    CertAuthority,
+}
+
+struct Entry<'a> {
+    marker: Marker,
+    hosts: &'a str,
+    key_type: &'a str,
+    key: Vec<u8>,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+/// The name OpenSSH looks up in known_hosts: bare host on port 22,
+/// `[host]:port` otherwise.
+fn lookup_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_lowercase()
+    } else {
+        format!("[{}]:{}", host.to_lowercase(), port)
+    }
+}
+
+fn parse_line(line: &str) -> Option<Entry<'_>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let mut first = fields.next()?;
+    let marker = match first {
+        "@revoked" => Marker::Revoked,
+        "@cert-authority" => Marker::CertAuthority,
+        marker if marker.starts_with('@') => return None,
+        _ => Marker::None,
+    };
+    if marker != Marker::None {
+        first = fields.next()?;
+    }
+    let key_type = fields.next()?;
+    let key = STANDARD.decode(fields.next()?).ok()?;
+
+    Some(Entry {
+        marker,
+        hosts: first,
+        key_type,
+        key,
+    })
+}
+
+fn hashed_match(entry: &str, name: &str) -> bool {
+    let mut parts = entry.trim_start_matches("|1|").splitn(2, '|');
+    let (Some(salt), Some(hash)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+    mac.update(name.as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p.eq_ignore_ascii_case(n) => {
+            wildcard_match(&pattern[1..], &name[1..])
+        }
+        _ => false,
+    }
+}
+
+fn hosts_match(hosts: &str, name: &str) -> bool {
+    if hosts.starts_with("|1|") {
+        return hashed_match(hosts, name);
+    }
+
+    let mut matched = false;
+    for pattern in hosts.split(',') {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated.as_bytes(), name.as_bytes()) {
+                return false;
+            }
+        } else if wildcard_match(pattern.as_bytes(), name.as_bytes()) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn check_contents(
+    contents: &str,
+    path: &Path,
+    host: &str,
+    port: u16,
+    key_type: &str,
+    key: &[u8],
+) -> HostKeyStatus {
+    let name = lookup_name(host, port);
+    let mut status = HostKeyStatus::Unknown;
+
+    for (index, line) in contents.lines().enumerate() {
+        let Some(entry) = parse_line(line) else {
+            continue;
+        };
+        if !hosts_match(entry.hosts, &name) {
+            continue;
+        }
+        let same_key = entry.key_type == key_type && entry.key == key;
+
+        match entry.marker {
+            // A revoked key is refused no matter what else the file says.
+            Marker::Revoked if same_key => {
+                return HostKeyStatus::Revoked {
+                    path: path.to_path_buf(),
+                    line: index + 1,
+                }
+            }
+            Marker::Revoked => {}
+            // CA keys only vouch for host certificates, never for a plain
+            // host key, so they can neither trust nor contradict it.
+            Marker::CertAuthority => {}
+            Marker::None if same_key => status = HostKeyStatus::Trusted,
+            Marker::None if entry.key_type == key_type => {
+                if status == HostKeyStatus::Unknown {
+                    status = HostKeyStatus::Changed {
+                        path: path.to_path_buf(),
+                        line: index + 1,
+                    };
+                }
+            }
+            Marker::None => {}
+        }
+    }
+    status
+}
+
+pub fn check(path: &Path, host: &str, port: u16, key_type: &str, key: &[u8]) -> HostKeyStatus {
+    match fs::read_to_string(path) {
+        Ok(contents) => check_contents(&contents, path, host, port, key_type, key),
+        Err(_) => HostKeyStatus::Unknown,
+    }
+}
+
+pub fn append(path: &Path, host: &str, port: u16, key_type: &str, key: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let needs_newline = fs::read(path)
+        .map(|contents| !contents.is_empty() && !contents.ends_with(b"\n"))
+        .unwrap_or(false);
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(
+        file,
+        "{} {} {}",
+        lookup_name(host, port),
+        key_type,
+        STANDARD.encode(key)
+    )
+}
+
+pub fn fingerprint(key: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(key)))
+}
+
+fn read_string(blob: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u32::from_be_bytes(blob.get(..4)?.try_into().ok()?) as usize;
+    let value = blob.get(4..4 + len)?;
+    Some((value, &blob[4 + len..]))
+}
+
+/// The key type a public key blob names in its first field, as
+/// known_hosts spells it. For RSA this is `ssh-rsa` whichever signature
+/// algorithm was negotiated.
+pub fn key_type(key: &[u8]) -> Option<String> {
+    let (key_type, _) = read_string(key)?;
+    String::from_utf8(key_type.to_vec()).ok()
+}
+
+fn key_bits(key_type: &str, key: &[u8]) -> Option<usize> {
+    match key_type {
+        "ssh-ed25519" => Some(256),
+        "ecdsa-sha2-nistp256" => Some(256),
+        "ecdsa-sha2-nistp384" => Some(384),
+        "ecdsa-sha2-nistp521" => Some(521),
+        "ssh-rsa" => {
+            let (_, rest) = read_string(key)?;
+            let (_, rest) = read_string(rest)?;
+            let (modulus, _) = read_string(rest)?;
+            let modulus = match modulus.iter().position(|&b| b != 0) {
+                Some(start) => &modulus[start..],
+                None => return None,
+            };
+            Some(modulus.len() * 8 - modulus[0].leading_zeros() as usize)
+        }
+        _ => None,
+    }
+}
+
+fn short_type(key_type: &str) -> &str {
+    match key_type {
+        "ssh-ed25519" => "ED25519",
+        "ssh-rsa" => "RSA",
+        t if t.starts_with("ecdsa-") => "ECDSA",
+        "ssh-dss" => "DSA",
+        t => t,
+    }
+}
+
+fn border(title: &str, width: usize) -> String {
+    let title = if title.len() > width { "" } else { title };
+    let left = (width - title.len()) / 2;
+    format!(
+        "+{}{}{}+",
+        "-".repeat(left),
+        title,
+        "-".repeat(width - left - title.len())
+    )
+}
+
+/// The "drunken bishop" visualisation `ssh-keygen -lv` prints.
+pub fn randomart(key_type: &str, key: &[u8]) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+    let len = SYMBOLS.len() - 1;
+
+    let mut field = [[0usize; HEIGHT]; WIDTH];
+    let (mut x, mut y) = (WIDTH / 2, HEIGHT / 2);
+
+    for byte in Sha256::digest(key) {
+        let mut input = byte;
+        for _ in 0..4 {
+            x = if input & 0x1 != 0 {
+                (x + 1).min(WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if input & 0x2 != 0 {
+                (y + 1).min(HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            if field[x][y] < len - 2 {
+                field[x][y] += 1;
+            }
+            input >>= 2;
+        }
+    }
+    field[WIDTH / 2][HEIGHT / 2] = len - 1;
+    field[x][y] = len;
+
+    let title = match key_bits(key_type, key) {
+        Some(bits) => format!("[{} {}]", short_type(key_type), bits),
+        None => format!("[{}]", short_type(key_type)),
+    };
+
+    let mut art = border(&title, WIDTH);
+    for row in 0..HEIGHT {
+        art.push_str("\n|");
+        for column in field.iter() {
+            art.push(SYMBOLS[column[row].min(len)] as char);
+        }
+        art.push('|');
+    }
+    art.push('\n');
+    art.push_str(&border("[SHA256]", WIDTH));
+    art
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"the host key";
+    const OTHER_KEY: &[u8] = b"another host key";
+
+    fn line(hosts: &str, key: &[u8]) -> String {
+        format!("{} ssh-ed25519 {}\n", hosts, STANDARD.encode(key))
+    }
+
+    /// `name` hashed the way `HashKnownHosts yes` writes it.
+    fn hashed(name: &str) -> String {
+        let salt = b"twenty bytes of salt";
+        let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+        mac.update(name.as_bytes());
+        let hash = mac.finalize().into_bytes();
+        format!("|1|{}|{}", STANDARD.encode(salt), STANDARD.encode(hash))
+    }
+
+    fn check(contents: &str, host: &str, port: u16) -> HostKeyStatus {
+        check_contents(
+            contents,
+            Path::new("known_hosts"),
+            host,
+            port,
+            "ssh-ed25519",
+            KEY,
+        )
+    }
+
+    fn changed(line: usize) -> HostKeyStatus {
+        HostKeyStatus::Changed {
+            path: PathBuf::from("known_hosts"),
+            line,
+        }
+    }
+
+    fn revoked(line: usize) -> HostKeyStatus {
+        HostKeyStatus::Revoked {
+            path: PathBuf::from("known_hosts"),
+            line,
+        }
+    }
+
+    #[test]
+    fn plain_hosts() {
+        let contents = line("example.com,192.0.2.1", KEY);
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Trusted);
+        assert_eq!(check(&contents, "EXAMPLE.com", 22), HostKeyStatus::Trusted);
+        assert_eq!(check(&contents, "192.0.2.1", 22), HostKeyStatus::Trusted);
+        assert_eq!(check(&contents, "example.org", 22), HostKeyStatus::Unknown);
+        assert_eq!(check("", "example.com", 22), HostKeyStatus::Unknown);
+    }
+
+    #[test]
+    fn ports() {
+        let contents = line("[example.com]:2222", KEY);
+        assert_eq!(
+            check(&contents, "example.com", 2222),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Unknown);
+        assert_eq!(
+            check(&contents, "example.com", 2223),
+            HostKeyStatus::Unknown
+        );
+
+        // A bare name is only for port 22.
+        let contents = line("example.com", KEY);
+        assert_eq!(
+            check(&contents, "example.com", 2222),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn hashed_hosts() {
+        let contents = line(&hashed("example.com"), KEY);
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Trusted);
+        assert_eq!(check(&contents, "example.org", 22), HostKeyStatus::Unknown);
+
+        let contents = line(&hashed("[example.com]:2222"), KEY);
+        assert_eq!(
+            check(&contents, "example.com", 2222),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Unknown);
+
+        assert_eq!(
+            check(&line("|1|bad|salt", KEY), "example.com", 22),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        let contents = line("*.example.com,host?", KEY);
+        for host in ["a.example.com", "a.b.example.com", "host1", "hostx"] {
+            assert_eq!(
+                check(&contents, host, 22),
+                HostKeyStatus::Trusted,
+                "{}",
+                host
+            );
+        }
+        for host in ["example.com", "host", "host12", "a.example.org"] {
+            assert_eq!(
+                check(&contents, host, 22),
+                HostKeyStatus::Unknown,
+                "{}",
+                host
+            );
+        }
+        let contents = line("[*.example.com]:*", KEY);
+        assert_eq!(
+            check(&contents, "a.example.com", 2222),
+            HostKeyStatus::Trusted
+        );
+    }
+
+    #[test]
+    fn negation() {
+        let contents = line("*.example.com,!secret.example.com", KEY);
+        assert_eq!(
+            check(&contents, "www.example.com", 22),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check(&contents, "secret.example.com", 22),
+            HostKeyStatus::Unknown
+        );
+
+        // A negation alone matches nothing.
+        let contents = line("!secret.example.com", KEY);
+        assert_eq!(
+            check(&contents, "www.example.com", 22),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn changed_keys() {
+        let contents = format!("# comment\n\n{}", line("example.com", OTHER_KEY));
+        assert_eq!(check(&contents, "example.com", 22), changed(3));
+
+        // Any line with the right key trusts it, even after a stale one.
+        let contents = format!("{}{}", contents, line("example.com", KEY));
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Trusted);
+
+        // A key of another type says nothing about this one.
+        let contents = format!("example.com ssh-rsa {}\n", STANDARD.encode(OTHER_KEY));
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Unknown);
+    }
+
+    #[test]
+    fn revoked_keys() {
+        let trusted = line("example.com", KEY);
+        let contents = format!("{}@revoked {}", trusted, line("*", KEY));
+        assert_eq!(check(&contents, "example.com", 22), revoked(2));
+
+        let contents = format!("@revoked {}{}", line("*", KEY), trusted);
+        assert_eq!(check(&contents, "example.com", 22), revoked(1));
+
+        // Revoking some other key leaves this one alone.
+        let contents = format!("@revoked {}{}", line("*", OTHER_KEY), trusted);
+        assert_eq!(check(&contents, "example.com", 22), HostKeyStatus::Trusted);
+    }
+
+    #[test]
+    fn cert_authorities() {
+        let contents = format!("@cert-authority {}", line("*.example.com", KEY));
+        assert_eq!(
+            check(&contents, "www.example.com", 22),
+            HostKeyStatus::Unknown
+        );
+
+        let contents = format!("@cert-authority {}", line("*.example.com", OTHER_KEY));
+        assert_eq!(
+            check(&contents, "www.example.com", 22),
+            HostKeyStatus::Unknown
+        );
+
+        // Unknown markers are skipped.
+        let contents = format!("@other {}", line("*.example.com", KEY));
+        assert_eq!(
+            check(&contents, "www.example.com", 22),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    /// An RSA public key blob with a 2048-bit modulus starting `first`.
+    fn rsa_blob(first: u8) -> Vec<u8> {
+        let mut modulus = vec![0, first];
+        modulus.resize(257, 0x5a);
+        let mut blob = Vec::new();
+        for field in [&b"ssh-rsa"[..], &[1, 0, 1], &modulus] {
+            blob.extend((field.len() as u32).to_be_bytes());
+            blob.extend(field);
+        }
+        blob
+    }
+
+    /// The type comes from the key itself, not from the `rsa-sha2-*`
+    /// signature algorithm, so `ssh-rsa` lines match.
+    #[test]
+    fn rsa_keys() {
+        let key = rsa_blob(0xc5);
+        assert_eq!(key_type(&key[..6]), None);
+        let key_type = key_type(&key).unwrap();
+        assert_eq!(key_type, "ssh-rsa");
+
+        let path = Path::new("known_hosts");
+        let contents = format!("example.com ssh-rsa {}\n", STANDARD.encode(&key));
+        let status = check_contents(&contents, path, "example.com", 22, &key_type, &key);
+        assert_eq!(status, HostKeyStatus::Trusted);
+        let other = rsa_blob(0xc6);
+        let status = check_contents(&contents, path, "example.com", 22, &key_type, &other);
+        assert_eq!(status, changed(1));
+
+        assert!(randomart(&key_type, &key).starts_with("+---[RSA 2048]----+"));
+    }
+}
@@ -1,46 +1,1220 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use iced::{
     alignment::{Horizontal, Vertical},
-    widget::{Button, Column, Container, Row, Text, TextInput},
-    Alignment, Element, Length,
+    widget::{Button, Checkbox, Column, Container, Radio, Row, Text, TextInput},
+    Alignment, Command, Element, Length,
 };
 use iced_aw::tab_bar::TabLabel;
+use iced_aw::{Card, Modal};
+use serde::{Deserialize, Serialize};
 
+use crate::config;
+use crate::forward;
+use crate::known_hosts::HostKeyStatus;
+use crate::proxy::{Proxy, ProxyKind, ProxySettings};
+use crate::sessions::{JumpHost, Protocol, SavedSession};
+use crate::ssh::{self, Auth, ConnectParams, Connection, HostKeyCheck, Keepalive, SshSession};
+use crate::ssh_config::{self, HostConfig, JumpSpec};
 use crate::{Icon, Message, Tab};
 
+/// How long typing in the host field pauses before ssh_config is read.
+const RESOLVE_DELAY: Duration = Duration::from_millis(300);
+
 #[derive(Debug, Clone)]
 pub enum LoginMessage {
+    HostChanged(String),
+    HostSettled(String),
+    HostResolved(String, Resolved),
+    PortChanged(String),
     UsernameChanged(String),
     PasswordChanged(String),
+    AuthMethodSelected(AuthMethod),
+    KeyPathChanged(String),
+    BrowseKeyPressed,
+    KeyFilePicked(Option<PathBuf>),
+    PassphraseChanged(String),
+    ForwardAgentToggled(bool),
+    LocalForwardsChanged(String),
+    RemoteForwardsChanged(String),
+    DynamicForwardsChanged(String),
+    AddJumpPressed,
+    Jump(usize, JumpMessage),
+    ProxyKindSelected(ProxyKind),
+    ProxyAddressChanged(String),
+    ProxyUsernameChanged(String),
+    ProxyPasswordChanged(String),
     ClearPressed,
+    SaveSessionPressed,
     LoginPressed,
+    LoginResolved(String, Resolved),
+    SessionResolved(Box<SavedSession>, Resolved),
+    HostKeyReceived(Result<HostKeyCheck, String>),
+    HostKeyDecided(HostKeyDecision),
+    Connected(Result<SshSession, String>),
+    Authenticated(Result<SshSession, String>),
+    ShellOpened(Result<Connection, String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum JumpMessage {
+    HostChanged(String),
+    PortChanged(String),
+    UsernameChanged(String),
+    AuthMethodSelected(AuthMethod),
+    PasswordChanged(String),
+    KeyPathChanged(String),
+    PassphraseChanged(String),
+    RemovePressed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyDecision {
+    AcceptOnce,
+    AcceptAndSave,
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthMethod {
+    #[default]
+    Password,
+    PublicKey,
+    Agent,
+}
+
+impl AuthMethod {
+    pub const ALL: [AuthMethod; 3] = [
+        AuthMethod::Password,
+        AuthMethod::PublicKey,
+        AuthMethod::Agent,
+    ];
+}
+
+impl From<AuthMethod> for String {
+    fn from(method: AuthMethod) -> Self {
+        String::from(match method {
+            AuthMethod::Password => "Password",
+            AuthMethod::PublicKey => "Public key",
+            AuthMethod::Agent => "SSH agent",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LoginState {
+    Idle,
+    InProgress(String),
+    Failed(String),
+    Done(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HopState {
+    Waiting,
+    Connecting,
+    Authenticating,
+    Connected,
+    Failed,
+}
+
+/// ssh_config's settings for a host, the jump hosts its ProxyJump names
+/// and any others on the form, by alias. Reading ssh_config and its
+/// includes can be slow, so this is done off the UI thread.
+#[derive(Debug, Clone, Default)]
+pub struct Resolved(HashMap<String, HostConfig>);
+
+impl Resolved {
+    fn read(host: &str, jumps: &[String]) -> Self {
+        let mut configs = HashMap::new();
+        for alias in jumps {
+            configs
+                .entry(alias.clone())
+                .or_insert_with(|| ssh_config::resolve(alias));
+        }
+        let config = ssh_config::resolve(host);
+        for spec in config
+            .proxy_jump
+            .as_deref()
+            .and_then(ssh_config::parse_proxy_jump)
+            .unwrap_or_default()
+        {
+            configs
+                .entry(spec.host.clone())
+                .or_insert_with(|| ssh_config::resolve(&spec.host));
+        }
+        configs.insert(host.to_string(), config);
+        Resolved(configs)
+    }
+
+    fn get(&self, alias: &str) -> HostConfig {
+        self.0.get(alias).cloned().unwrap_or_default()
+    }
+
+    fn contains(&self, alias: &str) -> bool {
+        self.0.contains_key(alias)
+    }
+}
+
+/// Reads ssh_config for `host` and the `jumps` aliases on a blocking
+/// thread.
+async fn resolve(host: String, jumps: Vec<String>) -> Resolved {
+    let alias = host.trim().to_string();
+    tokio::task::spawn_blocking(move || Resolved::read(&alias, &jumps))
+        .await
+        .unwrap_or_default()
+}
+
+/// A jump host as entered on the form, with its own credentials.
+#[derive(Debug, Clone, Default)]
+struct JumpForm {
+    host: String,
+    port: String,
+    username: String,
+    auth_method: AuthMethod,
+    password: String,
+    key_path: String,
+    passphrase: String,
+}
+
+impl JumpForm {
+    /// A hop from a `ProxyJump` line, with the key its ssh_config `config`
+    /// names or else the agent.
+    fn from_spec(spec: &JumpSpec, config: &HostConfig) -> Self {
+        let identity = config.identity_files.iter().find(|path| path.exists());
+        let auth_method = if identity.is_some() {
+            AuthMethod::PublicKey
+        } else if std::env::var_os("SSH_AUTH_SOCK").is_some() {
+            AuthMethod::Agent
+        } else {
+            AuthMethod::Password
+        };
+        JumpForm {
+            host: spec.host.clone(),
+            port: spec.port.map(|port| port.to_string()).unwrap_or_default(),
+            username: spec.user.clone().unwrap_or_default(),
+            auth_method,
+            key_path: identity
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(default_key_path),
+            ..JumpForm::default()
+        }
+    }
+
+    fn from_saved(hop: &JumpHost) -> Self {
+        JumpForm {
+            host: hop.host.clone(),
+            port: hop.port.map(|port| port.to_string()).unwrap_or_default(),
+            username: hop.username.clone(),
+            auth_method: hop.auth_method,
+            key_path: if hop.key_path.is_empty() {
+                default_key_path()
+            } else {
+                hop.key_path.clone()
+            },
+            ..JumpForm::default()
+        }
+    }
+
+    fn saved(&self) -> JumpHost {
+        JumpHost {
+            host: self.host.trim().to_string(),
+            port: self.port.trim().parse().ok(),
+            username: self.username.trim().to_string(),
+            auth_method: self.auth_method,
+            key_path: match self.auth_method {
+                AuthMethod::PublicKey => self.key_path.trim().to_string(),
+                _ => String::new(),
+            },
+        }
+    }
+
+    /// The hop's connection settings over ssh_config's `config` for it.
+    fn params(&self, config: &HostConfig, keepalive: Keepalive) -> Result<ConnectParams, String> {
+        let alias = self.host.trim();
+        if alias.is_empty() {
+            return Err(String::from("Host is required"));
+        }
+        let auth = auth(
+            self.auth_method,
+            &self.password,
+            &self.key_path,
+            &self.passphrase,
+        )?;
+        hop_params(alias, config, &self.port, &self.username, auth, keepalive)
+    }
+
+    fn update(&mut self, message: JumpMessage) {
+        match message {
+            JumpMessage::HostChanged(value) => self.host = value,
+            JumpMessage::PortChanged(value) => self.port = value,
+            JumpMessage::UsernameChanged(value) => self.username = value,
+            JumpMessage::AuthMethodSelected(method) => self.auth_method = method,
+            JumpMessage::PasswordChanged(value) => self.password = value,
+            JumpMessage::KeyPathChanged(value) => self.key_path = value,
+            JumpMessage::PassphraseChanged(value) => self.passphrase = value,
+            // Handled by the login tab, which owns the list.
+            JumpMessage::RemovePressed => {}
+        }
+    }
+
+    fn view(&self) -> Element<'_, JumpMessage> {
+        let mut column = Column::new()
+            .spacing(8)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        TextInput::new("Jump host", &self.host)
+                            .on_input(JumpMessage::HostChanged)
+                            .padding(8),
+                    )
+                    .push(
+                        TextInput::new("22", &self.port)
+                            .on_input(JumpMessage::PortChanged)
+                            .padding(8)
+                            .width(Length::Fixed(80.0)),
+                    )
+                    .push(
+                        TextInput::new("Username", &self.username)
+                            .on_input(JumpMessage::UsernameChanged)
+                            .padding(8),
+                    )
+                    .push(Button::new(Text::new("Remove")).on_press(JumpMessage::RemovePressed)),
+            )
+            .push(
+                AuthMethod::ALL
+                    .iter()
+                    .cloned()
+                    .fold(Row::new().spacing(10), |row, method| {
+                        row.push(
+                            Radio::new(
+                                method,
+                                method,
+                                Some(self.auth_method),
+                                JumpMessage::AuthMethodSelected,
+                            )
+                            .size(16),
+                        )
+                    }),
+            );
+        match self.auth_method {
+            AuthMethod::Password => {
+                column = column.push(
+                    TextInput::new("Password", &self.password)
+                        .on_input(JumpMessage::PasswordChanged)
+                        .padding(8)
+                        .password(),
+                );
+            }
+            AuthMethod::PublicKey => {
+                column = column
+                    .push(
+                        TextInput::new("Private key file", &self.key_path)
+                            .on_input(JumpMessage::KeyPathChanged)
+                            .padding(8),
+                    )
+                    .push(
+                        TextInput::new("Passphrase (if encrypted)", &self.passphrase)
+                            .on_input(JumpMessage::PassphraseChanged)
+                            .padding(8)
+                            .password(),
+                    );
+            }
+            AuthMethod::Agent => {}
+        }
+        column.into()
+    }
+}
+
+fn auth(
+    method: AuthMethod,
+    password: &str,
+    key_path: &str,
+    passphrase: &str,
+) -> Result<Auth, String> {
+    Ok(match method {
+        AuthMethod::Password => Auth::Password(password.to_string()),
+        AuthMethod::PublicKey => {
+            if key_path.trim().is_empty() {
+                return Err(String::from("Select a private key file"));
+            }
+            Auth::PublicKey {
+                path: PathBuf::from(key_path.trim()),
+                passphrase: (!passphrase.is_empty()).then(|| passphrase.to_string()),
+            }
+        }
+        AuthMethod::Agent => Auth::Agent,
+    })
+}
+
+/// Resolves one host the way `ssh` would: anything typed on the form
+/// overrides ssh_config.
+fn hop_params(
+    alias: &str,
+    config: &HostConfig,
+    port: &str,
+    username: &str,
+    auth: Auth,
+    keepalive: Keepalive,
+) -> Result<ConnectParams, String> {
+    let host = config.host_name.as_deref().unwrap_or(alias);
+    let port = match port.trim() {
+        "" => config.port.unwrap_or(22),
+        port => port
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid port: {}", port))?,
+    };
+    let username = match username.trim() {
+        "" => config
+            .user
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .ok_or_else(|| String::from("Username is required"))?,
+        username => username.to_string(),
+    };
+    Ok(ConnectParams {
+        host: host.to_string(),
+        port,
+        username,
+        auth,
+        forward_agent: false,
+        keepalive: Keepalive {
+            interval: match config.server_alive_interval {
+                Some(0) => None,
+                Some(seconds) => Some(Duration::from_secs(seconds)),
+                None => keepalive.interval,
+            },
+            max: config.server_alive_count_max.unwrap_or(keepalive.max),
+        },
+        local_forwards: Vec::new(),
+        remote_forwards: Vec::new(),
+        dynamic_forwards: Vec::new(),
+        proxy: config.proxy_command.clone().map(Proxy::Command),
+        jumps: Vec::new(),
+    })
 }
 
 pub struct LoginTab {
+    host: String,
+    port: String,
     username: String,
     password: String,
+    host_config: Option<HostConfig>,
+    /// The host `host_config` was resolved for, which lags behind `host`
+    /// while it is being typed.
+    resolved_host: String,
+    auth_method: AuthMethod,
+    key_path: String,
+    passphrase: String,
+    forward_agent: bool,
+    local_forwards: String,
+    remote_forwards: String,
+    dynamic_forwards: String,
+    jumps: Vec<JumpForm>,
+    /// Whether `jumps` were filled in from the host's ProxyJump rather than
+    /// by hand, so changing the host may replace them.
+    jumps_from_config: bool,
+    proxy: ProxySettings,
+    proxy_password: String,
+    /// Used for hosts ssh_config says nothing about.
+    keepalive: Keepalive,
+    pending_host_key: Option<HostKeyCheck>,
+    state: LoginState,
+    /// Every host being connected, jump hosts first.
+    route: Vec<ConnectParams>,
+    hop: usize,
+    hop_states: Vec<HopState>,
 }
 
 impl LoginTab {
     pub fn new() -> Self {
         LoginTab {
+            host: String::new(),
+            port: String::new(),
             username: String::new(),
             password: String::new(),
+            host_config: None,
+            resolved_host: String::new(),
+            auth_method: AuthMethod::default(),
+            key_path: default_key_path(),
+            passphrase: String::new(),
+            forward_agent: false,
+            local_forwards: String::new(),
+            remote_forwards: String::new(),
+            dynamic_forwards: String::new(),
+            jumps: Vec::new(),
+            jumps_from_config: false,
+            proxy: ProxySettings::default(),
+            proxy_password: String::new(),
+            keepalive: config::Connection::default().keepalive(),
+            pending_host_key: None,
+            state: LoginState::Idle,
+            route: Vec::new(),
+            hop: 0,
+            hop_states: Vec::new(),
+        }
+    }
+
+    pub fn set_keepalive(&mut self, keepalive: Keepalive) {
+        self.keepalive = keepalive;
+    }
+
+    fn busy(&self) -> bool {
+        matches!(self.state, LoginState::InProgress(_))
+    }
+
+    /// Fills the form from a saved session once ssh_config has been read
+    /// for its host and, unless a password is needed, starts connecting.
+    pub fn load_session(&mut self, session: &SavedSession) -> Command<LoginMessage> {
+        if self.busy() {
+            return Command::none();
+        }
+        let _ = self.update(LoginMessage::ClearPressed);
+        self.host = session.host.clone();
+        let session = Box::new(session.clone());
+        Command::perform(resolve(self.host.clone(), Vec::new()), move |resolved| {
+            LoginMessage::SessionResolved(session, resolved)
+        })
+    }
+
+    /// The rest of `load_session`: what the session says wins over
+    /// ssh_config.
+    fn fill_session(
+        &mut self,
+        session: &SavedSession,
+        resolved: &Resolved,
+    ) -> Command<LoginMessage> {
+        self.host = session.host.clone();
+        self.apply_host_config(self.host.clone(), Some(resolved));
+        self.port = session
+            .port
+            .map(|port| port.to_string())
+            .unwrap_or_default();
+        self.username = session.username.clone();
+        self.auth_method = session.auth_method;
+        if !session.key_path.is_empty() {
+            self.key_path = session.key_path.clone();
+        }
+        self.forward_agent = session.forward_agent;
+        self.local_forwards = forward::format_forward_list(&session.local_forwards);
+        self.remote_forwards = forward::format_forward_list(&session.remote_forwards);
+        self.dynamic_forwards = forward::format_forward_list(&session.dynamic_forwards);
+        if !session.jump_hosts.is_empty() {
+            self.jumps = session
+                .jump_hosts
+                .iter()
+                .map(JumpForm::from_saved)
+                .collect();
+            self.jumps_from_config = false;
+        }
+        self.proxy = session.proxy.clone();
+
+        let needs_password = self.auth_method == AuthMethod::Password
+            || self.proxy.needs_password()
+            || self
+                .jumps
+                .iter()
+                .any(|jump| jump.auth_method == AuthMethod::Password);
+        if needs_password {
+            Command::none()
+        } else {
+            self.update(LoginMessage::LoginPressed)
+        }
+    }
+
+    /// The form as a session entry, for saving to the session tree.
+    pub fn saved_session(&self) -> SavedSession {
+        let host = self.host.trim();
+        SavedSession {
+            name: host.to_string(),
+            protocol: Protocol::Ssh,
+            host: host.to_string(),
+            port: self.port.trim().parse().ok(),
+            username: self.username.trim().to_string(),
+            auth_method: self.auth_method,
+            key_path: match self.auth_method {
+                AuthMethod::PublicKey => self.key_path.trim().to_string(),
+                _ => String::new(),
+            },
+            forward_agent: self.forward_agent,
+            local_forwards: forward::parse_forward_list(&self.local_forwards).unwrap_or_default(),
+            remote_forwards: forward::parse_forward_list(&self.remote_forwards).unwrap_or_default(),
+            dynamic_forwards: forward::parse_dynamic_list(&self.dynamic_forwards)
+                .unwrap_or_default(),
+            // Jump hosts from ssh_config stay there.
+            jump_hosts: if self.jumps_from_config {
+                Vec::new()
+            } else {
+                self.jumps.iter().map(JumpForm::saved).collect()
+            },
+            proxy: self.proxy.clone(),
+            ..SavedSession::default()
+        }
+    }
+
+    /// The route to connect along, once ssh_config has been read for `host`
+    /// and `resolved` for every jump host.
+    fn params(&self, resolved: &Resolved) -> Result<ConnectParams, String> {
+        let alias = self.host.trim();
+        if alias.is_empty() {
+            return Err(String::from("Host is required"));
+        }
+        let config = self.host_config.clone().unwrap_or_default();
+        let auth = auth(
+            self.auth_method,
+            &self.password,
+            &self.key_path,
+            &self.passphrase,
+        )?;
+        let mut params = hop_params(
+            alias,
+            &config,
+            &self.port,
+            &self.username,
+            auth,
+            self.keepalive,
+        )?;
+        params.forward_agent = self.forward_agent;
+
+        params.local_forwards = forward::parse_forward_list(&self.local_forwards)?;
+        params
+            .local_forwards
+            .extend(config.local_forwards.iter().cloned());
+        params.remote_forwards = forward::parse_forward_list(&self.remote_forwards)?;
+        params
+            .remote_forwards
+            .extend(config.remote_forwards.iter().cloned());
+        params.dynamic_forwards = forward::parse_dynamic_list(&self.dynamic_forwards)?;
+        params
+            .dynamic_forwards
+            .extend(config.dynamic_forwards.iter().cloned());
+
+        params.jumps = self
+            .jumps
+            .iter()
+            .enumerate()
+            .map(|(index, jump)| {
+                jump.params(&resolved.get(jump.host.trim()), self.keepalive)
+                    .map_err(|e| format!("Jump host {}: {}", index + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+
+        // A proxy set here takes over from ssh_config for the first hop.
+        if let Some(proxy) = self.proxy.proxy(&self.proxy_password)? {
+            match params.jumps.first_mut() {
+                Some(first) => first.proxy = Some(proxy),
+                None => params.proxy = Some(proxy),
+            }
+        }
+        Ok(params)
+    }
+
+    /// Takes in ssh_config's settings for `host`: its key, and its ProxyJump
+    /// hops unless jump hosts were entered by hand.
+    fn apply_host_config(&mut self, host: String, resolved: Option<&Resolved>) {
+        let config = resolved.map(|resolved| resolved.get(host.trim()));
+        if let Some(identity) = config
+            .iter()
+            .flat_map(|config| config.identity_files.iter())
+            .find(|path| path.exists())
+        {
+            self.key_path = identity.display().to_string();
+        }
+        if self.jumps.is_empty() || self.jumps_from_config {
+            self.jumps = config
+                .as_ref()
+                .and_then(|config| config.proxy_jump.as_deref())
+                .and_then(ssh_config::parse_proxy_jump)
+                .unwrap_or_default()
+                .iter()
+                .map(|spec| {
+                    let config = resolved.map(|resolved| resolved.get(&spec.host));
+                    JumpForm::from_spec(spec, &config.unwrap_or_default())
+                })
+                .collect();
+            self.jumps_from_config = !self.jumps.is_empty();
+        }
+        self.host_config = config;
+        self.resolved_host = host;
+    }
+
+    /// Starts connecting to the first hop of the route on the form.
+    fn connect(&mut self, resolved: &Resolved) -> Command<LoginMessage> {
+        match self.params(resolved) {
+            Ok(mut params) => {
+                self.route = std::mem::take(&mut params.jumps);
+                self.route.push(params);
+                self.hop = 0;
+                self.hop_states = vec![HopState::Waiting; self.route.len()];
+                self.hop_states[0] = HopState::Connecting;
+                let first = self.route[0].clone();
+                self.state = LoginState::InProgress(format!(
+                    "Connecting to {}:{}...",
+                    first.host, first.port
+                ));
+                Command::perform(ssh::connect(first), LoginMessage::HostKeyReceived)
+            }
+            Err(e) => {
+                self.route = Vec::new();
+                self.hop_states = Vec::new();
+                self.state = LoginState::Failed(e);
+                Command::none()
+            }
+        }
+    }
+
+    fn hop_label(&self, hop: usize) -> String {
+        match self.route.get(hop) {
+            Some(params) => format!("{}@{}:{}", params.username, params.host, params.port),
+            None => String::new(),
+        }
+    }
+
+    /// Marks the hop being connected as failed. With jump hosts the error
+    /// says which hop it came from.
+    fn fail(&mut self, error: String) {
+        if let Some(state) = self.hop_states.get_mut(self.hop) {
+            *state = HopState::Failed;
+        }
+        self.state = LoginState::Failed(if self.route.len() > 1 {
+            format!(
+                "Hop {} of {} ({}): {}",
+                self.hop + 1,
+                self.route.len(),
+                self.hop_label(self.hop),
+                error
+            )
+        } else {
+            error
+        });
+    }
+
+    fn set_hop_state(&mut self, state: HopState) {
+        if let Some(current) = self.hop_states.get_mut(self.hop) {
+            *current = state;
         }
     }
 
-    pub fn update(&mut self, message: LoginMessage) {
+    pub fn update(&mut self, message: LoginMessage) -> Command<LoginMessage> {
         match message {
+            // ssh_config and its includes are read off the UI thread, once
+            // typing pauses.
+            LoginMessage::HostChanged(value) => {
+                self.host = value.clone();
+                if value.trim().is_empty() {
+                    self.apply_host_config(value, None);
+                    return Command::none();
+                }
+                return Command::perform(tokio::time::sleep(RESOLVE_DELAY), move |_| {
+                    LoginMessage::HostSettled(value)
+                });
+            }
+            LoginMessage::HostSettled(value) => {
+                if value != self.host {
+                    return Command::none();
+                }
+                return Command::perform(resolve(value.clone(), Vec::new()), move |resolved| {
+                    LoginMessage::HostResolved(value, resolved)
+                });
+            }
+            LoginMessage::HostResolved(value, resolved) => {
+                if value == self.host {
+                    self.apply_host_config(value, Some(&resolved));
+                }
+            }
+            LoginMessage::PortChanged(value) => self.port = value,
             LoginMessage::UsernameChanged(value) => self.username = value,
             LoginMessage::PasswordChanged(value) => self.password = value,
+            LoginMessage::AuthMethodSelected(method) => self.auth_method = method,
+            LoginMessage::KeyPathChanged(value) => self.key_path = value,
+            LoginMessage::BrowseKeyPressed => {
+                let mut dialog = rfd::AsyncFileDialog::new().set_title("Select private key");
+                if let Some(dir) = dirs::home_dir().map(|home| home.join(".ssh")) {
+                    dialog = dialog.set_directory(dir);
+                }
+                return Command::perform(
+                    async move {
+                        dialog
+                            .pick_file()
+                            .await
+                            .map(|file| file.path().to_path_buf())
+                    },
+                    LoginMessage::KeyFilePicked,
+                );
+            }
+            LoginMessage::KeyFilePicked(Some(path)) => self.key_path = path.display().to_string(),
+            LoginMessage::KeyFilePicked(None) => {}
+            LoginMessage::PassphraseChanged(value) => self.passphrase = value,
+            LoginMessage::ForwardAgentToggled(value) => self.forward_agent = value,
+            LoginMessage::LocalForwardsChanged(value) => self.local_forwards = value,
+            LoginMessage::RemoteForwardsChanged(value) => self.remote_forwards = value,
+            LoginMessage::DynamicForwardsChanged(value) => self.dynamic_forwards = value,
+            LoginMessage::ProxyKindSelected(kind) => self.proxy.kind = kind,
+            LoginMessage::ProxyAddressChanged(value) => self.proxy.address = value,
+            LoginMessage::ProxyUsernameChanged(value) => self.proxy.username = value,
+            LoginMessage::ProxyPasswordChanged(value) => self.proxy_password = value,
+            LoginMessage::AddJumpPressed => {
+                self.jumps.push(JumpForm {
+                    key_path: default_key_path(),
+                    ..JumpForm::default()
+                });
+                self.jumps_from_config = false;
+            }
+            LoginMessage::Jump(index, JumpMessage::RemovePressed) => {
+                if index < self.jumps.len() {
+                    self.jumps.remove(index);
+                }
+                self.jumps_from_config = false;
+            }
+            LoginMessage::Jump(index, message) => {
+                if let Some(jump) = self.jumps.get_mut(index) {
+                    jump.update(message);
+                }
+                self.jumps_from_config = false;
+            }
+            // Replies to a connect in flight would land on the cleared route.
+            LoginMessage::ClearPressed if self.busy() => {}
             LoginMessage::ClearPressed => {
+                self.host = String::new();
+                self.port = String::new();
                 self.username = String::new();
+                self.host_config = None;
+                self.resolved_host = String::new();
+                self.password = String::new();
+                self.key_path = default_key_path();
+                self.passphrase = String::new();
+                self.forward_agent = false;
+                self.local_forwards = String::new();
+                self.remote_forwards = String::new();
+                self.dynamic_forwards = String::new();
+                self.jumps = Vec::new();
+                self.jumps_from_config = false;
+                self.proxy = ProxySettings::default();
+                self.proxy_password = String::new();
+                self.state = LoginState::Idle;
+                self.route = Vec::new();
+                self.hop_states = Vec::new();
+            }
+            // Handled by the application, which owns the session tree.
+            LoginMessage::SaveSessionPressed => {}
+            LoginMessage::LoginPressed => {
+                if self.busy() {
+                    return Command::none();
+                }
+                // Enter may come before ssh_config has been read for the host,
+                // and the jump hosts are only read now, all in one go.
+                let jumps: Vec<String> = self
+                    .jumps
+                    .iter()
+                    .map(|jump| jump.host.trim().to_string())
+                    .filter(|alias| !alias.is_empty())
+                    .collect();
+                let stale = self.resolved_host != self.host && !self.host.trim().is_empty();
+                if !stale && jumps.is_empty() {
+                    return self.connect(&Resolved::default());
+                }
+                self.state = LoginState::InProgress(String::from("Reading ssh_config..."));
+                let host = self.host.clone();
+                return Command::perform(resolve(host.clone(), jumps), move |resolved| {
+                    LoginMessage::LoginResolved(host, resolved)
+                });
+            }
+            LoginMessage::LoginResolved(value, resolved) => {
+                self.state = LoginState::Idle;
+                // The host was edited while ssh_config was being read.
+                if value != self.host {
+                    return self.update(LoginMessage::LoginPressed);
+                }
+                if self.resolved_host != self.host {
+                    self.apply_host_config(value, Some(&resolved));
+                }
+                // Likewise if a jump host was.
+                let edited = self.jumps.iter().any(|jump| {
+                    let alias = jump.host.trim();
+                    !alias.is_empty() && !resolved.contains(alias)
+                });
+                if edited {
+                    return self.update(LoginMessage::LoginPressed);
+                }
+                return self.connect(&resolved);
+            }
+            LoginMessage::SessionResolved(session, resolved) => {
+                if !self.busy() {
+                    return self.fill_session(&session, &resolved);
+                }
+            }
+            LoginMessage::HostKeyReceived(Ok(check)) => match &check.status {
+                HostKeyStatus::Trusted => {
+                    self.state = LoginState::InProgress(String::from("Host key verified"));
+                    return Command::perform(check.accept(false), LoginMessage::Connected);
+                }
+                HostKeyStatus::Unknown => {
+                    self.state = LoginState::InProgress(String::from(
+                        "Waiting for host key confirmation...",
+                    ));
+                    self.pending_host_key = Some(check);
+                }
+                HostKeyStatus::Changed { path, line } => {
+                    check.reject();
+                    self.fail(format!(
+                        "WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!\n\
+                         Someone could be eavesdropping on you right now (man-in-the-middle \
+                         attack), or the host key has just been changed.\n\
+                         The {} key sent by {} has fingerprint {}.\n\
+                         Offending key in {}:{}. Connection refused.",
+                        check.key_type,
+                        check.host,
+                        check.fingerprint,
+                        path.display(),
+                        line
+                    ));
+                }
+                HostKeyStatus::Revoked { path, line } => {
+                    check.reject();
+                    self.fail(format!(
+                        "The {} key sent by {} ({}) is marked as revoked in {}:{}. \
+                         Connection refused.",
+                        check.key_type,
+                        check.host,
+                        check.fingerprint,
+                        path.display(),
+                        line
+                    ));
+                }
+            },
+            LoginMessage::HostKeyDecided(decision) => {
+                let Some(check) = self.pending_host_key.take() else {
+                    return Command::none();
+                };
+                match decision {
+                    HostKeyDecision::AcceptOnce | HostKeyDecision::AcceptAndSave => {
+                        self.state = LoginState::InProgress(String::from("Host key accepted"));
+                        return Command::perform(
+                            check.accept(decision == HostKeyDecision::AcceptAndSave),
+                            LoginMessage::Connected,
+                        );
+                    }
+                    HostKeyDecision::Reject => {
+                        check.reject();
+                        self.fail(format!("Host key for {} rejected", check.host));
+                    }
+                }
+            }
+            LoginMessage::Connected(Ok(session)) => {
+                self.set_hop_state(HopState::Authenticating);
+                self.state =
+                    LoginState::InProgress(format!("Authenticating as {}...", session.username()));
+                return Command::perform(session.authenticate(), LoginMessage::Authenticated);
+            }
+            LoginMessage::Authenticated(Ok(session)) => {
+                self.set_hop_state(HopState::Connected);
+                if let Some(next) = self.route.get(self.hop + 1).cloned() {
+                    self.hop += 1;
+                    self.set_hop_state(HopState::Connecting);
+                    self.state = LoginState::InProgress(format!(
+                        "Connecting to {}:{} via {}...",
+                        next.host,
+                        next.port,
+                        self.hop_label(self.hop - 1)
+                    ));
+                    return Command::perform(
+                        ssh::connect_via(session, next),
+                        LoginMessage::HostKeyReceived,
+                    );
+                }
+                self.state = LoginState::InProgress(String::from("Opening shell..."));
+                return Command::perform(session.open_shell(), LoginMessage::ShellOpened);
+            }
+            LoginMessage::ShellOpened(Ok(connection)) => {
                 self.password = String::new();
+                self.passphrase = String::new();
+                self.proxy_password = String::new();
+                for jump in &mut self.jumps {
+                    jump.password = String::new();
+                    jump.passphrase = String::new();
+                }
+                self.state = LoginState::Done(format!("Connected to {}", connection.title()));
+            }
+            LoginMessage::HostKeyReceived(Err(e))
+            | LoginMessage::Connected(Err(e))
+            | LoginMessage::Authenticated(Err(e))
+            | LoginMessage::ShellOpened(Err(e)) => self.fail(e),
+        }
+        Command::none()
+    }
+
+    fn host_config_hint(&self) -> Text<'_> {
+        let Some(config) = &self.host_config else {
+            return Text::new("");
+        };
+        let alias = self.resolved_host.trim();
+        let host = config.host_name.as_deref().unwrap_or(alias);
+        let says_nothing = host == alias
+            && config.user.is_none()
+            && config.port.is_none()
+            && config.proxy_jump.is_none()
+            && config.proxy_command.is_none()
+            && config.local_forwards.is_empty()
+            && config.remote_forwards.is_empty()
+            && config.dynamic_forwards.is_empty();
+        if says_nothing {
+            return Text::new("");
+        }
+
+        let mut hint = format!("{} -> ", alias);
+        if let Some(user) = &config.user {
+            hint.push_str(&format!("{}@", user));
+        }
+        hint.push_str(host);
+        if let Some(port) = config.port {
+            hint.push_str(&format!(":{}", port));
+        }
+        if let Some(jump) = &config.proxy_jump {
+            hint.push_str(&format!(" via {}", jump));
+        }
+        if config.proxy_command.is_some() {
+            hint.push_str(" via ProxyCommand");
+        }
+        if !config.local_forwards.is_empty() {
+            hint.push_str(&format!(
+                ", {} local forward(s)",
+                config.local_forwards.len()
+            ));
+        }
+        if !config.remote_forwards.is_empty() {
+            hint.push_str(&format!(
+                ", {} remote forward(s)",
+                config.remote_forwards.len()
+            ));
+        }
+        if !config.dynamic_forwards.is_empty() {
+            hint.push_str(&format!(
+                ", {} SOCKS forward(s)",
+                config.dynamic_forwards.len()
+            ));
+        }
+        Text::new(hint).size(16)
+    }
+
+    fn jumps_view(&self) -> Element<'_, LoginMessage> {
+        let header = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("Jump hosts").width(Length::Fill))
+            .push(Button::new(Text::new("Add jump host")).on_press(LoginMessage::AddJumpPressed));
+        self.jumps
+            .iter()
+            .enumerate()
+            .fold(
+                Column::new().spacing(12).push(header),
+                |column, (index, jump)| {
+                    column.push(
+                        jump.view()
+                            .map(move |message| LoginMessage::Jump(index, message)),
+                    )
+                },
+            )
+            .into()
+    }
+
+    fn proxy_view(&self) -> Element<'_, LoginMessage> {
+        let kinds = ProxyKind::ALL
+            .iter()
+            .cloned()
+            .fold(Row::new().spacing(10), |row, kind| {
+                row.push(
+                    Radio::new(
+                        kind,
+                        kind,
+                        Some(self.proxy.kind),
+                        LoginMessage::ProxyKindSelected,
+                    )
+                    .size(16),
+                )
+            });
+        let column = Column::new().spacing(8).push(kinds);
+        match self.proxy.kind {
+            ProxyKind::None => column.into(),
+            ProxyKind::Command => column
+                .push(
+                    TextInput::new(
+                        "ProxyCommand, e.g. nc -X connect -x proxy:3128 %h %p",
+                        &self.proxy.address,
+                    )
+                    .on_input(LoginMessage::ProxyAddressChanged)
+                    .padding(8),
+                )
+                .into(),
+            ProxyKind::Http | ProxyKind::Socks5 => column
+                .push(
+                    TextInput::new("Proxy host:port", &self.proxy.address)
+                        .on_input(LoginMessage::ProxyAddressChanged)
+                        .padding(8),
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            TextInput::new("Proxy username (optional)", &self.proxy.username)
+                                .on_input(LoginMessage::ProxyUsernameChanged)
+                                .padding(8),
+                        )
+                        .push(
+                            TextInput::new("Proxy password", &self.proxy_password)
+                                .on_input(LoginMessage::ProxyPasswordChanged)
+                                .padding(8)
+                                .password(),
+                        ),
+                )
+                .into(),
+        }
+    }
+
+    /// How far each hop got, shown while connecting through jump hosts.
+    fn hops_view(&self) -> Element<'_, LoginMessage> {
+        if self.route.len() < 2 {
+            return Column::new().into();
+        }
+        self.hop_states
+            .iter()
+            .enumerate()
+            .fold(Column::new().spacing(4), |column, (hop, state)| {
+                let state = match state {
+                    HopState::Waiting => "waiting",
+                    HopState::Connecting => "connecting...",
+                    HopState::Authenticating => "authenticating...",
+                    HopState::Connected => "connected",
+                    HopState::Failed => "failed",
+                };
+                column.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            Text::new(format!("{}. {}", hop + 1, self.hop_label(hop)))
+                                .size(16)
+                                .width(Length::Fill),
+                        )
+                        .push(Text::new(state).size(16)),
+                )
+            })
+            .into()
+    }
+
+    fn credentials(&self) -> Element<'_, LoginMessage> {
+        match self.auth_method {
+            AuthMethod::Password => TextInput::new("Password", &self.password)
+                .on_input(LoginMessage::PasswordChanged)
+                .on_submit(LoginMessage::LoginPressed)
+                .padding(10)
+                .size(32)
+                .password()
+                .into(),
+            AuthMethod::PublicKey => Column::new()
+                .spacing(16)
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .align_items(Alignment::Center)
+                        .push(
+                            TextInput::new("Private key file", &self.key_path)
+                                .on_input(LoginMessage::KeyPathChanged)
+                                .padding(10)
+                                .size(20),
+                        )
+                        .push(
+                            Button::new(Text::new("Browse..."))
+                                .on_press(LoginMessage::BrowseKeyPressed),
+                        ),
+                )
+                .push(
+                    TextInput::new("Passphrase (if encrypted)", &self.passphrase)
+                        .on_input(LoginMessage::PassphraseChanged)
+                        .on_submit(LoginMessage::LoginPressed)
+                        .padding(10)
+                        .size(32)
+                        .password(),
+                )
+                .into(),
+            AuthMethod::Agent => {
+                let agent = match std::env::var("SSH_AUTH_SOCK") {
+                    Ok(socket) => format!("Using the agent at {}", socket),
+                    Err(_) => String::from("SSH_AUTH_SOCK is not set, no agent is running"),
+                };
+                Text::new(agent).into()
             }
-            LoginMessage::LoginPressed => {}
         }
     }
 }
 
+fn monospace<'a>(text: &str) -> Element<'a, LoginMessage> {
+    text.lines()
+        .fold(Column::new(), |column, line| {
+            column.push(line.chars().fold(Row::new(), |row, c| {
+                row.push(
+                    Text::new(c.to_string())
+                        .width(Length::Fixed(9.0))
+                        .horizontal_alignment(Horizontal::Center),
+                )
+            }))
+        })
+        .into()
+}
+
+fn host_key_prompt(check: &HostKeyCheck) -> Element<'_, LoginMessage> {
+    let decision_button = |label, decision| {
+        Button::new(Text::new(label).horizontal_alignment(Horizontal::Center))
+            .width(Length::Fill)
+            .on_press(LoginMessage::HostKeyDecided(decision))
+    };
+
+    Card::new(
+        Text::new("Unknown host key"),
+        Column::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new(format!(
+                "The authenticity of host '{}' (port {}) can't be established.",
+                check.host, check.port
+            )))
+            .push(Text::new(format!(
+                "{} key fingerprint is {}",
+                check.key_type, check.fingerprint
+            )))
+            .push(monospace(&check.randomart))
+            .push(Text::new("Do you trust this host?")),
+    )
+    .foot(
+        Row::new()
+            .spacing(10)
+            .push(decision_button("Reject", HostKeyDecision::Reject))
+            .push(decision_button("Accept once", HostKeyDecision::AcceptOnce))
+            .push(decision_button(
+                "Accept and save",
+                HostKeyDecision::AcceptAndSave,
+            )),
+    )
+    .max_width(560.0)
+    .on_close(LoginMessage::HostKeyDecided(HostKeyDecision::Reject))
+    .into()
+}
+
+fn default_key_path() -> String {
+    let Some(ssh_dir) = dirs::home_dir().map(|home| home.join(".ssh")) else {
+        return String::new();
+    };
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|name| ssh_dir.join(name))
+        .find(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
 impl Tab for LoginTab {
     type Message = Message;
 
@@ -54,48 +1228,139 @@ impl Tab for LoginTab {
     }
 
     fn content(&self) -> Element<'_, Self::Message> {
+        let login_button = Button::new(Text::new("Login").horizontal_alignment(Horizontal::Center))
+            .width(Length::Fill);
+        let login_button = if self.busy() {
+            login_button
+        } else {
+            login_button.on_press(LoginMessage::LoginPressed)
+        };
+        let clear_button = Button::new(Text::new("Clear").horizontal_alignment(Horizontal::Center))
+            .width(Length::Fill);
+        let clear_button = if self.busy() {
+            clear_button
+        } else {
+            clear_button.on_press(LoginMessage::ClearPressed)
+        };
+
+        let status = match &self.state {
+            LoginState::Idle => Text::new(""),
+            LoginState::InProgress(status) | LoginState::Done(status) => Text::new(status),
+            LoginState::Failed(error) => {
+                Text::new(error).style(iced::Color::from_rgb(0.8, 0.2, 0.2))
+            }
+        };
+
         let content: Element<'_, LoginMessage> = Container::new(
             Column::new()
                 .align_items(Alignment::Center)
                 .max_width(600)
                 .padding(20)
                 .spacing(16)
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            TextInput::new("Host", &self.host)
+                                .on_input(LoginMessage::HostChanged)
+                                .padding(10)
+                                .size(32),
+                        )
+                        .push(
+                            TextInput::new("22", &self.port)
+                                .on_input(LoginMessage::PortChanged)
+                                .padding(10)
+                                .size(32)
+                                .width(Length::Fixed(120.0)),
+                        ),
+                )
+                .push(self.host_config_hint())
                 .push(
                     TextInput::new("Username", &self.username)
                         .on_input(LoginMessage::UsernameChanged)
                         .padding(10)
                         .size(32),
                 )
+                .push(AuthMethod::ALL.iter().cloned().fold(
+                    Row::new().spacing(20),
+                    |row, method| {
+                        row.push(
+                            Radio::new(
+                                method,
+                                method,
+                                Some(self.auth_method),
+                                LoginMessage::AuthMethodSelected,
+                            )
+                            .size(16),
+                        )
+                    },
+                ))
+                .push(self.credentials())
                 .push(
-                    TextInput::new("Password", &self.password)
-                        .on_input(LoginMessage::PasswordChanged)
+                    Checkbox::new(
+                        "Forward agent to the remote host",
+                        self.forward_agent,
+                        LoginMessage::ForwardAgentToggled,
+                    )
+                    .width(Length::Fill),
+                )
+                .push(self.jumps_view())
+                .push(self.proxy_view())
+                .push(
+                    TextInput::new(
+                        "Local forwards, e.g. 8080:localhost:80, 5433:db:5432",
+                        &self.local_forwards,
+                    )
+                    .on_input(LoginMessage::LocalForwardsChanged)
+                    .padding(10)
+                    .size(20),
+                )
+                .push(
+                    TextInput::new(
+                        "Remote forwards, e.g. 9000:localhost:3000",
+                        &self.remote_forwards,
+                    )
+                    .on_input(LoginMessage::RemoteForwardsChanged)
+                    .padding(10)
+                    .size(20),
+                )
+                .push(
+                    TextInput::new("SOCKS proxy ports, e.g. 1080", &self.dynamic_forwards)
+                        .on_input(LoginMessage::DynamicForwardsChanged)
                         .padding(10)
-                        .size(32)
-                        .password(),
+                        .size(20),
                 )
                 .push(
                     Row::new()
                         .spacing(10)
+                        .push(clear_button)
                         .push(
                             Button::new(
-                                Text::new("Clear").horizontal_alignment(Horizontal::Center),
+                                Text::new("Save...").horizontal_alignment(Horizontal::Center),
                             )
                             .width(Length::Fill)
-                            .on_press(LoginMessage::ClearPressed),
+                            .on_press(LoginMessage::SaveSessionPressed),
                         )
-                        .push(
-                            Button::new(
-                                Text::new("Login").horizontal_alignment(Horizontal::Center),
-                            )
-                            .width(Length::Fill)
-                            .on_press(LoginMessage::LoginPressed),
-                        ),
-                ),
+                        .push(login_button),
+                )
+                .push(self.hops_view())
+                .push(status),
         )
         .align_x(Horizontal::Center)
         .align_y(Vertical::Center)
         .into();
 
+        let content: Element<'_, LoginMessage> = Modal::new(
+            self.pending_host_key.is_some(),
+            content,
+            move || match &self.pending_host_key {
+                Some(check) => host_key_prompt(check),
+                None => Column::new().into(),
+            },
+        )
+        .on_esc(LoginMessage::HostKeyDecided(HostKeyDecision::Reject))
+        .into();
+
         content.map(Message::Login)
     }
 }
@@ -1,9 +1,8 @@
 use iced::widget::{column as col};
-use iced::widget::{
-    button, checkbox, container, horizontal_space, pick_list, row, slider, svg, text, text_input,
-    toggler, vertical_slider,
-};
+use iced::widget::{button, container, horizontal_space, row, svg, text, toggler};
 use iced::{alignment, theme, Color};
+use iced::keyboard::{self, KeyCode, Modifiers};
+use iced::event::{self, Event};
 
 use iced_aw::menu::{menu_tree::MenuTree, CloseCondition, ItemHeight, ItemWidth, PathHighlight};
 use iced_aw::{quad};
@@ -11,7 +10,7 @@ use iced_aw::{helpers::menu_tree, menu_bar, menu_tree};
 
 use iced::{
     widget::{Container, Text},
-    Application, Command, Element, Length, Settings, Theme,
+    Application, Command, Element, Length, Settings, Subscription, Theme,
 };
 use iced_aw::{split, Split};
 
@@ -21,7 +20,7 @@ use iced::{
     widget::{Column},
     Font, Sandbox,
 };
-use iced_aw::{TabLabel, Tabs};
+use iced_aw::{Card, Modal, TabLabel, Tabs};
 use login::{LoginMessage, LoginTab};
 
 mod ferris;
@@ -31,12 +30,42 @@ mod counter;
 use counter::{CounterMessage, CounterTab};
 
 mod settings;
-use settings::{SettingsMessage, SettingsTab, TabBarPosition};
+use settings::{SettingsMessage, SettingsTab, TabBarPosition, TabSettings};
+
+mod config;
 
 mod shell;
 use shell::ShellViewTab;
 use shell::ShellMessage;
 
+mod sessions;
+use sessions::{Protocol, SessionsMessage, SessionsPanel};
+
+mod clipboard;
+mod forward;
+mod keybindings;
+use keybindings::{Action, Chord};
+mod known_hosts;
+mod palette;
+mod panes;
+use panes::{Axis, Direction, Panes};
+mod proxy;
+mod pty;
+mod search;
+mod selection;
+mod socks;
+mod ssh;
+use ssh::SessionEvent;
+mod ssh_config;
+mod sftp;
+mod sftp_tab;
+use sftp_tab::{SftpMessage, SftpTab};
+mod transfers;
+use transfers::{Direction as TransferDirection, TransferQueue, TransfersMessage};
+mod terminal;
+mod terminal_view;
+use terminal_view::DEFAULT_FONT_SIZE;
+
 pub fn main() -> iced::Result {
     App::run(iced::Settings {
         default_text_size: 15.0,
@@ -49,27 +78,6 @@ pub fn main() -> iced::Result {
     })
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SizeOption {
-    Uniform,
-    Static,
-}
-impl SizeOption {
-    const ALL: [SizeOption; 2] = [SizeOption::Uniform, SizeOption::Static];
-}
-impl std::fmt::Display for SizeOption {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::Uniform => "Uniform",
-                Self::Static => "Static",
-            }
-        )
-    }
-}
-
 const HEADER_SIZE: u16 = 32;
 const TAB_PADDING: u16 = 16;
 
@@ -99,24 +107,44 @@ impl From<Icon> for char {
 
 #[derive(Debug, Clone)]
 enum Message {
-    Debug(String),
-    ValueChange(u8),
-    CheckChange(bool),
-    ToggleChange(bool),
     ColorChange(Color),
-    FlipHorizontal,
-    FlipVertical,
     ThemeChange(bool),
-    TextChange(String),
-    SizeOption(SizeOption),
     OnVerResize(u16),
     OnHorResize(u16),
     TabSelected(TabId),
+    TabClosed(TabId),
+    DismissWarning,
+    ShowAbout(bool),
+    Quit,
+    NewLocalShell,
+    /// A menu item, shared with the keyboard shortcuts.
+    RunAction(Action),
+    OpenRecent(usize),
+    DuplicateSession(usize, Placement),
+    SessionDuplicated(Placement, Result<ssh::Connection, String>),
+    ClosePane(usize),
+    PaneResized(usize, usize, u16),
     Login(LoginMessage),
     Ferris(FerrisMessage),
     Counter(CounterMessage),
     Settings(SettingsMessage),
-    Shell(ShellMessage)
+    Sessions(SessionsMessage),
+    Shell(usize, ShellMessage),
+    Session(usize, SessionEvent),
+    OpenSftp(usize),
+    SftpOpened(String, Result<sftp::Sftp, String>),
+    Sftp(usize, SftpMessage),
+    Transfers(TransfersMessage),
+    /// A key press no widget handled, which may be a shortcut.
+    KeyPressed(KeyCode, Modifiers),
+    Tick
+}
+
+/// Whether a new session opens in a tab of its own or splits a pane.
+#[derive(Debug, Clone, Copy)]
+enum Placement {
+    Tab,
+    Split(usize, Axis),
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -125,19 +153,12 @@ enum TabId {
     Ferris,
     Counter,
     Settings,
-    Shell
+    Session(usize),
+    Sftp(usize)
 }
 struct App {
-    title: String,
-    value: u8,
-    check: bool,
-    toggle: bool,
     theme: iced::Theme,
-    flip_h: bool,
-    flip_v: bool,
     dark_mode: bool,
-    text: String,
-    size_option: SizeOption,
     ver_divider_position: Option<u16>,
     hor_divider_position: Option<u16>,
     active_tab: TabId,
@@ -145,80 +166,233 @@ struct App {
     ferris_tab: FerrisTab,
     counter_tab: CounterTab,
     settings_tab: SettingsTab,
-    shell_tab: ShellViewTab
+    sessions_panel: SessionsPanel,
+    /// Every terminal session, each one a pane of some tab.
+    shell_tabs: Vec<ShellViewTab>,
+    /// The terminal tabs and how their panes are laid out.
+    session_tabs: Vec<Panes>,
+    sftp_tabs: Vec<SftpTab>,
+    transfers: TransferQueue,
+    next_tab_id: usize,
+    saved_config: config::Config,
+    /// Whether a setting changed since the config was last written. It is
+    /// written on the next Tick, so dragging a divider saves once.
+    config_dirty: bool,
+    warning: Option<String>,
+    about: bool
 }
-impl Application for App {
-    type Executor = iced::executor::Default;
-    type Message = Message;
-    type Theme = iced::Theme;
-    type Flags = ();
-
-    fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        let theme = iced::Theme::custom(theme::Palette {
-            primary: Color::from([0.45, 0.25, 0.57]),
-            ..iced::Theme::Light.palette()
-        });
+impl App {
+    fn open_tab_id(&mut self) -> usize {
+        let id = self.next_tab_id;
+        self.next_tab_id += 1;
+        id
+    }
 
-        (
-            Self {
-                title: "Menu Test".to_string(),
-                value: 0,
-                check: false,
-                toggle: false,
-                theme,
-                flip_h: false,
-                flip_v: false,
-                dark_mode: false,
-                text: "Text Input".into(),
-                size_option: SizeOption::Static,
-                ver_divider_position: None,
-                hor_divider_position: Some(200),
-                active_tab: TabId::Login,
-                login_tab: LoginTab::new(),
-                ferris_tab: FerrisTab::new(),
-                counter_tab: CounterTab::new(),
-                settings_tab: SettingsTab::new(),
-                shell_tab: ShellViewTab::new()
+    /// What is saved to the config file.
+    fn config(&self) -> config::Config {
+        let settings = self.settings_tab.settings();
+        config::Config {
+            version: config::CURRENT_VERSION,
+            appearance: config::Appearance {
+                dark_mode: self.dark_mode,
+                primary_color: color_to_hex(self.theme.palette().primary),
+                tab_bar_position: settings.tab_bar_position.unwrap_or_default(),
+                tab_bar_theme: settings::style_name(settings.tab_bar_theme.unwrap_or_default())
+                    .to_string(),
             },
-            iced::Command::none(),
-        )
+            layout: config::Layout {
+                sidebar_width: self.hor_divider_position,
+                vertical_divider: self.ver_divider_position,
+            },
+            connection: self.settings_tab.connection().clone(),
+            terminal: self.settings_tab.terminal().clone(),
+            keybindings: self.settings_tab.keybindings().clone(),
+        }
     }
 
-    fn theme(&self) -> Self::Theme {
-        self.theme.clone()
+    /// Applies the settings to the Login tab and the open terminals.
+    fn apply_settings(&mut self) {
+        let connection = self.settings_tab.connection();
+        self.login_tab.set_keepalive(connection.keepalive());
+        let max_delay = connection.reconnect_max_delay();
+        let terminal = self.settings_tab.terminal();
+        let palette = terminal.palette();
+        for tab in &mut self.shell_tabs {
+            tab.set_reconnect_max_delay(max_delay);
+            tab.set_scrollback_limit(terminal.scrollback_lines);
+            tab.set_palette(palette.clone());
+            tab.set_font_size(terminal.font_size);
+            tab.set_keybindings(self.settings_tab.keybindings().clone());
+        }
     }
 
-    fn title(&self) -> String {
-        self.title.clone()
+    fn active_panes(&mut self) -> Option<&mut Panes> {
+        match self.active_tab {
+            TabId::Session(id) => self.session_tabs.iter_mut().find(|panes| panes.id() == id),
+            _ => None,
+        }
     }
 
-    fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
-        match message {
-            Message::Debug(s) => {
-                self.title = s;
+    /// The focused session of the current tab.
+    fn active_session(&self) -> Option<&ShellViewTab> {
+        let TabId::Session(id) = self.active_tab else {
+            return None;
+        };
+        let panes = self.session_tabs.iter().find(|panes| panes.id() == id)?;
+        self.shell_tabs.iter().find(|tab| tab.id() == panes.focused())
+    }
+
+    /// Shows a new session in a new tab or a new pane.
+    fn place_session(&mut self, shell: ShellViewTab, placement: Placement) {
+        let pane = shell.id();
+        self.shell_tabs.push(shell);
+        self.apply_settings();
+        if let Placement::Split(target, axis) = placement {
+            if let Some(panes) = self.session_tabs.iter_mut().find(|panes| panes.contains(target)) {
+                panes.split(target, axis, pane);
+                self.active_tab = TabId::Session(panes.id());
+                return;
             }
-            Message::ValueChange(v) => {
-                self.value = v;
-                self.title = v.to_string();
+        }
+        let id = self.open_tab_id();
+        self.session_tabs.push(Panes::new(id, pane));
+        self.active_tab = TabId::Session(id);
+    }
+
+    fn close_shell(&mut self, id: usize) {
+        if let Some(index) = self.shell_tabs.iter().position(|tab| tab.id() == id) {
+            self.shell_tabs.remove(index).close();
+        }
+    }
+
+    /// The tabs in the order they are shown.
+    fn tab_ids(&self) -> Vec<TabId> {
+        self.session_tabs
+            .iter()
+            .map(|panes| TabId::Session(panes.id()))
+            .chain(self.sftp_tabs.iter().map(|tab| TabId::Sftp(tab.id())))
+            .chain([TabId::Login, TabId::Ferris, TabId::Counter, TabId::Settings])
+            .collect()
+    }
+
+    /// A tab with one pane looks like any other tab.
+    fn panes_view<'a>(&'a self, panes: &'a Panes) -> Element<'a, Message> {
+        let id = panes.id();
+        let single = panes.is_maximized() || panes.panes().len() == 1;
+        panes.view(
+            &|pane| match self.shell_tabs.iter().find(|tab| tab.id() == pane) {
+                Some(tab) if single => tab.view(),
+                Some(tab) => tab.pane_view(pane == panes.focused()),
+                None => Text::new("").into(),
+            },
+            move |split, position| Message::PaneResized(id, split, position),
+        )
+    }
+
+    /// Runs a shortcut or menu action.
+    fn run_action(&mut self, action: Action) -> iced::Command<Message> {
+        match action {
+            Action::NewTab => return self.handle(Message::NewLocalShell),
+            Action::CloseTab => return self.handle(Message::TabClosed(self.active_tab.clone())),
+            Action::NextTab | Action::PreviousTab => {
+                let ids = self.tab_ids();
+                let index = ids.iter().position(|id| *id == self.active_tab).unwrap_or(0);
+                let index = if action == Action::NextTab {
+                    (index + 1) % ids.len()
+                } else {
+                    (index + ids.len() - 1) % ids.len()
+                };
+                self.active_tab = ids[index].clone();
+            }
+            Action::Find => {
+                if let Some(id) = self.active_session().map(ShellViewTab::id) {
+                    return self.handle(Message::Shell(id, ShellMessage::ToggleFind));
+                }
+            }
+            Action::SplitRight | Action::SplitDown => {
+                if let Some(id) = self.active_session().map(ShellViewTab::id) {
+                    let axis = match action {
+                        Action::SplitRight => Axis::Vertical,
+                        _ => Axis::Horizontal,
+                    };
+                    return self.handle(Message::DuplicateSession(id, Placement::Split(id, axis)));
+                }
             }
-            Message::CheckChange(c) => {
-                self.check = c;
-                self.title = c.to_string();
+            Action::ClosePane => {
+                if let Some(id) = self.active_session().map(ShellViewTab::id) {
+                    return self.handle(Message::ClosePane(id));
+                }
             }
-            Message::ToggleChange(t) => {
-                self.toggle = t;
-                self.title = t.to_string();
+            Action::MaximizePane => {
+                if let Some(panes) = self.active_panes() {
+                    panes.toggle_maximized();
+                }
             }
+            Action::FocusLeft | Action::FocusRight | Action::FocusUp | Action::FocusDown => {
+                let direction = match action {
+                    Action::FocusLeft => Direction::Left,
+                    Action::FocusRight => Direction::Right,
+                    Action::FocusUp => Direction::Up,
+                    _ => Direction::Down,
+                };
+                if let Some(panes) = self.active_panes() {
+                    panes.focus_toward(direction);
+                }
+            }
+            Action::ZoomIn | Action::ZoomOut | Action::ZoomReset => {
+                let size = self.settings_tab.terminal().font_size;
+                self.settings_tab.set_font_size(match action {
+                    Action::ZoomIn => size + 1.0,
+                    Action::ZoomOut => size - 1.0,
+                    _ => DEFAULT_FONT_SIZE,
+                });
+                self.apply_settings();
+                self.config_dirty = true;
+            }
+            // A focused terminal copies and pastes by itself, so this came from
+            // the menu and copies the last text selected in any terminal.
+            Action::Copy => {
+                if let (Some(_), Some(text)) = (self.active_session(), clipboard::primary()) {
+                    return iced::clipboard::write(text);
+                }
+            }
+            Action::Paste => {
+                if let Some(id) = self.active_session().map(ShellViewTab::id) {
+                    return iced::clipboard::read(move |text| {
+                        Message::Shell(id, ShellMessage::Paste(text.unwrap_or_default()))
+                    });
+                }
+            }
+        }
+        iced::Command::none()
+    }
+
+    /// Writes the config back to disk if a setting changed.
+    fn persist(&mut self) {
+        if !std::mem::take(&mut self.config_dirty) {
+            return;
+        }
+        let config = self.config();
+        if config == self.saved_config {
+            return;
+        }
+        if let Err(e) = config::save(&config) {
+            self.warning = Some(e);
+        }
+        self.saved_config = config;
+    }
+
+    fn handle(&mut self, message: Message) -> iced::Command<Message> {
+        match message {
             Message::ColorChange(c) => {
+                self.config_dirty = true;
                 self.theme = iced::Theme::custom(theme::Palette {
                     primary: c,
                     ..self.theme.palette()
                 });
-                self.title = format!("[{:.2}, {:.2}, {:.2}]", c.r, c.g, c.b);
             }
-            Message::FlipHorizontal => self.flip_h = !self.flip_h,
-            Message::FlipVertical => self.flip_v = !self.flip_v,
             Message::ThemeChange(b) => {
+                self.config_dirty = true;
                 self.dark_mode = b;
                 let primary = self.theme.palette().primary;
                 if b {
@@ -233,49 +407,385 @@ impl Application for App {
                     })
                 }
             }
-            Message::TextChange(s) => {
-                self.text = s.clone();
-                self.title = s;
+            Message::OnVerResize(position) => {
+                self.ver_divider_position = Some(position);
+                self.config_dirty = true;
             }
-            Message::SizeOption(so) => {
-                self.size_option = so;
-                self.title = self.size_option.to_string();
+            Message::OnHorResize(position) => {
+                self.hor_divider_position = Some(position);
+                self.config_dirty = true;
             }
-            Message::OnVerResize(position) => self.ver_divider_position = Some(position),
-            Message::OnHorResize(position) => self.hor_divider_position = Some(position),
             Message::TabSelected(selected) => self.active_tab = selected,
-            Message::Login(message) => self.login_tab.update(message),
+            Message::TabClosed(TabId::Session(id)) => {
+                let Some(index) = self.session_tabs.iter().position(|panes| panes.id() == id)
+                else {
+                    return iced::Command::none();
+                };
+                let panes = self.session_tabs.remove(index);
+                for pane in panes.panes() {
+                    self.close_shell(pane);
+                }
+
+                if self.active_tab == TabId::Session(id) {
+                    // Show the neighbouring session, or the Login tab if none is left.
+                    self.active_tab = match self.session_tabs.get(index.saturating_sub(1)) {
+                        Some(panes) => TabId::Session(panes.id()),
+                        None => TabId::Login,
+                    };
+                }
+            }
+            Message::TabClosed(TabId::Sftp(id)) => {
+                self.sftp_tabs.retain(|tab| tab.id() != id);
+                if self.active_tab == TabId::Sftp(id) {
+                    self.active_tab = TabId::Login;
+                }
+            }
+            // The fixed tabs can't be closed.
+            Message::TabClosed(_) => {}
+            Message::DismissWarning => self.warning = None,
+            Message::ShowAbout(show) => self.about = show,
+            Message::Quit => {
+                self.persist();
+                return iced::window::close();
+            }
+            Message::RunAction(action) => return self.run_action(action),
+            Message::OpenRecent(index) => {
+                let Some(session) = self.sessions_panel.recent().get(index).cloned() else {
+                    return iced::Command::none();
+                };
+                self.active_tab = TabId::Login;
+                return self.login_tab.load_session(&session).map(Message::Login);
+            }
+            // An SSH session connects again with the same settings; anything else opens another
+            // local shell.
+            Message::DuplicateSession(id, placement) => {
+                let Some(tab) = self.shell_tabs.iter().find(|tab| tab.id() == id) else {
+                    return iced::Command::none();
+                };
+                if let Some(session) = tab.connection().and_then(|c| c.ssh_session()) {
+                    return iced::Command::perform(ssh::duplicate(session.clone()), move |result| {
+                        Message::SessionDuplicated(placement, result)
+                    });
+                }
+                let id = self.open_tab_id();
+                self.place_session(ShellViewTab::new(id), placement);
+            }
+            Message::SessionDuplicated(placement, Ok(connection)) => {
+                let id = self.open_tab_id();
+                self.place_session(ShellViewTab::with_connection(id, connection), placement);
+            }
+            Message::SessionDuplicated(_, Err(e)) => self.warning = Some(e),
+            // Closing the last pane closes its tab.
+            Message::ClosePane(pane) => {
+                let Some(panes) = self.session_tabs.iter_mut().find(|panes| panes.contains(pane))
+                else {
+                    return iced::Command::none();
+                };
+                if !panes.remove(pane) {
+                    let id = panes.id();
+                    return self.handle(Message::TabClosed(TabId::Session(id)));
+                }
+                self.close_shell(pane);
+            }
+            Message::PaneResized(id, split, position) => {
+                if let Some(panes) = self.session_tabs.iter_mut().find(|panes| panes.id() == id) {
+                    panes.resize(split, position);
+                }
+            }
+            Message::NewLocalShell => {
+                let id = self.open_tab_id();
+                self.place_session(ShellViewTab::new(id), Placement::Tab);
+            }
+            Message::Login(LoginMessage::ShellOpened(Ok(connection))) => {
+                self.sessions_panel.add_recent(self.login_tab.saved_session());
+                let id = self.open_tab_id();
+                self.place_session(
+                    ShellViewTab::with_connection(id, connection.clone()),
+                    Placement::Tab,
+                );
+                return self
+                    .login_tab
+                    .update(LoginMessage::ShellOpened(Ok(connection)))
+                    .map(Message::Login);
+            }
+            Message::Login(LoginMessage::SaveSessionPressed) => {
+                self.sessions_panel.edit_new(self.login_tab.saved_session());
+            }
+            Message::Login(message) => return self.login_tab.update(message).map(Message::Login),
+            Message::Sessions(SessionsMessage::Connect(index)) => {
+                let Some(session) = self.sessions_panel.session(index).cloned() else {
+                    return iced::Command::none();
+                };
+                match session.protocol {
+                    Protocol::Local => {
+                        let id = self.open_tab_id();
+                        self.place_session(ShellViewTab::new(id), Placement::Tab);
+                    }
+                    // The Login tab takes care of host keys and passwords.
+                    Protocol::Ssh => {
+                        self.active_tab = TabId::Login;
+                        return self.login_tab.load_session(&session).map(Message::Login);
+                    }
+                }
+            }
+            Message::Sessions(message) => {
+                return self.sessions_panel.update(message).map(Message::Sessions)
+            }
             Message::Ferris(message) => self.ferris_tab.update(message),
             Message::Counter(message) => self.counter_tab.update(message),
-            Message::Settings(message) => self.settings_tab.update(message),
-            Message::Shell(message) => self.shell_tab.update(message),
+            Message::Settings(message) => {
+                let command = self.settings_tab.update(message).map(Message::Settings);
+                self.apply_settings();
+                self.config_dirty = true;
+                return command;
+            }
+            // A clicked pane takes the focus.
+            Message::Shell(id, ShellMessage::Focused) => {
+                if let Some(panes) = self.session_tabs.iter_mut().find(|panes| panes.contains(id)) {
+                    panes.focus(id);
+                }
+            }
+            Message::Shell(id, message) => {
+                match self.shell_tabs.iter_mut().find(|tab| tab.id() == id) {
+                    Some(tab) => {
+                        return tab.update(message).map(move |message| Message::Shell(id, message))
+                    }
+                    // The tab was closed while it reconnected.
+                    None => {
+                        if let ShellMessage::Reconnected(Ok(connection)) = message {
+                            connection.close();
+                        }
+                    }
+                }
+            }
+            Message::Session(connection_id, event) => {
+                let tab = self.shell_tabs.iter_mut().find(|tab| {
+                    tab.connection().map(|connection| connection.id()) == Some(connection_id)
+                });
+                if let Some(tab) = tab {
+                    let id = tab.id();
+                    return tab
+                        .update(ShellMessage::Session(event))
+                        .map(move |message| Message::Shell(id, message));
+                }
+            }
+            Message::OpenSftp(id) => {
+                let connection = self
+                    .shell_tabs
+                    .iter()
+                    .find(|tab| tab.id() == id)
+                    .and_then(|tab| tab.connection());
+                let Some(connection) = connection else {
+                    return iced::Command::none();
+                };
+                if let Some(session) = connection.ssh_session() {
+                    let title = connection.title().to_string();
+                    return iced::Command::perform(sftp::open(session.clone()), move |result| {
+                        Message::SftpOpened(title, result)
+                    });
+                }
+            }
+            Message::SftpOpened(title, Ok(sftp)) => {
+                let id = self.open_tab_id();
+                let (tab, command) = SftpTab::new(id, title, sftp);
+                self.sftp_tabs.push(tab);
+                self.active_tab = TabId::Sftp(id);
+                return command.map(move |message| Message::Sftp(id, message));
+            }
+            Message::SftpOpened(_, Err(e)) => self.warning = Some(e),
+            Message::Sftp(id, SftpMessage::DownloadTo(remote, Some(local))) => {
+                if let Some(tab) = self.sftp_tabs.iter().find(|tab| tab.id() == id) {
+                    let sftp = tab.sftp().clone();
+                    self.transfers.enqueue(TransferDirection::Download, sftp, local, remote);
+                }
+            }
+            Message::Sftp(id, SftpMessage::UploadFrom(dir, Some(files))) => {
+                if let Some(tab) = self.sftp_tabs.iter().find(|tab| tab.id() == id) {
+                    for local in files {
+                        let Some(name) = local.file_name() else {
+                            continue;
+                        };
+                        let remote = sftp::join(&dir, &name.to_string_lossy());
+                        let sftp = tab.sftp().clone();
+                        self.transfers.enqueue(TransferDirection::Upload, sftp, local, remote);
+                    }
+                }
+            }
+            Message::Sftp(id, message) => {
+                if let Some(tab) = self.sftp_tabs.iter_mut().find(|tab| tab.id() == id) {
+                    return tab.update(message).map(move |message| Message::Sftp(id, message));
+                }
+            }
+            Message::KeyPressed(key_code, modifiers) => {
+                let action = Chord::new(key_code, modifiers)
+                    .and_then(|chord| self.settings_tab.keybindings().action(chord));
+                if let Some(action) = action {
+                    return self.run_action(action);
+                }
+            }
+            // Refreshes the counters on screen, moves reconnect countdowns on
+            // and saves changed settings.
+            Message::Tick => {
+                self.persist();
+                return iced::Command::batch(self.shell_tabs.iter_mut().map(|tab| {
+                    let id = tab.id();
+                    tab.update(ShellMessage::Tick)
+                        .map(move |message| Message::Shell(id, message))
+                }));
+            }
+            Message::Transfers(message) => {
+                // A finished upload refreshes the SFTP tabs showing its directory.
+                if let Some(dir) = self.transfers.update(message) {
+                    return iced::Command::batch(self.sftp_tabs.iter_mut().map(|tab| {
+                        let id = tab.id();
+                        tab.remote_changed(&dir)
+                            .map(move |message| Message::Sftp(id, message))
+                    }));
+                }
+            }
         }
         iced::Command::none()
     }
+}
 
-    fn view(&self) -> iced::Element<'_, Self::Message, iced::Renderer<Self::Theme>> {
-        let pick_size_option = pick_list(
-            &SizeOption::ALL[..],
-            Some(self.size_option),
-            Message::SizeOption,
+fn app_theme(primary: Color, dark_mode: bool) -> iced::Theme {
+    let base = if dark_mode {
+        iced::Theme::Dark
+    } else {
+        iced::Theme::Light
+    };
+    iced::Theme::custom(theme::Palette {
+        primary,
+        ..base.palette()
+    })
+}
+
+fn color_to_hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn color_from_hex(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+}
+
+impl Application for App {
+    type Executor = iced::executor::Default;
+    type Message = Message;
+    type Theme = iced::Theme;
+    type Flags = ();
+
+    fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
+        let config::Loaded { config, warning } = config::load();
+        let appearance = &config.appearance;
+        let primary =
+            color_from_hex(&appearance.primary_color).unwrap_or(Color::from([0.45, 0.25, 0.57]));
+        let theme = app_theme(primary, appearance.dark_mode);
+        let tab_settings = TabSettings {
+            tab_bar_position: Some(appearance.tab_bar_position),
+            tab_bar_theme: Some(settings::style_from_name(&appearance.tab_bar_theme)),
+        };
+
+        let mut app = Self {
+            theme,
+            dark_mode: appearance.dark_mode,
+            ver_divider_position: config.layout.vertical_divider,
+            hor_divider_position: config.layout.sidebar_width,
+            active_tab: TabId::Login,
+            login_tab: LoginTab::new(),
+            ferris_tab: FerrisTab::new(),
+            counter_tab: CounterTab::new(),
+            settings_tab: SettingsTab::with_settings(tab_settings, &config),
+            sessions_panel: SessionsPanel::new(),
+            shell_tabs: vec![ShellViewTab::new(0)],
+            session_tabs: vec![Panes::new(1, 0)],
+            sftp_tabs: Vec::new(),
+            transfers: TransferQueue::new(),
+            next_tab_id: 2,
+            saved_config: config,
+            config_dirty: false,
+            warning,
+            about: false
+        };
+        // The startup terminal uses the saved settings too.
+        app.apply_settings();
+        (app, iced::Command::none())
+    }
+
+    fn theme(&self) -> Self::Theme {
+        self.theme.clone()
+    }
+
+    fn title(&self) -> String {
+        String::from("my-ssh")
+    }
+
+    fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
+        self.handle(message)
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let sessions = Subscription::batch(
+            self.shell_tabs
+                .iter()
+                .filter_map(|tab| tab.connection())
+                .map(|connection| {
+                    connection
+                        .subscription()
+                        .map(|(id, event)| Message::Session(id, event))
+                }),
         );
+        let tick = if self
+            .shell_tabs
+            .iter()
+            .any(|tab| tab.showing_forwards() || tab.reconnect_pending())
+            || self.config_dirty
+        {
+            iced::time::every(std::time::Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        };
+        // Every key goes to the Settings tab while it records a shortcut.
+        let keys = if self.settings_tab.recording_shortcut() {
+            iced::subscription::events_with(|event, _status| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) => Some(
+                    Message::Settings(SettingsMessage::ShortcutPressed(key_code, modifiers)),
+                ),
+                _ => None,
+            })
+        } else {
+            iced::subscription::events_with(|event, status| match (event, status) {
+                (
+                    Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }),
+                    event::Status::Ignored,
+                ) => Some(Message::KeyPressed(key_code, modifiers)),
+                _ => None,
+            })
+        };
+        Subscription::batch([
+            sessions,
+            self.transfers.subscription().map(Message::Transfers),
+            tick,
+            keys,
+        ])
+    }
 
-        let mb = match self.size_option {
-            SizeOption::Uniform => {
-                menu_bar!(menu_1(self), menu_2(self), menu_3(self), menu_4(self))
-                    .item_width(ItemWidth::Uniform(180))
-                    .item_height(ItemHeight::Uniform(25))
-            }
-            SizeOption::Static => menu_bar!(
-                menu_1(self),
-                menu_2(self),
-                menu_3(self),
-                menu_4(self),
-                menu_5(self),
-            )
-            .item_width(ItemWidth::Static(180))
-            .item_height(ItemHeight::Static(25)),
-        }
+    fn view(&self) -> iced::Element<'_, Self::Message, iced::Renderer<Self::Theme>> {
+        let mb = menu_bar!(
+            file_menu(self),
+            edit_menu(self),
+            view_menu(self),
+            session_menu(self),
+            tools_menu(self),
+            help_menu(self),
+        )
+        .item_width(ItemWidth::Static(260))
+        .item_height(ItemHeight::Static(28))
         .spacing(4.0)
         .bounds_expand(30)
         .path_highlight(Some(PathHighlight::MenuActive))
@@ -285,11 +795,7 @@ impl Application for App {
             click_inside: false,
         });
 
-        let r = if self.flip_h {
-            row!(pick_size_option, horizontal_space(Length::Fill), mb,)
-        } else {
-            row!(mb, horizontal_space(Length::Fill), pick_size_option)
-        }
+        let r = row!(mb, horizontal_space(Length::Fill))
         .padding([2, 8])
         .align_items(alignment::Alignment::Center);
 
@@ -317,12 +823,21 @@ impl Application for App {
             .tab_bar_theme
             .unwrap_or_default();
 
-        let content_tabs = Tabs::new(Message::TabSelected)
-            .push(
-                TabId::Shell,
-                self.shell_tab.tab_label(),
-                self.shell_tab.view(),
-            )
+        let content_tabs = self.session_tabs.iter().fold(
+            Tabs::new(Message::TabSelected).on_close(Message::TabClosed),
+            |tabs, panes| match self.shell_tabs.iter().find(|tab| tab.id() == panes.focused()) {
+                Some(focused) => tabs.push(
+                    TabId::Session(panes.id()),
+                    focused.tab_label(),
+                    self.panes_view(panes),
+                ),
+                None => tabs,
+            },
+        );
+        let content_tabs = self.sftp_tabs.iter().fold(content_tabs, |tabs, tab| {
+            tabs.push(TabId::Sftp(tab.id()), tab.tab_label(), tab.view())
+        });
+        let content_tabs = content_tabs
             .push(
                 TabId::Login,
                 self.login_tab.tab_label(),
@@ -352,11 +867,9 @@ impl Application for App {
             });
         
 
-        let left = Container::new(Text::new("First"))
+        let left = Container::new(self.sessions_panel.view().map(Message::Sessions))
             .width(Length::Fill)
-            .height(Length::Fill)
-            .center_x()
-            .center_y();
+            .height(Length::Fill);
 
         let right = Container::new(content_tabs)
             .width(Length::Fill)
@@ -371,19 +884,38 @@ impl Application for App {
             split::Axis::Vertical,
             Message::OnHorResize,
         );
-        let back = container(col![panel])
+        let mut body = Column::new();
+        if let Some(warning) = &self.warning {
+            body = body.push(
+                container(
+                    row![
+                        text(warning).style(Color::from_rgb(0.8, 0.2, 0.2)).width(Length::Fill),
+                        button("Dismiss").on_press(Message::DismissWarning),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                )
+                .padding([4, 8])
+                .width(Length::Fill),
+            );
+        }
+        body = body.push(panel);
+        if !self.transfers.is_empty() {
+            body = body.push(
+                container(self.transfers.view().map(Message::Transfers)).width(Length::Fill),
+            );
+        }
+        let back = container(body)
             .width(Length::Fill)
             .height(Length::Fill)
             .style(back_style);
 
 
-        let c = if self.flip_v {
-            col![back, top_bar, ]
-        } else {
-            col![top_bar, back]
-        };
-        
-        c.into()
+        let c = col![top_bar, back];
+
+        Modal::new(self.about, c, about)
+            .on_esc(Message::ShowAbout(false))
+            .into()
     }
 }
 
@@ -411,42 +943,76 @@ impl button::StyleSheet for ButtonStyle {
     }
 }
 
+/// The button is disabled when `msg` is `None`.
 fn base_button<'a>(
     content: impl Into<Element<'a, Message, iced::Renderer>>,
-    msg: Message,
+    msg: Option<Message>,
 ) -> button::Button<'a, Message, iced::Renderer> {
-    button(content)
+    let button = button(content)
         .padding([4, 8])
-        .style(iced::theme::Button::Custom(Box::new(ButtonStyle {})))
-        .on_press(msg)
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle {})));
+    match msg {
+        Some(msg) => button.on_press(msg),
+        None => button,
+    }
 }
 
-fn labeled_button<'a>(label: &str, msg: Message) -> button::Button<'a, Message, iced::Renderer> {
-    base_button(
-        text(label)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .vertical_alignment(alignment::Vertical::Center),
+/// A menu item with its shortcut shown on the right.
+fn menu_item<'a>(
+    label: &str,
+    chord: Option<Chord>,
+    msg: Option<Message>,
+) -> MenuTree<'a, Message, iced::Renderer> {
+    let accelerator = chord.map(|chord| chord.to_string()).unwrap_or_default();
+    menu_tree!(base_button(
+        row![
+            text(label)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .vertical_alignment(alignment::Vertical::Center),
+            text(accelerator)
+                .size(13)
+                .height(Length::Fill)
+                .vertical_alignment(alignment::Vertical::Center),
+        ]
+        .spacing(16),
         msg,
     )
+    .width(Length::Fill)
+    .height(Length::Fill))
 }
 
-fn debug_button<'a>(label: &str) -> button::Button<'a, Message, iced::Renderer> {
-    labeled_button(label, Message::Debug(label.into()))
-}
-
-fn debug_item<'a>(label: &str) -> MenuTree<'a, Message, iced::Renderer> {
-    menu_tree!(debug_button(label).width(Length::Fill).height(Length::Fill))
+/// The menu item for an action, showing its current shortcut.
+fn action_item<'a>(
+    app: &App,
+    label: &str,
+    action: Action,
+    enabled: bool,
+) -> MenuTree<'a, Message, iced::Renderer> {
+    menu_item(
+        label,
+        app.settings_tab.keybindings().chord(action),
+        enabled.then_some(Message::RunAction(action)),
+    )
 }
 
 fn color_item<'a>(color: impl Into<Color>) -> MenuTree<'a, Message, iced::Renderer> {
     let color = color.into();
-    menu_tree!(base_button(circle(color), Message::ColorChange(color)))
+    menu_tree!(base_button(circle(color), Some(Message::ColorChange(color))))
+}
+
+fn menu_label<'a>(label: &str) -> Element<'a, Message, iced::Renderer> {
+    container(
+        text(label)
+            .height(Length::Fill)
+            .vertical_alignment(alignment::Vertical::Center),
+    )
+    .padding([4, 8])
+    .into()
 }
 
 fn sub_menu<'a>(
     label: &str,
-    msg: Message,
     children: Vec<MenuTree<'a, Message, iced::Renderer>>,
 ) -> MenuTree<'a, Message, iced::Renderer> {
     let handle = svg::Handle::from_path(format!(
@@ -460,7 +1026,7 @@ fn sub_menu<'a>(
         }));
 
     menu_tree(
-        base_button(
+        container(
             row![
                 text(label)
                     .width(Length::Fill)
@@ -468,21 +1034,14 @@ fn sub_menu<'a>(
                     .vertical_alignment(alignment::Vertical::Center),
                 arrow
             ],
-            msg,
         )
+        .padding([4, 8])
         .width(Length::Fill)
         .height(Length::Fill),
         children,
     )
 }
 
-fn debug_sub_menu<'a>(
-    label: &str,
-    children: Vec<MenuTree<'a, Message, iced::Renderer>>,
-) -> MenuTree<'a, Message, iced::Renderer> {
-    sub_menu(label, Message::Debug(label.into()), children)
-}
-
 fn separator<'a>() -> MenuTree<'a, Message, iced::Renderer> {
     menu_tree!(quad::Quad {
         color: [0.5; 3].into(),
@@ -492,38 +1051,6 @@ fn separator<'a>() -> MenuTree<'a, Message, iced::Renderer> {
     })
 }
 
-fn dot_separator<'a>() -> MenuTree<'a, Message, iced::Renderer> {
-    menu_tree!(text("·························")
-        .size(30)
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .horizontal_alignment(alignment::Horizontal::Center)
-        .vertical_alignment(alignment::Vertical::Center))
-}
-
-fn labeled_separator(label: &'_ str) -> MenuTree<'_, Message, iced::Renderer> {
-    let q_1 = quad::Quad {
-        color: [0.5; 3].into(),
-        border_radius: 4.0.into(),
-        inner_bounds: quad::InnerBounds::Ratio(0.98, 0.1),
-        ..Default::default()
-    };
-    let q_2 = quad::Quad {
-        color: [0.5; 3].into(),
-        border_radius: 4.0.into(),
-        inner_bounds: quad::InnerBounds::Ratio(0.98, 0.1),
-        ..Default::default()
-    };
-
-    menu_tree!(row![
-        q_1,
-        text(label)
-            .height(Length::Fill)
-            .vertical_alignment(alignment::Vertical::Center),
-        q_2,
-    ])
-}
-
 fn circle(color: Color) -> quad::Quad {
     let radius = 10.0;
 
@@ -535,172 +1062,168 @@ fn circle(color: Color) -> quad::Quad {
     }
 }
 
-fn menu_1<'a>(_app: &App) -> MenuTree<'a, Message, iced::Renderer> {
-    let root = menu_tree(
-        debug_button("Nested Menus"),
-        vec![
-            debug_item("Item"),
-            debug_item("Item"),
-        ],
-    )
-    .width(110);
-
-    root
-}
+fn file_menu<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    let recent = app.sessions_panel.recent();
+    let recent_hosts = if recent.is_empty() {
+        vec![menu_item("No recent hosts", None, None)]
+    } else {
+        recent
+            .iter()
+            .enumerate()
+            .map(|(index, session)| {
+                menu_item(&session.summary(), None, Some(Message::OpenRecent(index)))
+            })
+            .collect()
+    };
+    let closable = matches!(app.active_tab, TabId::Session(_) | TabId::Sftp(_));
 
-fn menu_2<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
-    let sub_1 = menu_tree(
-        container(toggler(
-            Some("Or as a sub menu item".to_string()),
-            app.toggle,
-            Message::ToggleChange,
-        ))
-        .padding([0, 8])
-        .height(Length::Fill)
-        .align_y(alignment::Vertical::Center),
+    menu_tree(
+        menu_label("File"),
         vec![
-            debug_item("Item"),
-            debug_item("Item"),
-            debug_item("Item"),
-            debug_item("Item"),
+            menu_item(
+                "New connection...",
+                None,
+                Some(Message::TabSelected(TabId::Login)),
+            ),
+            action_item(app, "New local shell", Action::NewTab, true),
+            sub_menu("Recent hosts", recent_hosts),
+            separator(),
+            action_item(app, "Close tab", Action::CloseTab, closable),
+            menu_item("Quit", None, Some(Message::Quit)),
         ],
-    );
-
-    let bt = menu_tree!(button(
-        text("Button")
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .vertical_alignment(alignment::Vertical::Center),
     )
-    .width(Length::Fill)
-    .height(Length::Fill)
-    .on_press(Message::Debug("Button".into())));
-
-    let cb = menu_tree!(checkbox("Checkbox", app.check, Message::CheckChange).width(Length::Fill));
-
-    let sld = menu_tree!(row![
-        "Slider",
-        horizontal_space(Length::Fixed(8.0)),
-        slider(0..=255, app.value, Message::ValueChange)
-    ]);
+}
 
-    let txn = menu_tree!(text_input("", &app.text).on_input(Message::TextChange));
+fn edit_menu<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    let in_session = app.active_session().is_some();
 
-    let root = menu_tree(
-        debug_button("Widgets"),
+    menu_tree(
+        menu_label("Edit"),
         vec![
-            debug_item("You can use any widget"),
-            debug_item("as a menu item"),
-            bt,
-            cb,
-            sld,
-            txn,
-            sub_1,
+            action_item(app, "Copy", Action::Copy, in_session),
+            action_item(app, "Paste", Action::Paste, in_session),
             separator(),
-            debug_item("Seperators are also widgets"),
-            labeled_separator("Separator"),
-            debug_item("Item"),
-            debug_item("Item"),
-            dot_separator(),
-            debug_item("Item"),
-            debug_item("Item"),
+            action_item(app, "Find...", Action::Find, in_session),
         ],
-    );
-
-    root
+    )
 }
 
-fn menu_3<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
-    let [r, g, b, _] = app.theme.palette().primary.into_rgba8();
-
-    let primary = debug_sub_menu(
-        "Primary",
+fn view_menu<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    let accent = sub_menu(
+        "Accent colour",
         vec![
-            menu_tree!(slider(0..=255, r, move |x| {
-                Message::ColorChange(Color::from_rgb8(x, g, b))
-            })),
-            menu_tree!(slider(0..=255, g, move |x| {
-                Message::ColorChange(Color::from_rgb8(r, x, b))
-            })),
-            menu_tree!(slider(0..=255, b, move |x| {
-                Message::ColorChange(Color::from_rgb8(r, g, x))
-            })),
+            color_item([0.45, 0.25, 0.57]),
+            color_item([0.15, 0.59, 0.64]),
+            color_item([0.76, 0.82, 0.20]),
+            color_item([0.17, 0.27, 0.33]),
         ],
     );
 
-    let root = menu_tree(
-        debug_button("Controls"),
+    menu_tree(
+        menu_label("View"),
         vec![
-            menu_tree!(labeled_button("Flip Horizontal", Message::FlipHorizontal)
-                .width(Length::Fill)
-                .height(Length::Fill)),
-            menu_tree!(labeled_button("Flip Vertical", Message::FlipVertical)
-                .width(Length::Fill)
-                .height(Length::Fill)),
+            action_item(app, "Zoom in", Action::ZoomIn, true),
+            action_item(app, "Zoom out", Action::ZoomOut, true),
+            action_item(app, "Reset zoom", Action::ZoomReset, true),
+            separator(),
+            action_item(app, "Next tab", Action::NextTab, true),
+            action_item(app, "Previous tab", Action::PreviousTab, true),
             separator(),
             menu_tree!(row![toggler(
-                Some("Dark Mode".into()),
+                Some("Dark mode".into()),
                 app.dark_mode,
                 Message::ThemeChange
             )]
             .padding([0, 8])),
-            color_item([0.45, 0.25, 0.57]),
-            color_item([0.15, 0.59, 0.64]),
-            color_item([0.76, 0.82, 0.20]),
-            color_item([0.17, 0.27, 0.33]),
-            primary,
+            accent,
         ],
-    );
-
-    root
+    )
 }
 
-fn menu_4<'a>(_app: &App) -> MenuTree<'a, Message, iced::Renderer> {
-    
-    let root = menu_tree(
-        debug_button("Scroll"),
+fn session_menu<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    let session = app.active_session();
+    let duplicate = session
+        .filter(|tab| tab.is_local() || tab.connection().is_some())
+        .map(|tab| Message::DuplicateSession(tab.id(), Placement::Tab));
+    let reconnect = session
+        .filter(|tab| tab.is_disconnected())
+        .map(|tab| Message::Shell(tab.id(), ShellMessage::ReconnectNow));
+    let disconnect = session
+        .filter(|tab| tab.connection().is_some() || tab.is_disconnected())
+        .map(|tab| Message::Shell(tab.id(), ShellMessage::Disconnect));
+    let panes = match app.active_tab {
+        TabId::Session(id) => app.session_tabs.iter().find(|panes| panes.id() == id),
+        _ => None,
+    };
+    let several = panes.map_or(false, |panes| panes.panes().len() > 1);
+    let maximize = if panes.map_or(false, Panes::is_maximized) {
+        "Restore pane"
+    } else {
+        "Maximize pane"
+    };
+    let in_session = session.is_some();
+
+    menu_tree(
+        menu_label("Session"),
         vec![
-            debug_item("ajrs"), // 0
-            debug_item("bsdfho"),
-            debug_item("clkjhbf"),
-            debug_item("dekjdaud"),
+            menu_item("Duplicate session", None, duplicate),
+            menu_item("Reconnect", None, reconnect),
+            menu_item("Disconnect", None, disconnect),
+            separator(),
+            action_item(app, "Split right", Action::SplitRight, in_session),
+            action_item(app, "Split down", Action::SplitDown, in_session),
+            action_item(app, maximize, Action::MaximizePane, several),
+            action_item(app, "Close pane", Action::ClosePane, in_session),
         ],
-    );
+    )
+}
+
+fn tools_menu<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    // Only SSH sessions have SFTP and port forwards.
+    let ssh = app
+        .active_session()
+        .filter(|tab| tab.connection().and_then(|c| c.ssh_session()).is_some())
+        .map(|tab| tab.id());
 
-    root
+    menu_tree(
+        menu_label("Tools"),
+        vec![
+            menu_item("SFTP browser", None, ssh.map(Message::OpenSftp)),
+            menu_item(
+                "Port forwards",
+                None,
+                ssh.map(|id| Message::Shell(id, ShellMessage::ToggleForwards)),
+            ),
+            separator(),
+            menu_item(
+                "Settings",
+                None,
+                Some(Message::TabSelected(TabId::Settings)),
+            ),
+        ],
+    )
 }
 
-fn menu_5<'a>(app: &App) -> MenuTree<'a, Message, iced::Renderer> {
-    let slider_count = 3;
-    let slider_width = 30;
-    let spacing = 4;
-
-    let [r, g, b, _] = app.theme.palette().primary.into_rgba8();
-
-    let sliders = menu_tree!(row![
-        vertical_slider(0..=255, r, move |x| Message::ColorChange(Color::from_rgb8(
-            x, g, b
-        )))
-        .width(30),
-        vertical_slider(0..=255, g, move |x| Message::ColorChange(Color::from_rgb8(
-            r, x, b
-        )))
-        .width(30),
-        vertical_slider(0..=255, b, move |x| Message::ColorChange(Color::from_rgb8(
-            r, g, x
-        )))
-        .width(30),
-    ]
-    .spacing(4))
-    .height(100);
-
-    let root = menu_tree(
-        debug_button("Static"),
-        vec![labeled_separator("Primary"), sliders],
+fn help_menu<'a>(_app: &App) -> MenuTree<'a, Message, iced::Renderer> {
+    menu_tree(
+        menu_label("Help"),
+        vec![menu_item("About my-ssh", None, Some(Message::ShowAbout(true)))],
     )
-    .width(slider_width * slider_count + (slider_count - 1) * spacing);
+}
 
-    root
+fn about<'a>() -> Element<'a, Message> {
+    Card::new(
+        text("About my-ssh"),
+        col![
+            text(format!("my-ssh {}", env!("CARGO_PKG_VERSION"))),
+            text("An SSH client with tabs, saved sessions, SFTP and port forwarding."),
+        ]
+        .spacing(10),
+    )
+    .foot(button("Close").on_press(Message::ShowAbout(false)))
+    .max_width(400.0)
+    .on_close(Message::ShowAbout(false))
+    .into()
 }
 
 trait Tab {
@@ -0,0 +1,570 @@
+//! Terminal colour schemes: the built-in ones, and importers for schemes
+//! written for iTerm2, Windows Terminal, Alacritty and base16.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_NAME: &str = "xterm";
+
+/// Colour names in ANSI order, as Windows Terminal and Alacritty spell them.
+const WINDOWS_TERMINAL_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "purple", "cyan", "white",
+];
+const ALACRITTY_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Which base16 colour each ANSI colour is, as base16-shell maps them.
+const BASE16_ANSI: [usize; 16] = [
+    0x0, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x5, 0x3, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x7,
+];
+
+/// Saved as `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Reads `#rrggbb`, `0xrrggbb` or plain `rrggbb`.
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        let hex = hex.trim();
+        let hex = hex
+            .strip_prefix('#')
+            .or_else(|| hex.strip_prefix("0x"))
+            .unwrap_or(hex);
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// From channels between 0 and 1, as iTerm2 saves them.
+    fn from_fractions(r: f32, g: f32, b: f32) -> Rgb {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgb(channel(r), channel(g), channel(b))
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        Rgb::from_hex(&hex).ok_or_else(|| format!("invalid colour {}, expected #rrggbb", hex))
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(Rgb(r, g, b): Rgb) -> Self {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    pub foreground: Rgb,
+    pub background: Rgb,
+    /// The foreground colour if unset.
+    pub cursor: Option<Rgb>,
+    pub selection: Option<Rgb>,
+    /// Black, red, green, yellow, blue, magenta, cyan and white, then their
+    /// bright versions.
+    pub ansi: [Rgb; 16],
+}
+
+const fn rgb(hex: u32) -> Rgb {
+    Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+fn builtin(name: &str, foreground: u32, background: u32, ansi: [u32; 16]) -> Palette {
+    Palette {
+        name: name.to_string(),
+        foreground: rgb(foreground),
+        background: rgb(background),
+        cursor: None,
+        selection: None,
+        ansi: ansi.map(rgb),
+    }
+}
+
+/// The schemes that come with the program, the default first.
+pub fn builtins() -> Vec<Palette> {
+    let solarized = [
+        0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5, 0x002b36,
+        0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+    ];
+    vec![
+        builtin(
+            DEFAULT_NAME,
+            0xd1d1d1,
+            0x1c1c1c,
+            [
+                0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+                0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+            ],
+        ),
+        builtin("Solarized Dark", 0x839496, 0x002b36, solarized),
+        builtin("Solarized Light", 0x657b83, 0xfdf6e3, solarized),
+        builtin(
+            "Tango Dark",
+            0xd3d7cf,
+            0x2e3436,
+            [
+                0x2e3436, 0xcc0000, 0x4e9a06, 0xc4a000, 0x3465a4, 0x75507b, 0x06989a, 0xd3d7cf,
+                0x555753, 0xef2929, 0x8ae234, 0xfce94f, 0x729fcf, 0xad7fa8, 0x34e2e2, 0xeeeeec,
+            ],
+        ),
+    ]
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        builtins().swap_remove(0)
+    }
+}
+
+impl Palette {
+    /// The scheme called `name`, among the built-in and `imported` ones, or
+    /// the default.
+    pub fn find(name: &str, imported: &[Palette]) -> Palette {
+        builtins()
+            .into_iter()
+            .chain(imported.iter().cloned())
+            .find(|palette| palette.name == name)
+            .unwrap_or_default()
+    }
+
+    /// Colour `index` of the 256-colour palette: this scheme's sixteen, then
+    /// xterm's 6x6x6 cube and grey ramp.
+    pub fn indexed(&self, index: u8) -> Rgb {
+        match index {
+            0..=15 => self.ansi[usize::from(index)],
+            16..=231 => {
+                let index = index - 16;
+                let level = |value: u8| if value == 0 { 0 } else { 55 + 40 * value };
+                Rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            _ => {
+                let grey = 8 + 10 * (index - 232);
+                Rgb(grey, grey, grey)
+            }
+        }
+    }
+}
+
+/// Reads the schemes in a file, going by its extension: iTerm2
+/// `.itermcolors`, Windows Terminal `.json` (a scheme, or a whole
+/// settings.json), Alacritty `.toml` or base16 `.yaml`. Schemes without a
+/// name of their own are named after the file.
+pub fn import(path: &Path) -> Result<Vec<Palette>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let palettes = match extension.as_str() {
+        "itermcolors" => from_itermcolors(&text, &stem).map(|palette| vec![palette]),
+        "json" => from_windows_terminal(&text),
+        "toml" => from_alacritty(&text, &stem).map(|palette| vec![palette]),
+        "yaml" | "yml" => from_base16(&text, &stem).map(|palette| vec![palette]),
+        _ => {
+            return Err(format!(
+                "{} is not an .itermcolors, .json, .toml or .yaml colour scheme",
+                path.display()
+            ))
+        }
+    };
+    palettes.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// An iTerm2 property list: a dict of colour dicts with `Red Component`
+/// and so on between 0 and 1.
+fn from_itermcolors(text: &str, name: &str) -> Result<Palette, String> {
+    let entry = Regex::new(r"(?s)<key>([^<]*)</key>\s*<dict>(.*?)</dict>").unwrap();
+    let component =
+        Regex::new(r"<key>(\w+) Component</key>\s*<(?:real|integer)>([^<]*)</").unwrap();
+    let mut colors = HashMap::new();
+    for entry in entry.captures_iter(text) {
+        let mut channels = [None; 3];
+        for component in component.captures_iter(&entry[2]) {
+            let index = match &component[1] {
+                "Red" => 0,
+                "Green" => 1,
+                "Blue" => 2,
+                _ => continue,
+            };
+            channels[index] = component[2].trim().parse::<f32>().ok();
+        }
+        if let [Some(r), Some(g), Some(b)] = channels {
+            colors.insert(entry[1].to_string(), Rgb::from_fractions(r, g, b));
+        }
+    }
+    let color = |key: &str| {
+        colors
+            .get(key)
+            .copied()
+            .ok_or_else(|| format!("no {}", key))
+    };
+    let mut ansi = [Rgb(0, 0, 0); 16];
+    for (index, slot) in ansi.iter_mut().enumerate() {
+        *slot = color(&format!("Ansi {} Color", index))?;
+    }
+    Ok(Palette {
+        name: name.to_string(),
+        foreground: color("Foreground Color")?,
+        background: color("Background Color")?,
+        cursor: color("Cursor Color").ok(),
+        selection: color("Selection Color").ok(),
+        ansi,
+    })
+}
+
+fn from_windows_terminal(text: &str) -> Result<Vec<Palette>, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    match value.get("schemes") {
+        Some(serde_json::Value::Array(schemes)) if !schemes.is_empty() => {
+            schemes.iter().map(windows_terminal_scheme).collect()
+        }
+        Some(_) => Err(String::from("no schemes")),
+        None => windows_terminal_scheme(&value).map(|palette| vec![palette]),
+    }
+}
+
+fn windows_terminal_scheme(scheme: &serde_json::Value) -> Result<Palette, String> {
+    let color = |key: &str| {
+        scheme
+            .get(key)
+            .and_then(serde_json::Value::as_str)
+            .and_then(Rgb::from_hex)
+            .ok_or_else(|| format!("no valid {}", key))
+    };
+    let mut ansi = [Rgb(0, 0, 0); 16];
+    for (index, name) in WINDOWS_TERMINAL_NAMES.iter().enumerate() {
+        ansi[index] = color(name)?;
+        let mut bright = format!("bright{}", name);
+        bright[6..7].make_ascii_uppercase();
+        ansi[index + 8] = color(&bright)?;
+    }
+    Ok(Palette {
+        name: scheme
+            .get("name")
+            .and_then(serde_json::Value::as_str)
+            .ok_or("a scheme has no name")?
+            .to_string(),
+        foreground: color("foreground")?,
+        background: color("background")?,
+        cursor: color("cursorColor").ok(),
+        selection: color("selectionBackground").ok(),
+        ansi,
+    })
+}
+
+fn from_alacritty(text: &str, name: &str) -> Result<Palette, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let colors = table.get("colors").ok_or("no [colors] table")?;
+    let color = |section: &str, key: &str| {
+        colors
+            .get(section)
+            .and_then(|section| section.get(key))
+            .and_then(toml::Value::as_str)
+            .and_then(Rgb::from_hex)
+            .ok_or_else(|| format!("no valid colors.{}.{}", section, key))
+    };
+    let mut ansi = [Rgb(0, 0, 0); 16];
+    for (index, key) in ALACRITTY_NAMES.iter().enumerate() {
+        ansi[index] = color("normal", key)?;
+        ansi[index + 8] = color("bright", key)?;
+    }
+    // The cursor and selection may be `CellForeground` and the like, which
+    // follow the text rather than being colours.
+    Ok(Palette {
+        name: name.to_string(),
+        foreground: color("primary", "foreground")?,
+        background: color("primary", "background")?,
+        cursor: color("cursor", "cursor").ok(),
+        selection: color("selection", "background").ok(),
+        ansi,
+    })
+}
+
+/// A YAML scalar without its quotes or a trailing comment.
+fn yaml_scalar(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(quoted) = value.strip_prefix(quote) {
+            return quoted.split(quote).next().unwrap_or_default();
+        }
+    }
+    value.split(" #").next().unwrap_or_default().trim()
+}
+
+/// A base16 scheme: `base00` to `base0F`, at the top level or, in the
+/// newer layout, under `palette`. The extra colours of a base24 scheme are
+/// ignored.
+fn from_base16(text: &str, fallback_name: &str) -> Result<Palette, String> {
+    let mut name = None;
+    let mut base = [None; 16];
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = yaml_scalar(value);
+        if matches!(key, "scheme" | "name") && name.is_none() && !value.is_empty() {
+            name = Some(value.to_string());
+        }
+        let index = key
+            .strip_prefix("base")
+            .filter(|index| index.len() == 2)
+            .and_then(|index| usize::from_str_radix(index, 16).ok())
+            // base24 adds base10 to base17, which have no ANSI slot here.
+            .filter(|&index| index < base.len());
+        if let Some(index) = index {
+            base[index] = Rgb::from_hex(value);
+        }
+    }
+    let color = |index: usize| base[index].ok_or_else(|| format!("no valid base{:02X}", index));
+    let mut ansi = [Rgb(0, 0, 0); 16];
+    for (slot, &index) in ansi.iter_mut().zip(BASE16_ANSI.iter()) {
+        *slot = color(index)?;
+    }
+    Ok(Palette {
+        name: name.unwrap_or_else(|| fallback_name.to_string()),
+        foreground: color(0x5)?,
+        background: color(0x0)?,
+        cursor: Some(color(0x5)?),
+        selection: Some(color(0x2)?),
+        ansi,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE16: [&str; 16] = [
+        "181818", "282828", "383838", "585858", "b8b8b8", "d8d8d8", "e8e8e8", "f8f8f8", "ab4642",
+        "dc9656", "f7ca88", "a1b56c", "86c1b9", "7cafc2", "ba8baf", "a16946",
+    ];
+
+    fn hex(hex: &str) -> Rgb {
+        Rgb::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn hex_colours() {
+        assert_eq!(Rgb::from_hex("#1c1C1c"), Some(Rgb(0x1c, 0x1c, 0x1c)));
+        assert_eq!(Rgb::from_hex("0xff0080"), Some(Rgb(0xff, 0, 0x80)));
+        assert_eq!(Rgb::from_hex(" 00ff00 "), Some(Rgb(0, 0xff, 0)));
+        for bad in ["#fff", "#gggggg", "ff00ff00", "", "#ffé000"] {
+            assert_eq!(Rgb::from_hex(bad), None, "{}", bad);
+        }
+        assert_eq!(String::from(Rgb(0xab, 0x01, 0xff)), "#ab01ff");
+    }
+
+    #[test]
+    fn indexed_colours() {
+        let palette = Palette::default();
+        assert_eq!(palette.indexed(1), palette.ansi[1]);
+        assert_eq!(palette.indexed(15), palette.ansi[15]);
+        assert_eq!(palette.indexed(16), Rgb(0, 0, 0));
+        assert_eq!(palette.indexed(196), Rgb(255, 0, 0));
+        assert_eq!(palette.indexed(231), Rgb(255, 255, 255));
+        assert_eq!(palette.indexed(110), Rgb(135, 175, 215));
+        assert_eq!(palette.indexed(232), Rgb(8, 8, 8));
+        assert_eq!(palette.indexed(255), Rgb(238, 238, 238));
+    }
+
+    /// A base16 scheme with its colours indented by `prefix`.
+    fn base16_yaml(prefix: &str) -> String {
+        let mut text = String::from("scheme: \"Default Dark\" # a comment\nauthor: someone\n");
+        for (index, color) in BASE16.iter().enumerate() {
+            text.push_str(&format!("{}base{:02X}: \"{}\"\n", prefix, index, color));
+        }
+        text
+    }
+
+    fn assert_base16(palette: &Palette) {
+        assert_eq!(palette.name, "Default Dark");
+        assert_eq!(palette.background, hex(BASE16[0]));
+        assert_eq!(palette.foreground, hex(BASE16[5]));
+        assert_eq!(palette.selection, Some(hex(BASE16[2])));
+        assert_eq!(palette.ansi[1], hex(BASE16[8]));
+        assert_eq!(palette.ansi[8], hex(BASE16[3]));
+        assert_eq!(palette.ansi[15], hex(BASE16[7]));
+    }
+
+    #[test]
+    fn base16() {
+        assert_base16(&from_base16(&base16_yaml(""), "file").unwrap());
+
+        // The newer layout, with the colours under `palette`.
+        let text = format!("system: base16\npalette:\n{}", base16_yaml("  "));
+        assert_base16(&from_base16(&text, "file").unwrap());
+
+        let text = base16_yaml("").replace("base0E: \"ba8baf\"", "base0E: none");
+        assert_eq!(from_base16(&text, "file").unwrap_err(), "no valid base0E");
+
+        let text = base16_yaml("").replacen("scheme:", "title:", 1);
+        assert_eq!(from_base16(&text, "file").unwrap().name, "file");
+    }
+
+    /// base24 goes on to base17; the extra colours must not trip it up.
+    #[test]
+    fn base24() {
+        let mut text = base16_yaml("");
+        for index in 0x10..0x18 {
+            text.push_str(&format!("base{:02X}: \"123456\"\n", index));
+        }
+        text.push_str("baseFF: \"123456\"\n");
+        assert_base16(&from_base16(&text, "file").unwrap());
+    }
+
+    fn iterm_color(key: &str, (r, g, b): (f32, f32, f32)) -> String {
+        format!(
+            "<key>{}</key>\n<dict>\n<key>Alpha Component</key><real>1</real>\n\
+             <key>Blue Component</key><real>{}</real>\n\
+             <key>Green Component</key><real>{}</real>\n\
+             <key>Red Component</key><integer>{}</integer>\n</dict>\n",
+            key, b, g, r
+        )
+    }
+
+    #[test]
+    fn itermcolors() {
+        let mut text = String::from("<plist version=\"1.0\">\n<dict>\n");
+        for index in 0..16 {
+            text.push_str(&iterm_color(
+                &format!("Ansi {} Color", index),
+                (0.0, 0.0, 0.0),
+            ));
+        }
+        text.push_str(&iterm_color("Foreground Color", (1.0, 0.5, 0.0)));
+        text.push_str("</dict>\n</plist>\n");
+        assert_eq!(
+            from_itermcolors(&text, "Theme").unwrap_err(),
+            "no Background Color"
+        );
+
+        text.push_str(&iterm_color("Background Color", (0.0, 0.0, 1.5)));
+        let palette = from_itermcolors(&text, "Theme").unwrap();
+        assert_eq!(palette.name, "Theme");
+        assert_eq!(palette.foreground, Rgb(255, 128, 0));
+        assert_eq!(palette.background, Rgb(0, 0, 255));
+        assert_eq!(palette.cursor, None);
+        assert_eq!(palette.ansi[15], Rgb(0, 0, 0));
+    }
+
+    fn windows_terminal_scheme(name: &str) -> String {
+        let mut fields = vec![
+            format!("\"name\": \"{}\"", name),
+            String::from("\"foreground\": \"#CCCCCC\""),
+            String::from("\"background\": \"#0C0C0C\""),
+            String::from("\"cursorColor\": \"#FFFFFF\""),
+        ];
+        for (index, color) in WINDOWS_TERMINAL_NAMES.iter().enumerate() {
+            let mut bright = format!("bright{}", color);
+            bright[6..7].make_ascii_uppercase();
+            fields.push(format!("\"{}\": \"#00000{}\"", color, index));
+            fields.push(format!("\"{}\": \"#10000{}\"", bright, index));
+        }
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    #[test]
+    fn windows_terminal() {
+        let palettes = from_windows_terminal(&windows_terminal_scheme("Campbell")).unwrap();
+        assert_eq!(palettes.len(), 1);
+        let palette = &palettes[0];
+        assert_eq!(palette.name, "Campbell");
+        assert_eq!(palette.background, Rgb(0x0c, 0x0c, 0x0c));
+        assert_eq!(palette.cursor, Some(Rgb(0xff, 0xff, 0xff)));
+        assert_eq!(palette.selection, None);
+        assert_eq!(palette.ansi[5], Rgb(0, 0, 5));
+        assert_eq!(palette.ansi[13], Rgb(0x10, 0, 5));
+
+        // A whole settings.json.
+        let settings = format!(
+            "{{\"profiles\": {{}}, \"schemes\": [{}, {}]}}",
+            windows_terminal_scheme("One"),
+            windows_terminal_scheme("Two")
+        );
+        let names: Vec<_> = from_windows_terminal(&settings)
+            .unwrap()
+            .into_iter()
+            .map(|palette| palette.name)
+            .collect();
+        assert_eq!(names, ["One", "Two"]);
+
+        assert!(from_windows_terminal("{\"schemes\": []}").is_err());
+        let unnamed = windows_terminal_scheme("x").replace("\"name\": \"x\", ", "");
+        assert!(from_windows_terminal(&unnamed).is_err());
+        assert!(from_windows_terminal("not json").is_err());
+    }
+
+    #[test]
+    fn alacritty() {
+        let mut text = String::from(
+            "[colors.primary]\nforeground = \"#d8d8d8\"\nbackground = \"0x181818\"\n\
+             [colors.cursor]\ntext = \"CellBackground\"\ncursor = \"CellForeground\"\n",
+        );
+        for section in ["normal", "bright"] {
+            text.push_str(&format!("[colors.{}]\n", section));
+            for (index, color) in ALACRITTY_NAMES.iter().enumerate() {
+                let value = if section == "normal" {
+                    index
+                } else {
+                    index + 8
+                };
+                text.push_str(&format!("{} = \"#0000{:02x}\"\n", color, value));
+            }
+        }
+        let palette = from_alacritty(&text, "alacritty").unwrap();
+        assert_eq!(palette.name, "alacritty");
+        assert_eq!(palette.foreground, Rgb(0xd8, 0xd8, 0xd8));
+        assert_eq!(palette.background, Rgb(0x18, 0x18, 0x18));
+        // `CellForeground` is not a colour.
+        assert_eq!(palette.cursor, None);
+        for (index, color) in palette.ansi.iter().enumerate() {
+            assert_eq!(*color, Rgb(0, 0, index as u8));
+        }
+
+        let text = text.replace("magenta = \"#000005\"", "magenta = \"red\"");
+        assert_eq!(
+            from_alacritty(&text, "alacritty").unwrap_err(),
+            "no valid colors.normal.magenta"
+        );
+        assert!(from_alacritty("[window]\n", "alacritty").is_err());
+    }
+
+    #[test]
+    fn imports_by_extension() {
+        let dir = std::env::temp_dir().join(format!("palette-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Ocean.YAML");
+        fs::write(&path, base16_yaml("").replacen("scheme:", "title:", 1)).unwrap();
+        let palettes = import(&path).unwrap();
+        assert_eq!(palettes[0].name, "Ocean");
+
+        let path = dir.join("scheme.conf");
+        fs::write(&path, "").unwrap();
+        assert!(import(&path)
+            .unwrap_err()
+            .contains("is not an .itermcolors"));
+        assert!(import(&dir.join("missing.json"))
+            .unwrap_err()
+            .starts_with("Could not read"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
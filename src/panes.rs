@@ -0,0 +1,474 @@
+//! The terminal panes of a tab, tiled by splitting one pane in two over and
+//! over. Panes are known by the id of the shell tab they show.
+
+use iced::{Element, Rectangle};
+use iced_aw::{split, Split};
+
+/// Which way a pane is cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    /// Side by side.
+    Vertical,
+    /// One above the other.
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug)]
+enum Node {
+    Pane(usize),
+    Split {
+        id: usize,
+        axis: Axis,
+        /// Where the divider was dragged to, in pixels from the left or top.
+        divider: Option<u16>,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn panes(&self, panes: &mut Vec<usize>) {
+        match self {
+            Node::Pane(pane) => panes.push(*pane),
+            Node::Split { first, second, .. } => {
+                first.panes(panes);
+                second.panes(panes);
+            }
+        }
+    }
+
+    /// Where each pane is, in a unit square. Dragged dividers are taken to
+    /// be in the middle, which is near enough to tell what is beside what.
+    fn bounds(&self, bounds: Rectangle, panes: &mut Vec<(usize, Rectangle)>) {
+        match self {
+            Node::Pane(pane) => panes.push((*pane, bounds)),
+            Node::Split {
+                axis,
+                first,
+                second,
+                ..
+            } => {
+                let (a, b) = match axis {
+                    Axis::Vertical => {
+                        let width = bounds.width / 2.0;
+                        (
+                            Rectangle { width, ..bounds },
+                            Rectangle {
+                                x: bounds.x + width,
+                                width,
+                                ..bounds
+                            },
+                        )
+                    }
+                    Axis::Horizontal => {
+                        let height = bounds.height / 2.0;
+                        (
+                            Rectangle { height, ..bounds },
+                            Rectangle {
+                                y: bounds.y + height,
+                                height,
+                                ..bounds
+                            },
+                        )
+                    }
+                };
+                first.bounds(a, panes);
+                second.bounds(b, panes);
+            }
+        }
+    }
+
+    /// Replaces `pane` with what `f` makes of it.
+    fn replace(self, pane: usize, f: &mut impl FnMut(Node) -> Option<Node>) -> Option<Node> {
+        match self {
+            Node::Pane(other) if other == pane => f(self),
+            Node::Pane(_) => Some(self),
+            Node::Split {
+                id,
+                axis,
+                divider,
+                first,
+                second,
+            } => match (first.replace(pane, f), second.replace(pane, f)) {
+                (Some(first), Some(second)) => Some(Node::Split {
+                    id,
+                    axis,
+                    divider,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                // The split goes with the pane, leaving the other side
+                // in its place.
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+        }
+    }
+
+    fn divider(&mut self, split: usize) -> Option<&mut Option<u16>> {
+        match self {
+            Node::Pane(_) => None,
+            Node::Split {
+                id,
+                divider,
+                first,
+                second,
+                ..
+            } => {
+                if *id == split {
+                    Some(divider)
+                } else {
+                    first.divider(split).or_else(|| second.divider(split))
+                }
+            }
+        }
+    }
+}
+
+/// The panes of one tab, one of which has the keyboard.
+#[derive(Debug)]
+pub struct Panes {
+    id: usize,
+    root: Node,
+    focused: usize,
+    /// Whether the focused pane fills the tab, hiding the others.
+    maximized: bool,
+    next_split: usize,
+}
+
+impl Panes {
+    /// A tab `id` holding just `pane`.
+    pub fn new(id: usize, pane: usize) -> Self {
+        Panes {
+            id,
+            root: Node::Pane(pane),
+            focused: pane,
+            maximized: false,
+            next_split: 0,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.maximized
+    }
+
+    /// Every pane, left to right and top to bottom.
+    pub fn panes(&self) -> Vec<usize> {
+        let mut panes = Vec::new();
+        self.root.panes(&mut panes);
+        panes
+    }
+
+    pub fn contains(&self, pane: usize) -> bool {
+        self.panes().contains(&pane)
+    }
+
+    pub fn focus(&mut self, pane: usize) {
+        if self.contains(pane) {
+            self.focused = pane;
+        }
+    }
+
+    /// Cuts `pane` in two along `axis`, with `new_pane` in the right or
+    /// bottom half, and focuses the new one.
+    pub fn split(&mut self, pane: usize, axis: Axis, new_pane: usize) {
+        if !self.contains(pane) {
+            return;
+        }
+        let id = self.next_split;
+        self.next_split += 1;
+        let root = std::mem::replace(&mut self.root, Node::Pane(pane));
+        self.root = root
+            .replace(pane, &mut |node| {
+                Some(Node::Split {
+                    id,
+                    axis,
+                    divider: None,
+                    first: Box::new(node),
+                    second: Box::new(Node::Pane(new_pane)),
+                })
+            })
+            .unwrap_or(Node::Pane(pane));
+        self.focused = new_pane;
+        self.maximized = false;
+    }
+
+    /// Takes `pane` out, giving its room to its neighbour. Returns `false`
+    /// if it was the last one.
+    pub fn remove(&mut self, pane: usize) -> bool {
+        let panes = self.panes();
+        let Some(index) = panes.iter().position(|&other| other == pane) else {
+            return true;
+        };
+        let root = std::mem::replace(&mut self.root, Node::Pane(pane));
+        match root.replace(pane, &mut |_| None) {
+            Some(root) => self.root = root,
+            None => return false,
+        }
+        if self.focused == pane {
+            let neighbour = if index > 0 { index - 1 } else { index + 1 };
+            self.focused = panes[neighbour];
+            self.maximized = false;
+        }
+        true
+    }
+
+    /// Moves the focus to the nearest pane in `direction`, if there is one.
+    pub fn focus_toward(&mut self, direction: Direction) {
+        let mut bounds = Vec::new();
+        let unit = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        self.root.bounds(unit, &mut bounds);
+        let Some(&(_, from)) = bounds.iter().find(|&&(pane, _)| pane == self.focused) else {
+            return;
+        };
+        let overlap =
+            |a: f32, a_len: f32, b: f32, b_len: f32| (a + a_len).min(b + b_len) - a.max(b);
+        let nearest = bounds
+            .iter()
+            .filter(|&&(pane, _)| pane != self.focused)
+            .filter_map(|&(pane, to)| {
+                // How far past the edge on that side, and how much of the
+                // edge the two share.
+                let (gap, shared) = match direction {
+                    Direction::Left => (
+                        from.x - (to.x + to.width),
+                        overlap(from.y, from.height, to.y, to.height),
+                    ),
+                    Direction::Right => (
+                        to.x - (from.x + from.width),
+                        overlap(from.y, from.height, to.y, to.height),
+                    ),
+                    Direction::Up => (
+                        from.y - (to.y + to.height),
+                        overlap(from.x, from.width, to.x, to.width),
+                    ),
+                    Direction::Down => (
+                        to.y - (from.y + from.height),
+                        overlap(from.x, from.width, to.x, to.width),
+                    ),
+                };
+                (gap > -f32::EPSILON && shared > f32::EPSILON).then_some((pane, gap, shared))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(b.2.total_cmp(&a.2)));
+        if let Some((pane, _, _)) = nearest {
+            self.focused = pane;
+            self.maximized = false;
+        }
+    }
+
+    pub fn toggle_maximized(&mut self) {
+        self.maximized = !self.maximized && self.panes().len() > 1;
+    }
+
+    pub fn resize(&mut self, split: usize, position: u16) {
+        if let Some(divider) = self.root.divider(split) {
+            *divider = Some(position);
+        }
+    }
+
+    /// The panes drawn by `pane`, with draggable dividers between them.
+    pub fn view<'a, Message: Clone + 'a>(
+        &self,
+        pane: &impl Fn(usize) -> Element<'a, Message>,
+        on_resize: impl Fn(usize, u16) -> Message + Clone + 'static,
+    ) -> Element<'a, Message> {
+        if self.maximized {
+            return pane(self.focused);
+        }
+        view_node(&self.root, pane, &on_resize)
+    }
+}
+
+fn view_node<'a, Message: Clone + 'a>(
+    node: &Node,
+    pane: &impl Fn(usize) -> Element<'a, Message>,
+    on_resize: &(impl Fn(usize, u16) -> Message + Clone + 'static),
+) -> Element<'a, Message> {
+    match node {
+        Node::Pane(id) => pane(*id),
+        Node::Split {
+            id,
+            axis,
+            divider,
+            first,
+            second,
+        } => {
+            let id = *id;
+            let on_resize = on_resize.clone();
+            Split::new(
+                view_node(first, pane, &on_resize),
+                view_node(second, pane, &on_resize),
+                *divider,
+                match axis {
+                    Axis::Vertical => split::Axis::Vertical,
+                    Axis::Horizontal => split::Axis::Horizontal,
+                },
+                move |position| on_resize(id, position),
+            )
+            .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pane 1 on the left, 2 above 3 on the right.
+    fn l_shaped() -> Panes {
+        let mut panes = Panes::new(0, 1);
+        panes.split(1, Axis::Vertical, 2);
+        panes.split(2, Axis::Horizontal, 3);
+        panes
+    }
+
+    fn focus_from(panes: &mut Panes, pane: usize, direction: Direction) -> usize {
+        panes.focus(pane);
+        panes.focus_toward(direction);
+        panes.focused()
+    }
+
+    #[test]
+    fn splitting() {
+        let mut panes = l_shaped();
+        assert_eq!(panes.panes(), [1, 2, 3]);
+        assert_eq!(panes.focused(), 3);
+
+        // Panes that aren't there can't be split or focused.
+        panes.split(4, Axis::Vertical, 5);
+        panes.focus(5);
+        assert_eq!(panes.panes(), [1, 2, 3]);
+        assert_eq!(panes.focused(), 3);
+    }
+
+    #[test]
+    fn removing_the_last_pane() {
+        let mut panes = Panes::new(0, 1);
+        assert!(!panes.remove(1));
+        assert_eq!(panes.panes(), [1]);
+
+        let mut panes = l_shaped();
+        assert!(panes.remove(4));
+        assert!(panes.remove(1));
+        assert!(panes.remove(2));
+        assert!(!panes.remove(3));
+        assert_eq!(panes.panes(), [3]);
+    }
+
+    /// The focus goes to the pane before, or after for the first one.
+    #[test]
+    fn removing_the_focused_pane() {
+        let mut panes = l_shaped();
+        panes.toggle_maximized();
+        assert!(panes.remove(3));
+        assert_eq!(panes.focused(), 2);
+        assert!(!panes.is_maximized());
+
+        let mut panes = l_shaped();
+        panes.focus(1);
+        assert!(panes.remove(1));
+        assert_eq!(panes.focused(), 2);
+
+        // Anything else keeps the focus where it was.
+        let mut panes = l_shaped();
+        assert!(panes.remove(1));
+        assert_eq!(panes.focused(), 3);
+    }
+
+    /// Taking out one side of a split leaves the other side in its place.
+    #[test]
+    fn removing_collapses_splits() {
+        let mut panes = l_shaped();
+        panes.resize(0, 100);
+        panes.resize(1, 200);
+        assert!(panes.remove(3));
+        assert_eq!(panes.panes(), [1, 2]);
+        assert!(panes.root.divider(1).is_none());
+        assert_eq!(panes.root.divider(0).copied(), Some(Some(100)));
+
+        // Pane 2 now has the whole right side.
+        assert_eq!(focus_from(&mut panes, 2, Direction::Down), 2);
+        assert_eq!(focus_from(&mut panes, 2, Direction::Left), 1);
+
+        let mut panes = l_shaped();
+        assert!(panes.remove(1));
+        assert_eq!(panes.panes(), [2, 3]);
+        assert!(panes.root.divider(0).is_none());
+        assert_eq!(focus_from(&mut panes, 2, Direction::Down), 3);
+        assert_eq!(focus_from(&mut panes, 3, Direction::Left), 3);
+    }
+
+    #[test]
+    fn focusing_by_direction() {
+        let mut panes = l_shaped();
+        assert_eq!(focus_from(&mut panes, 2, Direction::Left), 1);
+        assert_eq!(focus_from(&mut panes, 3, Direction::Left), 1);
+        assert_eq!(focus_from(&mut panes, 2, Direction::Down), 3);
+        assert_eq!(focus_from(&mut panes, 3, Direction::Up), 2);
+        // Both panes on the right touch 1; the first one wins.
+        assert_eq!(focus_from(&mut panes, 1, Direction::Right), 2);
+
+        // Nothing there: the focus stays.
+        assert_eq!(focus_from(&mut panes, 1, Direction::Left), 1);
+        assert_eq!(focus_from(&mut panes, 1, Direction::Up), 1);
+        assert_eq!(focus_from(&mut panes, 1, Direction::Down), 1);
+        assert_eq!(focus_from(&mut panes, 2, Direction::Right), 2);
+        assert_eq!(focus_from(&mut panes, 2, Direction::Up), 2);
+        assert_eq!(focus_from(&mut panes, 3, Direction::Down), 3);
+    }
+
+    /// The nearest pane wins over one further away on the same side.
+    #[test]
+    fn focusing_the_nearest_pane() {
+        // 1 | 2 | 3, with 2 and 3 sharing the right half.
+        let mut panes = Panes::new(0, 1);
+        panes.split(1, Axis::Vertical, 2);
+        panes.split(2, Axis::Vertical, 3);
+        assert_eq!(focus_from(&mut panes, 1, Direction::Right), 2);
+        assert_eq!(focus_from(&mut panes, 3, Direction::Left), 2);
+
+        panes.toggle_maximized();
+        assert!(panes.is_maximized());
+        panes.focus_toward(Direction::Left);
+        assert_eq!(panes.focused(), 1);
+        assert!(!panes.is_maximized());
+    }
+
+    #[test]
+    fn maximizing() {
+        let mut panes = Panes::new(0, 1);
+        panes.toggle_maximized();
+        assert!(!panes.is_maximized());
+
+        panes.split(1, Axis::Horizontal, 2);
+        panes.toggle_maximized();
+        assert!(panes.is_maximized());
+        panes.toggle_maximized();
+        assert!(!panes.is_maximized());
+
+        panes.toggle_maximized();
+        panes.split(2, Axis::Vertical, 3);
+        assert!(!panes.is_maximized());
+    }
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Join, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+use crate::socks;
+use crate::ssh_config;
+
+/// Longest HTTP response header accepted from a proxy.
+const MAX_HTTP_HEADER: usize = 8 * 1024;
+
+/// Anything SSH can run over.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyKind {
+    #[default]
+    None,
+    Command,
+    Http,
+    Socks5,
+}
+
+impl ProxyKind {
+    pub const ALL: [ProxyKind; 4] = [
+        ProxyKind::None,
+        ProxyKind::Command,
+        ProxyKind::Http,
+        ProxyKind::Socks5,
+    ];
+}
+
+impl From<ProxyKind> for String {
+    fn from(kind: ProxyKind) -> Self {
+        String::from(match kind {
+            ProxyKind::None => "Direct",
+            ProxyKind::Command => "ProxyCommand",
+            ProxyKind::Http => "HTTP proxy",
+            ProxyKind::Socks5 => "SOCKS5 proxy",
+        })
+    }
+}
+
+/// How a session reaches its first host, as saved. The proxy password is
+/// never saved.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub kind: ProxyKind,
+    /// The command line for `Command`, `host:port` of the proxy otherwise.
+    pub address: String,
+    pub username: String,
+}
+
+impl ProxySettings {
+    pub fn needs_password(&self) -> bool {
+        matches!(self.kind, ProxyKind::Http | ProxyKind::Socks5) && !self.username.is_empty()
+    }
+
+    pub fn proxy(&self, password: &str) -> Result<Option<Proxy>, String> {
+        let address = self.address.trim();
+        let server = || {
+            let (host, port) = match ssh_config::split_host_port(address) {
+                Some((Some(host), port)) if !host.is_empty() => (host, port),
+                _ => return Err(format!("Invalid proxy {}, expected host:port", address)),
+            };
+            let username = self.username.trim();
+            Ok(ProxyServer {
+                host,
+                port,
+                credentials: (!username.is_empty())
+                    .then(|| (username.to_string(), password.to_string())),
+            })
+        };
+        Ok(match self.kind {
+            ProxyKind::None => None,
+            ProxyKind::Command if address.is_empty() => {
+                return Err(String::from("Enter a proxy command"))
+            }
+            ProxyKind::Command => Some(Proxy::Command(address.to_string())),
+            ProxyKind::Http => Some(Proxy::Http(server()?)),
+            ProxyKind::Socks5 => Some(Proxy::Socks5(server()?)),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ProxyServer {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+}
+
+#[derive(Clone)]
+pub enum Proxy {
+    /// Run through `sh -c`, with `%h`, `%p`, `%r` and `%%` expanded; its
+    /// stdin and stdout carry the connection.
+    Command(String),
+    Http(ProxyServer),
+    Socks5(ProxyServer),
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Proxy::Command(command) => write!(f, "Command({})", command),
+            Proxy::Http(server) => write!(f, "Http({}:{})", server.host, server.port),
+            Proxy::Socks5(server) => write!(f, "Socks5({}:{})", server.host, server.port),
+        }
+    }
+}
+
+/// Opens a byte stream to `host:port`, directly or through `proxy`.
+pub async fn connect(
+    proxy: Option<&Proxy>,
+    host: &str,
+    port: u16,
+    username: &str,
+) -> Result<Box<dyn Transport>, String> {
+    match proxy {
+        None => {
+            let stream = TcpStream::connect((host, port))
+                .await
+                .map_err(|e| format!("Connection to {}:{} failed: {}", host, port, e))?;
+            Ok(Box::new(stream))
+        }
+        Some(Proxy::Command(command)) => {
+            let command = expand_command(command, host, port, username);
+            let stream = CommandStream::spawn(&command)
+                .map_err(|e| format!("Could not run ProxyCommand {}: {}", command, e))?;
+            Ok(Box::new(stream))
+        }
+        Some(Proxy::Http(server)) => {
+            let mut stream = connect_server(server).await?;
+            http_connect(&mut stream, host, port, server.credentials.as_ref())
+                .await
+                .map_err(|e| format!("HTTP proxy {}:{}: {}", server.host, server.port, e))?;
+            Ok(Box::new(stream))
+        }
+        Some(Proxy::Socks5(server)) => {
+            let mut stream = connect_server(server).await?;
+            let credentials = server
+                .credentials
+                .as_ref()
+                .map(|(user, password)| (user.as_str(), password.as_str()));
+            socks::connect(&mut stream, host, port, credentials)
+                .await
+                .map_err(|e| format!("SOCKS5 proxy {}:{}: {}", server.host, server.port, e))?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+async fn connect_server(server: &ProxyServer) -> Result<TcpStream, String> {
+    TcpStream::connect((server.host.as_str(), server.port))
+        .await
+        .map_err(|e| {
+            format!(
+                "Could not reach the proxy at {}:{}: {}",
+                server.host, server.port, e
+            )
+        })
+}
+
+fn expand_command(command: &str, host: &str, port: u16, username: &str) -> String {
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(host),
+            Some('p') => expanded.push_str(&port.to_string()),
+            Some('r') => expanded.push_str(username),
+            Some(other) => expanded.push(other),
+            None => {}
+        }
+    }
+    expanded
+}
+
+/// A proxy command's stdio. The child is killed when this is dropped.
+struct CommandStream {
+    _child: Child,
+    io: Join<ChildStdout, ChildStdin>,
+}
+
+impl CommandStream {
+    fn spawn(command: &str) -> io::Result<Self> {
+        let mut child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::new(io::ErrorKind::Other, "no stdio pipes"));
+        };
+        Ok(CommandStream {
+            _child: child,
+            io: tokio::io::join(stdout, stdin),
+        })
+    }
+}
+
+impl AsyncRead for CommandStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CommandStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// Asks an HTTP proxy to tunnel to `host:port` with `CONNECT`.
+async fn http_connect<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    credentials: Option<&(String, String)>,
+) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some((user, password)) = credentials {
+        let token = STANDARD.encode(format!("{}:{}", user, password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Read byte by byte so nothing after the header, which already belongs
+    // to the SSH server, is consumed.
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() == MAX_HTTP_HEADER {
+            return Err(String::from("response header too long"));
+        }
+        match stream.read_u8().await {
+            Ok(byte) => header.push(byte),
+            Err(_) => return Err(String::from("connection closed during CONNECT")),
+        }
+    }
+    let header = String::from_utf8_lossy(&header);
+    let status_line = header.lines().next().unwrap_or_default();
+    let mut fields = status_line.splitn(3, ' ');
+    let version = fields.next().unwrap_or_default();
+    let status = fields.next().unwrap_or_default();
+    if !version.starts_with("HTTP/") {
+        return Err(format!("unexpected response {}", status_line));
+    }
+    match status {
+        "200" => Ok(()),
+        "407" => Err(String::from("proxy authentication required")),
+        _ => Err(format!("CONNECT refused: {}", status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    /// Runs `http_connect` against a proxy that answers `response`, returning
+    /// its result, the request it sent and whatever it left unread.
+    async fn http_exchange(
+        host: &str,
+        credentials: Option<(&str, &str)>,
+        response: &[u8],
+    ) -> (Result<(), String>, String, Vec<u8>) {
+        let (mut client, mut server) = duplex(MAX_HTTP_HEADER * 2);
+        server.write_all(response).await.unwrap();
+        server.shutdown().await.unwrap();
+        let credentials = credentials.map(|(user, password)| (user.into(), password.into()));
+        let result = http_connect(&mut client, host, 22, credentials.as_ref()).await;
+        client.shutdown().await.unwrap();
+        let mut request = String::new();
+        server.read_to_string(&mut request).await.unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        (result, request, rest)
+    }
+
+    #[tokio::test]
+    async fn http_requests() {
+        let ok = b"HTTP/1.1 200 Connection established\r\n\r\n";
+        let (result, request, _) = http_exchange("example.com", None, ok).await;
+        result.unwrap();
+        assert_eq!(
+            request,
+            "CONNECT example.com:22 HTTP/1.1\r\nHost: example.com:22\r\n\r\n"
+        );
+
+        let (_, request, _) = http_exchange("2001:db8::1", None, ok).await;
+        assert!(request.starts_with("CONNECT [2001:db8::1]:22 HTTP/1.1\r\n"));
+
+        let (_, request, _) = http_exchange("example.com", Some(("alice", "secret")), ok).await;
+        assert!(request.contains("\r\nProxy-Authorization: Basic YWxpY2U6c2VjcmV0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn http_responses() {
+        // The SSH banner right behind the header is left for SSH.
+        let response = b"HTTP/1.0 200 OK\r\nVia: proxy\r\n\r\nSSH-2.0-OpenSSH\r\n";
+        let (result, _, rest) = http_exchange("host", None, response).await;
+        result.unwrap();
+        assert_eq!(rest, b"SSH-2.0-OpenSSH\r\n");
+
+        let response = b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n";
+        let (result, _, _) = http_exchange("host", None, response).await;
+        assert_eq!(result.unwrap_err(), "proxy authentication required");
+
+        let response = b"HTTP/1.1 403 Forbidden\r\n\r\n";
+        let (result, _, _) = http_exchange("host", None, response).await;
+        assert_eq!(
+            result.unwrap_err(),
+            "CONNECT refused: HTTP/1.1 403 Forbidden"
+        );
+
+        let response = b"SSH-2.0-OpenSSH\r\n\r\n";
+        let (result, _, _) = http_exchange("host", None, response).await;
+        assert_eq!(result.unwrap_err(), "unexpected response SSH-2.0-OpenSSH");
+
+        let (result, _, _) = http_exchange("host", None, b"HTTP/1.1 200 OK\r\n").await;
+        assert_eq!(result.unwrap_err(), "connection closed during CONNECT");
+
+        let response = [b'x'; MAX_HTTP_HEADER + 1];
+        let (result, _, _) = http_exchange("host", None, &response).await;
+        assert_eq!(result.unwrap_err(), "response header too long");
+    }
+
+    #[test]
+    fn command_expansion() {
+        assert_eq!(
+            expand_command("nc -X connect -x proxy:3128 %h %p", "host", 2222, "me"),
+            "nc -X connect -x proxy:3128 host 2222"
+        );
+        assert_eq!(
+            expand_command("ssh %r@gw -W %h:%p", "host", 22, "me"),
+            "ssh me@gw -W host:22"
+        );
+        assert_eq!(
+            expand_command("echo 100%% %x%", "host", 22, "me"),
+            "echo 100% x"
+        );
+    }
+
+    #[test]
+    fn settings() {
+        let settings = |kind, address: &str, username: &str| ProxySettings {
+            kind,
+            address: address.to_string(),
+            username: username.to_string(),
+        };
+
+        assert!(settings(ProxyKind::None, "ignored", "")
+            .proxy("")
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            settings(ProxyKind::Command, " nc %h %p ", "").proxy(""),
+            Ok(Some(Proxy::Command(command))) if command == "nc %h %p"
+        ));
+        assert!(settings(ProxyKind::Command, " ", "").proxy("").is_err());
+
+        let http = settings(ProxyKind::Http, "proxy:3128", "");
+        assert!(!http.needs_password());
+        assert!(matches!(
+            http.proxy(""),
+            Ok(Some(Proxy::Http(ProxyServer { host, port: 3128, credentials: None })))
+                if host == "proxy"
+        ));
+
+        let socks = settings(ProxyKind::Socks5, "[::1]:1080", " alice ");
+        assert!(socks.needs_password());
+        assert!(matches!(
+            socks.proxy("secret"),
+            Ok(Some(Proxy::Socks5(ProxyServer { host, port: 1080, credentials: Some(c) })))
+                if host == "::1" && c == ("alice".to_string(), "secret".to_string())
+        ));
+
+        for address in ["proxy", ":3128", "proxy:http", ""] {
+            assert!(
+                settings(ProxyKind::Http, address, "").proxy("").is_err(),
+                "{}",
+                address
+            );
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use std::thread;
+
+use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use tokio::sync::mpsc;
+
+use crate::ssh::{Connection, SessionEvent, SessionInput};
+
+fn shell_name() -> String {
+    std::env::var("SHELL")
+        .ok()
+        .and_then(|shell| {
+            Path::new(&shell)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| String::from("shell"))
+}
+
+/// Starts the user's login shell in a pseudo-terminal. It behaves like any
+/// other [`Connection`]: keystrokes go in through `send`, output comes back
+/// through its subscription.
+pub fn spawn_shell(columns: u16, rows: u16) -> Result<Connection, String> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows,
+            cols: columns,
+            ..PtySize::default()
+        })
+        .map_err(|e| format!("Could not open a pseudo-terminal: {}", e))?;
+
+    let mut command = CommandBuilder::new_default_prog();
+    command.env("TERM", "xterm-256color");
+    if let Some(home) = dirs::home_dir() {
+        command.cwd(home);
+    }
+    let child = pair
+        .slave
+        .spawn_command(command)
+        .map_err(|e| format!("Could not start {}: {}", shell_name(), e))?;
+    // Only the child should hold the slave open, so that we see EOF once it
+    // exits.
+    drop(pair.slave);
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("Could not read from the pseudo-terminal: {}", e))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("Could not write to the pseudo-terminal: {}", e))?;
+    let killer = child.clone_killer();
+
+    let (connection, input, events) = Connection::channel(format!("local: {}", shell_name()));
+
+    // Both ends of a pty are blocking file descriptors, so they get threads
+    // of their own rather than tying up the async runtime.
+    thread::spawn(move || read_output(reader, child, events));
+    thread::spawn(move || write_input(pair.master, writer, killer, input));
+
+    Ok(connection)
+}
+
+fn read_output(
+    mut reader: Box<dyn Read + Send>,
+    mut child: Box<dyn Child + Send + Sync>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) {
+    let mut buffer = [0; 8192];
+    // Linux reports EIO rather than EOF once the child has gone.
+    while let Ok(read @ 1..) = reader.read(&mut buffer) {
+        if events
+            .send(SessionEvent::Output(buffer[..read].to_vec()))
+            .is_err()
+        {
+            break;
+        }
+    }
+
+    let reason = match child.wait() {
+        Ok(status) if !status.success() => {
+            let _ = events.send(SessionEvent::Output(
+                format!("\r\n[exit status {}]\r\n", status.exit_code()).into_bytes(),
+            ));
+            None
+        }
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
+    };
+    let _ = events.send(SessionEvent::Closed(reason));
+}
+
+fn write_input(
+    // Kept alive for as long as the session is: dropping the master hangs up
+    // the terminal.
+    master: Box<dyn MasterPty + Send>,
+    mut writer: Box<dyn Write + Send>,
+    mut killer: Box<dyn ChildKiller + Send + Sync>,
+    mut input: mpsc::UnboundedReceiver<SessionInput>,
+) {
+    while let Some(input) = input.blocking_recv() {
+        match input {
+            SessionInput::Data(data) => {
+                if writer
+                    .write_all(&data)
+                    .and_then(|_| writer.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            SessionInput::Resize { columns, rows } => {
+                // Sets the size with TIOCSWINSZ, which sends the shell
+                // SIGWINCH.
+                let _ = master.resize(PtySize {
+                    rows,
+                    cols: columns,
+                    ..PtySize::default()
+                });
+            }
+            SessionInput::Close => {
+                let _ = killer.kill();
+                break;
+            }
+        }
+    }
+}
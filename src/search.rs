@@ -0,0 +1,306 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::terminal::{Cell, Terminal};
+
+/// A match on one line. `line` counts dropped lines too (see
+/// [`Terminal::dropped_lines`]) so it stays put as the scrollback moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub line: usize,
+    pub columns: Range<usize>,
+}
+
+/// The find bar's query and where it last landed.
+pub struct Search {
+    query: String,
+    regex: bool,
+    case_sensitive: bool,
+    pattern: Option<Regex>,
+    error: Option<String>,
+    current: Option<Match>,
+    /// The current match's position among all of them, and their number.
+    position: Option<(usize, usize)>,
+}
+
+/// A line's text with trailing blanks removed, and the column each
+/// character starts at by byte offset, ending with where the text ends.
+fn line_text(cells: &[Cell]) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::new();
+    let mut columns = Vec::new();
+    for (column, cell) in cells.iter().enumerate() {
+        if cell.flags.wide_spacer {
+            continue;
+        }
+        columns.push((text.len(), column));
+        text.push(cell.c);
+    }
+    let trimmed = text.trim_end_matches(' ').len();
+    text.truncate(trimmed);
+    let end = columns
+        .iter()
+        .find(|&&(offset, _)| offset == trimmed)
+        .map_or(cells.len(), |&(_, column)| column);
+    columns.retain(|&(offset, _)| offset < trimmed);
+    columns.push((trimmed, end));
+    (text, columns)
+}
+
+fn column_at(columns: &[(usize, usize)], offset: usize) -> usize {
+    let index = columns.partition_point(|&(start, _)| start < offset);
+    columns.get(index).map_or(0, |&(_, column)| column)
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Search {
+            query: String::new(),
+            regex: false,
+            case_sensitive: false,
+            pattern: None,
+            error: None,
+            current: None,
+            position: None,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn regex(&self) -> bool {
+        self.regex
+    }
+
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.position
+    }
+
+    pub fn current(&self) -> Option<&Match> {
+        self.current.as_ref()
+    }
+
+    /// Changes the query and jumps to the match nearest the bottom.
+    pub fn set_query(&mut self, terminal: &mut Terminal, query: String) {
+        self.query = query;
+        self.restart(terminal);
+    }
+
+    pub fn set_regex(&mut self, terminal: &mut Terminal, regex: bool) {
+        self.regex = regex;
+        self.restart(terminal);
+    }
+
+    pub fn set_case_sensitive(&mut self, terminal: &mut Terminal, case_sensitive: bool) {
+        self.case_sensitive = case_sensitive;
+        self.restart(terminal);
+    }
+
+    fn restart(&mut self, terminal: &mut Terminal) {
+        self.pattern = None;
+        self.error = None;
+        self.current = None;
+        self.position = None;
+        if self.query.is_empty() {
+            return;
+        }
+        let source = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        match RegexBuilder::new(&source)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+        {
+            Ok(pattern) => self.pattern = Some(pattern),
+            Err(e) => {
+                // Syntax errors span several lines; the last one says what
+                // is wrong.
+                let message = e.to_string();
+                let reason = message.lines().last().unwrap_or_default();
+                self.error = Some(reason.trim_start_matches("error: ").to_string());
+                return;
+            }
+        }
+        self.step(terminal, false);
+    }
+
+    /// Column ranges of every match in one line, for highlighting.
+    pub fn matches_in_line(&self, cells: &[Cell]) -> Vec<Range<usize>> {
+        let Some(pattern) = &self.pattern else {
+            return Vec::new();
+        };
+        let (text, columns) = line_text(cells);
+        pattern
+            .find_iter(&text)
+            .filter(|found| !found.is_empty())
+            .map(|found| column_at(&columns, found.start())..column_at(&columns, found.end()))
+            .collect()
+    }
+
+    fn all_matches(&self, terminal: &Terminal) -> Vec<Match> {
+        let dropped = terminal.dropped_lines();
+        (0..terminal.line_count())
+            .flat_map(|line| {
+                self.matches_in_line(terminal.line(line))
+                    .into_iter()
+                    .map(move |columns| Match {
+                        line: dropped + line,
+                        columns,
+                    })
+            })
+            .collect()
+    }
+
+    /// Moves to the next match down (`forward`) or up, wrapping around, and
+    /// scrolls it into view. Without a current match, goes to the last one.
+    pub fn step(&mut self, terminal: &mut Terminal, forward: bool) {
+        let matches = self.all_matches(terminal);
+        if matches.is_empty() {
+            self.current = None;
+            self.position = Some((0, 0));
+            return;
+        }
+        let key = |found: &Match| (found.line, found.columns.start);
+        let index = match &self.current {
+            None => matches.len() - 1,
+            Some(current) if forward => matches
+                .iter()
+                .position(|found| key(found) > key(current))
+                .unwrap_or(0),
+            Some(current) => matches
+                .iter()
+                .rposition(|found| key(found) < key(current))
+                .unwrap_or(matches.len() - 1),
+        };
+        let found = matches[index].clone();
+        if let Some(line) = found.line.checked_sub(terminal.dropped_lines()) {
+            terminal.reveal_line(line);
+        }
+        self.position = Some((index + 1, matches.len()));
+        self.current = Some(found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(output: &str) -> Terminal {
+        let mut terminal = Terminal::new(20, 3);
+        terminal.advance(output.replace('\n', "\r\n").as_bytes());
+        terminal
+    }
+
+    fn found(search: &Search) -> Option<(usize, Range<usize>)> {
+        search
+            .current()
+            .map(|found| (found.line, found.columns.clone()))
+    }
+
+    #[test]
+    fn plain_and_case() {
+        let mut terminal = terminal("Error one\nerror two\nno problem\nERROR three");
+        let mut search = Search::new();
+        search.set_query(&mut terminal, String::from("error"));
+        // The match nearest the bottom comes first.
+        assert_eq!(search.position(), Some((3, 3)));
+        assert_eq!(found(&search), Some((3, 0..5)));
+
+        search.set_case_sensitive(&mut terminal, true);
+        assert_eq!(search.position(), Some((1, 1)));
+        assert_eq!(found(&search), Some((1, 0..5)));
+
+        // Without regex, metacharacters are literal.
+        search.set_query(&mut terminal, String::from("e."));
+        assert_eq!(search.position(), Some((0, 0)));
+        assert_eq!(search.current(), None);
+
+        search.set_query(&mut terminal, String::new());
+        assert_eq!(search.position(), None);
+    }
+
+    #[test]
+    fn regex() {
+        let mut terminal = terminal("id=42 id=7\nid=x");
+        let mut search = Search::new();
+        search.set_regex(&mut terminal, true);
+        search.set_query(&mut terminal, String::from(r"id=\d+"));
+        assert_eq!(search.position(), Some((2, 2)));
+        assert_eq!(found(&search), Some((0, 6..10)));
+        assert_eq!(search.matches_in_line(terminal.line(0)), [0..5, 6..10]);
+        // Empty matches are not highlighted.
+        search.set_query(&mut terminal, String::from("z*"));
+        assert!(search.matches_in_line(terminal.line(0)).is_empty());
+
+        search.set_query(&mut terminal, String::from("id=("));
+        assert!(search.error().is_some());
+        assert_eq!(search.position(), None);
+        search.set_regex(&mut terminal, false);
+        assert_eq!(search.error(), None);
+        assert_eq!(search.position(), Some((0, 0)));
+    }
+
+    #[test]
+    fn next_and_previous() {
+        let mut terminal = terminal("a.a\nb\na");
+        let mut search = Search::new();
+        search.set_query(&mut terminal, String::from("a"));
+        assert_eq!(found(&search), Some((2, 0..1)));
+
+        // Forward from the last match wraps to the first.
+        search.step(&mut terminal, true);
+        assert_eq!(search.position(), Some((1, 3)));
+        assert_eq!(found(&search), Some((0, 0..1)));
+        search.step(&mut terminal, true);
+        assert_eq!(found(&search), Some((0, 2..3)));
+
+        search.step(&mut terminal, false);
+        assert_eq!(found(&search), Some((0, 0..1)));
+        search.step(&mut terminal, false);
+        assert_eq!(search.position(), Some((3, 3)));
+        assert_eq!(found(&search), Some((2, 0..1)));
+    }
+
+    /// Columns count cells, so a match after a wide character lands on the
+    /// right ones.
+    #[test]
+    fn wide_characters() {
+        let mut terminal = terminal("中文 text");
+        let mut search = Search::new();
+        search.set_query(&mut terminal, String::from("text"));
+        assert_eq!(found(&search), Some((0, 5..9)));
+        search.set_query(&mut terminal, String::from("文"));
+        assert_eq!(found(&search), Some((0, 2..4)));
+    }
+
+    /// Match lines stay put while older lines are dropped, and stepping
+    /// scrolls the match into view.
+    #[test]
+    fn scrolled_matches() {
+        let mut terminal = Terminal::new(20, 2);
+        terminal.set_scrollback_limit(3);
+        terminal.advance(b"x\r\n1\r\n2\r\nx\r\n3\r\n4");
+        let mut search = Search::new();
+        search.set_query(&mut terminal, String::from("x"));
+        assert_eq!(found(&search), Some((3, 0..1)));
+        assert_eq!(terminal.dropped_lines(), 1);
+        assert_eq!(terminal.display_offset(), 2);
+        assert_eq!(terminal.visible_line(1), 2);
+
+        // The first "x" is gone, so this wraps onto the same one.
+        search.step(&mut terminal, true);
+        assert_eq!(search.position(), Some((1, 1)));
+        assert_eq!(found(&search), Some((3, 0..1)));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::terminal::{Cell, Terminal};
+
+/// Characters besides letters and digits that a double-click treats as part
+/// of a word, so paths and URLs are picked up whole.
+const WORD_CHARS: &str = "_-./~:@+%#?=&";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// Everything between the two ends, in reading order.
+    Simple,
+    Word,
+    Line,
+    /// The rectangle with the two ends as corners.
+    Block,
+}
+
+/// A cell. `line` counts dropped lines too (see
+/// [`Terminal::dropped_lines`]) so it stays put as the scrollback moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    mode: SelectionMode,
+    anchor: Point,
+    head: Point,
+}
+
+fn is_word(cell: &Cell) -> bool {
+    cell.c.is_alphanumeric() || WORD_CHARS.contains(cell.c)
+}
+
+fn cells(terminal: &Terminal, line: usize) -> Option<&[Cell]> {
+    let line = line.checked_sub(terminal.dropped_lines())?;
+    (line < terminal.line_count()).then(|| terminal.line(line))
+}
+
+/// Text of `cells[columns]`, without wide-character spacers or trailing
+/// blanks.
+fn text(cells: &[Cell], start: usize, end: usize) -> String {
+    let end = end.min(cells.len().saturating_sub(1));
+    let text: String = cells
+        .get(start..=end)
+        .unwrap_or_default()
+        .iter()
+        .filter(|cell| !cell.flags.wide_spacer)
+        .map(|cell| cell.c)
+        .collect();
+    text.trim_end_matches(' ').to_string()
+}
+
+impl Selection {
+    pub fn new(mode: SelectionMode, at: Point) -> Self {
+        Selection {
+            mode,
+            anchor: at,
+            head: at,
+        }
+    }
+
+    pub fn extend(&mut self, to: Point) {
+        self.head = to;
+    }
+
+    /// Whether a plain click selected nothing.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.mode, SelectionMode::Simple | SelectionMode::Block)
+            && self.anchor == self.head
+    }
+
+    /// The first and last selected cells, with words and lines expanded.
+    fn bounds(&self, terminal: &Terminal) -> (Point, Point) {
+        let (mut start, mut end) = if self.anchor <= self.head {
+            (self.anchor, self.head)
+        } else {
+            (self.head, self.anchor)
+        };
+        match self.mode {
+            SelectionMode::Simple | SelectionMode::Block => {}
+            SelectionMode::Word => {
+                if let Some(cells) = cells(terminal, start.line) {
+                    if cells.get(start.column).is_some_and(is_word) {
+                        while start.column > 0 && is_word(&cells[start.column - 1]) {
+                            start.column -= 1;
+                        }
+                    }
+                }
+                if let Some(cells) = cells(terminal, end.line) {
+                    if cells.get(end.column).is_some_and(is_word) {
+                        while cells.get(end.column + 1).is_some_and(is_word) {
+                            end.column += 1;
+                        }
+                    }
+                }
+            }
+            SelectionMode::Line => {
+                start.column = 0;
+                end.column = usize::MAX;
+            }
+        }
+        (start, end)
+    }
+
+    pub fn contains(&self, terminal: &Terminal, point: Point) -> bool {
+        let (start, end) = self.bounds(terminal);
+        match self.mode {
+            SelectionMode::Block => {
+                let (left, right) = (
+                    self.anchor.column.min(self.head.column),
+                    self.anchor.column.max(self.head.column),
+                );
+                (start.line..=end.line).contains(&point.line)
+                    && (left..=right).contains(&point.column)
+            }
+            _ => (start..=end).contains(&point),
+        }
+    }
+
+    /// The selected text, one line per terminal line.
+    pub fn text(&self, terminal: &Terminal) -> String {
+        let (start, end) = self.bounds(terminal);
+        let (left, right) = (
+            self.anchor.column.min(self.head.column),
+            self.anchor.column.max(self.head.column),
+        );
+        let lines: Vec<String> = (start.line..=end.line)
+            .filter_map(|line| {
+                let cells = cells(terminal, line)?;
+                Some(match self.mode {
+                    SelectionMode::Block => text(cells, left, right),
+                    _ => {
+                        let first = if line == start.line { start.column } else { 0 };
+                        let last = if line == end.line {
+                            end.column
+                        } else {
+                            usize::MAX
+                        };
+                        text(cells, first, last)
+                    }
+                })
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 20 by 4 terminal showing `lines`.
+    fn terminal(lines: &[&str]) -> Terminal {
+        let mut terminal = Terminal::new(20, 4);
+        terminal.advance(lines.join("\r\n").as_bytes());
+        terminal
+    }
+
+    fn point(line: usize, column: usize) -> Point {
+        Point { line, column }
+    }
+
+    fn select(terminal: &Terminal, mode: SelectionMode, from: Point, to: Point) -> String {
+        let mut selection = Selection::new(mode, from);
+        selection.extend(to);
+        selection.text(terminal)
+    }
+
+    #[test]
+    fn simple() {
+        let terminal = terminal(&["hello world", "", "second line"]);
+        let simple = SelectionMode::Simple;
+        assert_eq!(
+            select(&terminal, simple, point(0, 6), point(2, 5)),
+            "world\n\nsecond"
+        );
+        // Either end can come first.
+        assert_eq!(
+            select(&terminal, simple, point(2, 5), point(0, 6)),
+            "world\n\nsecond"
+        );
+        // Blanks past the end of a line aren't copied.
+        assert_eq!(
+            select(&terminal, simple, point(0, 0), point(0, 19)),
+            "hello world"
+        );
+        assert_eq!(select(&terminal, simple, point(0, 12), point(0, 19)), "");
+
+        let mut selection = Selection::new(simple, point(0, 3));
+        assert!(selection.is_empty());
+        assert!(!Selection::new(SelectionMode::Word, point(0, 3)).is_empty());
+        selection.extend(point(2, 1));
+        assert!(!selection.is_empty());
+        assert!(selection.contains(&terminal, point(1, 19)));
+        assert!(selection.contains(&terminal, point(0, 3)));
+        assert!(!selection.contains(&terminal, point(0, 2)));
+        assert!(!selection.contains(&terminal, point(2, 2)));
+    }
+
+    #[test]
+    fn words() {
+        let terminal = terminal(&["vim ~/src/main.rs:42", "(see a.txt) ok", "hi"]);
+        let word = SelectionMode::Word;
+        // Paths and URLs are one word.
+        for column in [4, 9, 19] {
+            assert_eq!(
+                select(&terminal, word, point(0, column), point(0, column)),
+                "~/src/main.rs:42"
+            );
+        }
+        assert_eq!(select(&terminal, word, point(0, 1), point(0, 1)), "vim");
+        // Brackets are not.
+        assert_eq!(select(&terminal, word, point(1, 7), point(1, 7)), "a.txt");
+        assert_eq!(select(&terminal, word, point(1, 0), point(1, 0)), "(");
+        // A blank stays a blank.
+        assert_eq!(select(&terminal, word, point(1, 4), point(1, 4)), "");
+
+        // Dragging selects whole words at both ends.
+        assert_eq!(
+            select(&terminal, word, point(1, 9), point(0, 2)),
+            "vim ~/src/main.rs:42\n(see a.txt"
+        );
+        let mut selection = Selection::new(word, point(2, 1));
+        selection.extend(point(1, 13));
+        assert!(selection.contains(&terminal, point(1, 12)));
+        assert!(!selection.contains(&terminal, point(1, 11)));
+        assert_eq!(selection.text(&terminal), "ok\nhi");
+    }
+
+    #[test]
+    fn lines() {
+        let terminal = terminal(&["first", "second", "third"]);
+        let line = SelectionMode::Line;
+        assert_eq!(select(&terminal, line, point(1, 3), point(1, 3)), "second");
+        assert_eq!(
+            select(&terminal, line, point(2, 4), point(0, 2)),
+            "first\nsecond\nthird"
+        );
+        let selection = Selection::new(line, point(1, 3));
+        assert!(selection.contains(&terminal, point(1, 0)));
+        assert!(selection.contains(&terminal, point(1, 19)));
+        assert!(!selection.contains(&terminal, point(0, 19)));
+    }
+
+    #[test]
+    fn blocks() {
+        let terminal = terminal(&["abcdef", "ab", "", "abcdefgh"]);
+        let block = SelectionMode::Block;
+        assert_eq!(
+            select(&terminal, block, point(0, 1), point(3, 3)),
+            "bcd\nb\n\nbcd"
+        );
+        // Any two opposite corners make the same block.
+        assert_eq!(
+            select(&terminal, block, point(3, 1), point(0, 3)),
+            "bcd\nb\n\nbcd"
+        );
+        let mut selection = Selection::new(block, point(3, 1));
+        assert!(selection.is_empty());
+        selection.extend(point(0, 3));
+        assert!(selection.contains(&terminal, point(1, 2)));
+        assert!(!selection.contains(&terminal, point(1, 4)));
+        assert!(!selection.contains(&terminal, point(2, 0)));
+    }
+
+    /// Lines in the scrollback keep the width they were written at.
+    #[test]
+    fn blocks_across_widths() {
+        let mut terminal = Terminal::new(12, 2);
+        terminal.advance(b"0123456789ab\r\n0123456789ab\r\nshort\r\nrow");
+        terminal.resize(6, 2);
+        assert_eq!(terminal.line(0).len(), 12);
+        assert_eq!(terminal.line(2).len(), 6);
+        assert_eq!(
+            select(&terminal, SelectionMode::Block, point(0, 4), point(3, 8)),
+            "45678\n45678\nt\n"
+        );
+        assert_eq!(
+            select(&terminal, SelectionMode::Simple, point(1, 10), point(2, 2)),
+            "ab\nsho"
+        );
+    }
+
+    /// Wide characters are copied once, not with the cell they spill into.
+    #[test]
+    fn wide_characters() {
+        let terminal = terminal(&["a中文b  ", "x"]);
+        assert!(terminal.line(0)[2].flags.wide_spacer);
+        let simple = SelectionMode::Simple;
+        assert_eq!(
+            select(&terminal, simple, point(0, 0), point(0, 19)),
+            "a中文b"
+        );
+        assert_eq!(select(&terminal, simple, point(0, 2), point(0, 3)), "文");
+        assert_eq!(
+            select(&terminal, SelectionMode::Block, point(0, 4), point(1, 6)),
+            "b\n"
+        );
+    }
+
+    /// Points stay on their line while older lines leave the scrollback,
+    /// and lines that have left are skipped.
+    #[test]
+    fn dropped_lines() {
+        let mut terminal = Terminal::new(10, 2);
+        terminal.set_scrollback_limit(2);
+        terminal.advance(b"l0\r\nl1\r\nl2\r\nl3");
+        assert_eq!(terminal.dropped_lines(), 0);
+        let selection = Selection::new(SelectionMode::Line, point(1, 0));
+        let mut extended = selection;
+        extended.extend(point(3, 0));
+        assert_eq!(selection.text(&terminal), "l1");
+
+        terminal.advance(b"\r\nl4\r\nl5");
+        assert_eq!(terminal.dropped_lines(), 2);
+        assert_eq!(selection.text(&terminal), "");
+        assert_eq!(extended.text(&terminal), "l2\nl3");
+        assert_eq!(
+            select(&terminal, SelectionMode::Simple, point(5, 0), point(5, 9)),
+            "l5"
+        );
+        assert_eq!(
+            select(&terminal, SelectionMode::Simple, point(6, 0), point(6, 9)),
+            ""
+        );
+    }
+}
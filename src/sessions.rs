@@ -0,0 +1,957 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use iced::{
+    alignment::Horizontal,
+    theme,
+    widget::{Button, Checkbox, Column, Container, Radio, Row, Scrollable, Text, TextInput},
+    Alignment, Command, Element, Length,
+};
+use iced_aw::{Card, ContextMenu, Modal};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::forward;
+use crate::login::AuthMethod;
+use crate::proxy::{ProxyKind, ProxySettings};
+use crate::ssh_config::{self, ForwardSpec, JumpSpec};
+
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+const INDENT: f32 = 16.0;
+/// How many recently used hosts are remembered.
+const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Ssh,
+    Local,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 2] = [Protocol::Ssh, Protocol::Local];
+}
+
+impl From<Protocol> for String {
+    fn from(protocol: Protocol) -> Self {
+        String::from(match protocol {
+            Protocol::Ssh => "SSH",
+            Protocol::Local => "Local shell",
+        })
+    }
+}
+
+/// A host entry in the session tree. Passwords and passphrases are never
+/// saved; they are asked for on the Login tab when connecting.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedSession {
+    pub name: String,
+    /// Slash-separated folder path, empty for the top level.
+    pub folder: String,
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub auth_method: AuthMethod,
+    pub key_path: String,
+    pub forward_agent: bool,
+    pub local_forwards: Vec<ForwardSpec>,
+    pub remote_forwards: Vec<ForwardSpec>,
+    pub dynamic_forwards: Vec<ForwardSpec>,
+    /// Bastions to go through, first hop first.
+    pub jump_hosts: Vec<JumpHost>,
+    pub proxy: ProxySettings,
+}
+
+/// A jump host on the way to a saved session. Like the session itself it
+/// keeps how to authenticate, never a password.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JumpHost {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub auth_method: AuthMethod,
+    pub key_path: String,
+}
+
+impl JumpHost {
+    fn spec(&self) -> JumpSpec {
+        JumpSpec {
+            user: (!self.username.is_empty()).then(|| self.username.clone()),
+            host: self.host.clone(),
+            port: self.port,
+        }
+    }
+}
+
+fn format_jump_hosts(hops: &[JumpHost]) -> String {
+    let specs: Vec<JumpSpec> = hops.iter().map(JumpHost::spec).collect();
+    ssh_config::format_proxy_jump(&specs)
+}
+
+/// Parses a `ProxyJump` style list, keeping the credentials already set up
+/// for hops that were there before.
+fn parse_jump_hosts(text: &str, previous: &[JumpHost]) -> Result<Vec<JumpHost>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let specs = ssh_config::parse_proxy_jump(text).ok_or_else(|| {
+        format!(
+            "Invalid jump hosts {}, expected [user@]host[:port],...",
+            text
+        )
+    })?;
+    Ok(specs
+        .into_iter()
+        .map(|spec| {
+            let username = spec.user.unwrap_or_default();
+            let known = previous
+                .iter()
+                .find(|hop| hop.host == spec.host && hop.username == username);
+            JumpHost {
+                auth_method: known.map(|hop| hop.auth_method).unwrap_or_default(),
+                key_path: known.map(|hop| hop.key_path.clone()).unwrap_or_default(),
+                host: spec.host,
+                port: spec.port,
+                username,
+            }
+        })
+        .collect())
+}
+
+impl SavedSession {
+    pub fn summary(&self) -> String {
+        match self.protocol {
+            Protocol::Local => String::from("local shell"),
+            Protocol::Ssh => {
+                let mut summary = String::new();
+                if !self.username.is_empty() {
+                    summary.push_str(&format!("{}@", self.username));
+                }
+                summary.push_str(&self.host);
+                if let Some(port) = self.port {
+                    summary.push_str(&format!(":{}", port));
+                }
+                if let Some(hop) = self.jump_hosts.first() {
+                    summary.push_str(&format!(" via {}", hop.host));
+                }
+                summary
+            }
+        }
+    }
+
+    fn matches(&self, filter: &str) -> bool {
+        [&self.name, &self.host, &self.username, &self.folder]
+            .iter()
+            .any(|field| field.to_lowercase().contains(filter))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionsFile {
+    #[serde(default)]
+    folders: Vec<String>,
+    #[serde(default, rename = "session")]
+    sessions: Vec<SavedSession>,
+    /// Hosts connected to from the Login tab, most recent first.
+    #[serde(default)]
+    recent: Vec<SavedSession>,
+}
+
+fn sessions_path() -> Option<PathBuf> {
+    config::dir().map(|dir| dir.join("sessions.toml"))
+}
+
+fn load() -> Result<SessionsFile, String> {
+    let Some(path) = sessions_path() else {
+        return Ok(SessionsFile::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SessionsFile::default()),
+        Err(e) => Err(format!("Could not read {}: {}", path.display(), e)),
+    }
+}
+
+fn save(file: &SessionsFile) -> Result<(), String> {
+    let path = sessions_path().ok_or_else(|| String::from("No configuration directory"))?;
+    let contents = toml::to_string_pretty(file).map_err(|e| e.to_string())?;
+    config::write_atomic(&path, contents.as_bytes())
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+fn parent_folder(folder: &str) -> &str {
+    folder.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn folder_name(folder: &str) -> &str {
+    folder.rsplit_once('/').map_or(folder, |(_, name)| name)
+}
+
+fn normalize_folder(folder: &str) -> String {
+    folder
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn in_folder(session_folder: &str, folder: &str) -> bool {
+    session_folder == folder || session_folder.starts_with(&format!("{}/", folder))
+}
+
+#[derive(Debug, Clone)]
+pub enum SessionsMessage {
+    FilterChanged(String),
+    FolderToggled(String),
+    SessionClicked(usize),
+    Connect(usize),
+    NewSession(String),
+    Edit(usize),
+    Duplicate(usize),
+    Delete(usize),
+    NewFolder(String),
+    DeleteFolder(String),
+    NameChanged(String),
+    FolderChanged(String),
+    ProtocolSelected(Protocol),
+    HostChanged(String),
+    PortChanged(String),
+    UsernameChanged(String),
+    AuthMethodSelected(AuthMethod),
+    KeyPathChanged(String),
+    ForwardAgentToggled(bool),
+    LocalForwardsChanged(String),
+    RemoteForwardsChanged(String),
+    DynamicForwardsChanged(String),
+    JumpHostsChanged(String),
+    JumpAuthMethodSelected(usize, AuthMethod),
+    JumpKeyPathChanged(usize, String),
+    ProxyKindSelected(ProxyKind),
+    ProxyAddressChanged(String),
+    ProxyUsernameChanged(String),
+    EditorSaved,
+    EditorCancelled,
+    FolderNameChanged(String),
+    FolderSaved,
+    FolderCancelled,
+}
+
+struct Editor {
+    index: Option<usize>,
+    session: SavedSession,
+    port: String,
+    local_forwards: String,
+    remote_forwards: String,
+    dynamic_forwards: String,
+    jump_hosts: String,
+    error: Option<String>,
+}
+
+pub struct SessionsPanel {
+    sessions: Vec<SavedSession>,
+    recent: Vec<SavedSession>,
+    folders: Vec<String>,
+    collapsed: HashSet<String>,
+    filter: String,
+    selected: Option<usize>,
+    last_click: Option<(usize, Instant)>,
+    editor: Option<Editor>,
+    new_folder: Option<String>,
+    error: Option<String>,
+}
+
+impl SessionsPanel {
+    pub fn new() -> Self {
+        let (file, error) = match load() {
+            Ok(file) => (file, None),
+            Err(e) => (SessionsFile::default(), Some(e)),
+        };
+        SessionsPanel {
+            sessions: file.sessions,
+            recent: file.recent,
+            folders: file.folders,
+            collapsed: HashSet::new(),
+            filter: String::new(),
+            selected: None,
+            last_click: None,
+            editor: None,
+            new_folder: None,
+            error,
+        }
+    }
+
+    pub fn session(&self, index: usize) -> Option<&SavedSession> {
+        self.sessions.get(index)
+    }
+
+    pub fn recent(&self) -> &[SavedSession] {
+        &self.recent
+    }
+
+    /// Puts `session` at the top of the recent hosts, in place of an
+    /// earlier entry for the same host.
+    pub fn add_recent(&mut self, session: SavedSession) {
+        self.recent
+            .retain(|other| other.summary() != session.summary());
+        self.recent.insert(0, session);
+        self.recent.truncate(MAX_RECENT);
+        self.persist();
+    }
+
+    /// Opens the editor for a new entry, e.g. one filled in from the Login tab.
+    pub fn edit_new(&mut self, session: SavedSession) {
+        self.editor = Some(Editor {
+            index: None,
+            port: session
+                .port
+                .map(|port| port.to_string())
+                .unwrap_or_default(),
+            local_forwards: forward::format_forward_list(&session.local_forwards),
+            remote_forwards: forward::format_forward_list(&session.remote_forwards),
+            dynamic_forwards: forward::format_forward_list(&session.dynamic_forwards),
+            jump_hosts: format_jump_hosts(&session.jump_hosts),
+            session,
+            error: None,
+        });
+    }
+
+    fn persist(&mut self) {
+        let file = SessionsFile {
+            folders: self.folders.clone(),
+            sessions: self.sessions.clone(),
+            recent: self.recent.clone(),
+        };
+        self.error = save(&file).err();
+    }
+
+    fn all_folders(&self) -> BTreeSet<String> {
+        let mut folders = BTreeSet::new();
+        let named = self
+            .folders
+            .iter()
+            .chain(self.sessions.iter().map(|s| &s.folder));
+        for folder in named {
+            let mut folder = folder.as_str();
+            while !folder.is_empty() {
+                folders.insert(folder.to_string());
+                folder = parent_folder(folder);
+            }
+        }
+        folders
+    }
+
+    pub fn update(&mut self, message: SessionsMessage) -> Command<SessionsMessage> {
+        match message {
+            SessionsMessage::FilterChanged(value) => self.filter = value,
+            SessionsMessage::FolderToggled(folder) => {
+                if !self.collapsed.remove(&folder) {
+                    self.collapsed.insert(folder);
+                }
+            }
+            SessionsMessage::SessionClicked(index) => {
+                let now = Instant::now();
+                let double_click = matches!(
+                    self.last_click,
+                    Some((last, at)) if last == index && now.duration_since(at) < DOUBLE_CLICK
+                );
+                self.selected = Some(index);
+                if double_click {
+                    self.last_click = None;
+                    return Command::perform(async move { index }, SessionsMessage::Connect);
+                }
+                self.last_click = Some((index, now));
+            }
+            // Handled by the application, which owns the tabs.
+            SessionsMessage::Connect(_) => {}
+            SessionsMessage::NewSession(folder) => self.edit_new(SavedSession {
+                name: String::from("New session"),
+                folder,
+                ..SavedSession::default()
+            }),
+            SessionsMessage::Edit(index) => {
+                if let Some(session) = self.sessions.get(index) {
+                    self.editor = Some(Editor {
+                        index: Some(index),
+                        port: session
+                            .port
+                            .map(|port| port.to_string())
+                            .unwrap_or_default(),
+                        local_forwards: forward::format_forward_list(&session.local_forwards),
+                        remote_forwards: forward::format_forward_list(&session.remote_forwards),
+                        dynamic_forwards: forward::format_forward_list(&session.dynamic_forwards),
+                        jump_hosts: format_jump_hosts(&session.jump_hosts),
+                        session: session.clone(),
+                        error: None,
+                    });
+                }
+            }
+            SessionsMessage::Duplicate(index) => {
+                if let Some(session) = self.sessions.get(index) {
+                    let mut copy = session.clone();
+                    copy.name = format!("{} (copy)", copy.name);
+                    self.sessions.insert(index + 1, copy);
+                    self.selected = Some(index + 1);
+                    self.last_click = None;
+                    self.persist();
+                }
+            }
+            SessionsMessage::Delete(index) => {
+                if index < self.sessions.len() {
+                    self.sessions.remove(index);
+                    self.selected = None;
+                    self.last_click = None;
+                    self.persist();
+                }
+            }
+            SessionsMessage::NewFolder(parent) => {
+                self.new_folder = Some(match parent.as_str() {
+                    "" => String::new(),
+                    parent => format!("{}/", parent),
+                })
+            }
+            SessionsMessage::DeleteFolder(folder) => {
+                self.folders.retain(|named| !in_folder(named, &folder));
+                self.collapsed.remove(&folder);
+                self.persist();
+            }
+            SessionsMessage::FolderNameChanged(value) => {
+                if let Some(name) = &mut self.new_folder {
+                    *name = value;
+                }
+            }
+            SessionsMessage::FolderSaved => {
+                if let Some(name) = self.new_folder.take() {
+                    let folder = normalize_folder(&name);
+                    if !folder.is_empty() && !self.folders.contains(&folder) {
+                        self.folders.push(folder);
+                        self.persist();
+                    }
+                }
+            }
+            SessionsMessage::FolderCancelled => self.new_folder = None,
+            SessionsMessage::EditorSaved => self.save_editor(),
+            SessionsMessage::EditorCancelled => self.editor = None,
+            message => {
+                if let Some(editor) = &mut self.editor {
+                    let session = &mut editor.session;
+                    match message {
+                        SessionsMessage::NameChanged(value) => session.name = value,
+                        SessionsMessage::FolderChanged(value) => session.folder = value,
+                        SessionsMessage::ProtocolSelected(value) => session.protocol = value,
+                        SessionsMessage::HostChanged(value) => session.host = value,
+                        SessionsMessage::PortChanged(value) => editor.port = value,
+                        SessionsMessage::UsernameChanged(value) => session.username = value,
+                        SessionsMessage::AuthMethodSelected(value) => session.auth_method = value,
+                        SessionsMessage::KeyPathChanged(value) => session.key_path = value,
+                        SessionsMessage::ForwardAgentToggled(value) => {
+                            session.forward_agent = value
+                        }
+                        SessionsMessage::LocalForwardsChanged(value) => {
+                            editor.local_forwards = value
+                        }
+                        SessionsMessage::RemoteForwardsChanged(value) => {
+                            editor.remote_forwards = value
+                        }
+                        SessionsMessage::DynamicForwardsChanged(value) => {
+                            editor.dynamic_forwards = value
+                        }
+                        SessionsMessage::JumpHostsChanged(value) => {
+                            if let Ok(hops) = parse_jump_hosts(&value, &session.jump_hosts) {
+                                session.jump_hosts = hops;
+                            }
+                            editor.jump_hosts = value;
+                        }
+                        SessionsMessage::JumpAuthMethodSelected(index, value) => {
+                            if let Some(hop) = session.jump_hosts.get_mut(index) {
+                                hop.auth_method = value;
+                            }
+                        }
+                        SessionsMessage::ProxyKindSelected(value) => session.proxy.kind = value,
+                        SessionsMessage::ProxyAddressChanged(value) => {
+                            session.proxy.address = value
+                        }
+                        SessionsMessage::ProxyUsernameChanged(value) => {
+                            session.proxy.username = value
+                        }
+                        SessionsMessage::JumpKeyPathChanged(index, value) => {
+                            if let Some(hop) = session.jump_hosts.get_mut(index) {
+                                hop.key_path = value;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Command::none()
+    }
+
+    fn save_editor(&mut self) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        let mut session = editor.session.clone();
+        session.name = session.name.trim().to_string();
+        session.host = session.host.trim().to_string();
+        session.username = session.username.trim().to_string();
+        session.folder = normalize_folder(&session.folder);
+
+        if session.name.is_empty() {
+            editor.error = Some(String::from("Name is required"));
+            return;
+        }
+        if session.protocol == Protocol::Ssh && session.host.is_empty() {
+            editor.error = Some(String::from("Host is required"));
+            return;
+        }
+        session.port = match editor.port.trim() {
+            "" => None,
+            port => match port.parse::<u16>() {
+                Ok(port) if port != 0 => Some(port),
+                _ => {
+                    editor.error = Some(format!("Invalid port: {}", port));
+                    return;
+                }
+            },
+        };
+
+        let forwards = forward::parse_forward_list(&editor.local_forwards).and_then(|local| {
+            Ok((
+                local,
+                forward::parse_forward_list(&editor.remote_forwards)?,
+                forward::parse_dynamic_list(&editor.dynamic_forwards)?,
+            ))
+        });
+        (
+            session.local_forwards,
+            session.remote_forwards,
+            session.dynamic_forwards,
+        ) = match forwards {
+            Ok(forwards) => forwards,
+            Err(e) => {
+                editor.error = Some(e);
+                return;
+            }
+        };
+        session.jump_hosts = match parse_jump_hosts(&editor.jump_hosts, &session.jump_hosts) {
+            Ok(hops) => hops,
+            Err(e) => {
+                editor.error = Some(e);
+                return;
+            }
+        };
+        if let Err(e) = session.proxy.proxy("") {
+            editor.error = Some(e);
+            return;
+        }
+
+        match editor.index {
+            Some(index) if index < self.sessions.len() => self.sessions[index] = session,
+            _ => {
+                self.sessions.push(session);
+                self.selected = Some(self.sessions.len() - 1);
+            }
+        }
+        self.editor = None;
+        self.persist();
+    }
+
+    fn session_row(&self, index: usize, depth: usize) -> Element<'_, SessionsMessage> {
+        let session = &self.sessions[index];
+        let style = if self.selected == Some(index) {
+            theme::Button::Primary
+        } else {
+            theme::Button::Text
+        };
+        let entry = Button::new(
+            Column::new()
+                .push(Text::new(&session.name))
+                .push(Text::new(session.summary()).size(12)),
+        )
+        .width(Length::Fill)
+        .style(style)
+        .on_press(SessionsMessage::SessionClicked(index));
+
+        let entry = ContextMenu::new(entry, move || {
+            Column::new()
+                .width(Length::Fixed(140.0))
+                .push(menu_button("Connect", SessionsMessage::Connect(index)))
+                .push(menu_button("Edit...", SessionsMessage::Edit(index)))
+                .push(menu_button("Duplicate", SessionsMessage::Duplicate(index)))
+                .push(menu_button("Delete", SessionsMessage::Delete(index)))
+                .into()
+        });
+
+        Row::new().push(indent(depth)).push(entry).into()
+    }
+
+    fn folder_row(&self, folder: &str, depth: usize, empty: bool) -> Element<'_, SessionsMessage> {
+        let marker = if self.collapsed.contains(folder) && self.filter.is_empty() {
+            "+"
+        } else {
+            "-"
+        };
+        let entry = Button::new(Text::new(format!("{} {}", marker, folder_name(folder))))
+            .width(Length::Fill)
+            .style(theme::Button::Text)
+            .on_press(SessionsMessage::FolderToggled(folder.to_string()));
+
+        let folder = folder.to_string();
+        let entry = ContextMenu::new(entry, move || {
+            // Only empty folders can go; sessions are deleted one by one.
+            let delete = Button::new(Text::new("Delete folder"))
+                .width(Length::Fill)
+                .style(theme::Button::Secondary);
+            let delete = if empty {
+                delete.on_press(SessionsMessage::DeleteFolder(folder.clone()))
+            } else {
+                delete
+            };
+            Column::new()
+                .width(Length::Fixed(160.0))
+                .push(menu_button(
+                    "New session here...",
+                    SessionsMessage::NewSession(folder.clone()),
+                ))
+                .push(menu_button(
+                    "New folder here...",
+                    SessionsMessage::NewFolder(folder.clone()),
+                ))
+                .push(delete)
+                .into()
+        });
+
+        Row::new().push(indent(depth)).push(entry).into()
+    }
+
+    fn tree<'a>(
+        &'a self,
+        parent: &str,
+        depth: usize,
+        folders: &BTreeSet<String>,
+        visible: &[usize],
+        mut column: Column<'a, SessionsMessage>,
+    ) -> Column<'a, SessionsMessage> {
+        let filtering = !self.filter.is_empty();
+
+        for folder in folders
+            .iter()
+            .filter(|folder| parent_folder(folder) == parent)
+        {
+            let contents = visible
+                .iter()
+                .filter(|&&index| in_folder(&self.sessions[index].folder, folder))
+                .count();
+            if filtering && contents == 0 {
+                continue;
+            }
+            let empty = !self.sessions.iter().any(|s| in_folder(&s.folder, folder))
+                && !folders.iter().any(|other| parent_folder(other) == folder);
+            column = column.push(self.folder_row(folder, depth, empty));
+            if filtering || !self.collapsed.contains(folder) {
+                column = self.tree(folder, depth + 1, folders, visible, column);
+            }
+        }
+
+        let mut sessions: Vec<usize> = visible
+            .iter()
+            .copied()
+            .filter(|&index| self.sessions[index].folder == parent)
+            .collect();
+        sessions.sort_by_key(|&index| self.sessions[index].name.to_lowercase());
+        for index in sessions {
+            column = column.push(self.session_row(index, depth));
+        }
+        column
+    }
+
+    fn editor_view<'a>(&self, editor: &'a Editor) -> Element<'a, SessionsMessage> {
+        let session = &editor.session;
+
+        let mut form =
+            Column::new()
+                .spacing(10)
+                .push(field("Name", &session.name, SessionsMessage::NameChanged))
+                .push(field(
+                    "Folder, e.g. Production/Databases",
+                    &session.folder,
+                    SessionsMessage::FolderChanged,
+                ))
+                .push(Protocol::ALL.iter().cloned().fold(
+                    Row::new().spacing(10),
+                    |row, protocol| {
+                        row.push(Radio::new(
+                            protocol,
+                            protocol,
+                            Some(session.protocol),
+                            SessionsMessage::ProtocolSelected,
+                        ))
+                    },
+                ));
+
+        if session.protocol == Protocol::Ssh {
+            form = form
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(field("Host", &session.host, SessionsMessage::HostChanged))
+                        .push(
+                            field("22", &editor.port, SessionsMessage::PortChanged)
+                                .width(Length::Fixed(80.0)),
+                        ),
+                )
+                .push(field(
+                    "Username",
+                    &session.username,
+                    SessionsMessage::UsernameChanged,
+                ))
+                .push(AuthMethod::ALL.iter().cloned().fold(
+                    Row::new().spacing(10),
+                    |row, method| {
+                        row.push(Radio::new(
+                            method,
+                            method,
+                            Some(session.auth_method),
+                            SessionsMessage::AuthMethodSelected,
+                        ))
+                    },
+                ));
+            if session.auth_method == AuthMethod::PublicKey {
+                form = form.push(field(
+                    "Private key file",
+                    &session.key_path,
+                    SessionsMessage::KeyPathChanged,
+                ));
+            }
+            form = form
+                .push(Checkbox::new(
+                    "Forward ssh-agent",
+                    session.forward_agent,
+                    SessionsMessage::ForwardAgentToggled,
+                ))
+                .push(field(
+                    "Local forwards, e.g. 8080:localhost:80",
+                    &editor.local_forwards,
+                    SessionsMessage::LocalForwardsChanged,
+                ))
+                .push(field(
+                    "Remote forwards, e.g. 9000:localhost:3000",
+                    &editor.remote_forwards,
+                    SessionsMessage::RemoteForwardsChanged,
+                ))
+                .push(field(
+                    "SOCKS proxy ports, e.g. 1080",
+                    &editor.dynamic_forwards,
+                    SessionsMessage::DynamicForwardsChanged,
+                ))
+                .push(field(
+                    "Jump hosts, e.g. admin@bastion:2222,inner",
+                    &editor.jump_hosts,
+                    SessionsMessage::JumpHostsChanged,
+                ));
+            for (index, hop) in session.jump_hosts.iter().enumerate() {
+                form = form.push(jump_host_editor(index, hop));
+            }
+            form = form.push(ProxyKind::ALL.iter().cloned().fold(
+                Row::new().spacing(10),
+                |row, kind| {
+                    row.push(Radio::new(
+                        kind,
+                        kind,
+                        Some(session.proxy.kind),
+                        SessionsMessage::ProxyKindSelected,
+                    ))
+                },
+            ));
+            match session.proxy.kind {
+                ProxyKind::None => {}
+                ProxyKind::Command => {
+                    form = form.push(field(
+                        "ProxyCommand, e.g. nc -X connect -x proxy:3128 %h %p",
+                        &session.proxy.address,
+                        SessionsMessage::ProxyAddressChanged,
+                    ));
+                }
+                ProxyKind::Http | ProxyKind::Socks5 => {
+                    form = form.push(
+                        Row::new()
+                            .spacing(10)
+                            .push(field(
+                                "Proxy host:port",
+                                &session.proxy.address,
+                                SessionsMessage::ProxyAddressChanged,
+                            ))
+                            .push(field(
+                                "Proxy username (optional)",
+                                &session.proxy.username,
+                                SessionsMessage::ProxyUsernameChanged,
+                            )),
+                    );
+                }
+            }
+        }
+
+        if let Some(error) = &editor.error {
+            form = form.push(Text::new(error).style(iced::Color::from_rgb(0.8, 0.2, 0.2)));
+        }
+
+        let title = match editor.index {
+            Some(_) => "Edit session",
+            None => "New session",
+        };
+        Card::new(Text::new(title), form)
+            .foot(
+                Row::new()
+                    .spacing(10)
+                    .push(dialog_button("Cancel", SessionsMessage::EditorCancelled))
+                    .push(dialog_button("Save", SessionsMessage::EditorSaved)),
+            )
+            .max_width(480.0)
+            .on_close(SessionsMessage::EditorCancelled)
+            .into()
+    }
+
+    fn folder_prompt<'a>(&self, name: &'a str) -> Element<'a, SessionsMessage> {
+        Card::new(
+            Text::new("New folder"),
+            TextInput::new("Folder, e.g. Production/Databases", name)
+                .on_input(SessionsMessage::FolderNameChanged)
+                .on_submit(SessionsMessage::FolderSaved)
+                .padding(8),
+        )
+        .foot(
+            Row::new()
+                .spacing(10)
+                .push(dialog_button("Cancel", SessionsMessage::FolderCancelled))
+                .push(dialog_button("Create", SessionsMessage::FolderSaved)),
+        )
+        .max_width(360.0)
+        .on_close(SessionsMessage::FolderCancelled)
+        .into()
+    }
+
+    pub fn view(&self) -> Element<'_, SessionsMessage> {
+        let filter = self.filter.to_lowercase();
+        let visible: Vec<usize> = (0..self.sessions.len())
+            .filter(|&index| self.sessions[index].matches(&filter))
+            .collect();
+        let folders = self.all_folders();
+
+        let toolbar = Row::new()
+            .spacing(6)
+            .align_items(Alignment::Center)
+            .push(
+                TextInput::new("Filter sessions", &self.filter)
+                    .on_input(SessionsMessage::FilterChanged)
+                    .padding(6),
+            )
+            .push(
+                Button::new(Text::new("+ Session"))
+                    .on_press(SessionsMessage::NewSession(String::new())),
+            )
+            .push(
+                Button::new(Text::new("+ Folder"))
+                    .on_press(SessionsMessage::NewFolder(String::new())),
+            );
+
+        let tree = self.tree("", 0, &folders, &visible, Column::new().spacing(2));
+        let mut content = Column::new()
+            .spacing(10)
+            .padding(8)
+            .push(toolbar)
+            .push(Scrollable::new(tree).height(Length::Fill));
+        if self.sessions.is_empty() && folders.is_empty() {
+            content = content.push(Text::new("No saved sessions yet").size(14));
+        }
+        if let Some(error) = &self.error {
+            content = content.push(
+                Text::new(error)
+                    .size(14)
+                    .style(iced::Color::from_rgb(0.8, 0.2, 0.2)),
+            );
+        }
+
+        let content = Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        let show = self.editor.is_some() || self.new_folder.is_some();
+        Modal::new(show, content, move || {
+            match (&self.editor, &self.new_folder) {
+                (Some(editor), _) => self.editor_view(editor),
+                (None, Some(name)) => self.folder_prompt(name),
+                (None, None) => Text::new("").into(),
+            }
+        })
+        .on_esc(if self.editor.is_some() {
+            SessionsMessage::EditorCancelled
+        } else {
+            SessionsMessage::FolderCancelled
+        })
+        .into()
+    }
+}
+
+fn indent<'a>(depth: usize) -> Element<'a, SessionsMessage> {
+    iced::widget::horizontal_space(Length::Fixed(depth as f32 * INDENT)).into()
+}
+
+/// How to authenticate to one jump host.
+fn jump_host_editor(index: usize, hop: &JumpHost) -> Element<'_, SessionsMessage> {
+    let methods = AuthMethod::ALL.iter().cloned().fold(
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new(format!("via {}", hop.spec())).width(Length::Fill)),
+        |row, method| {
+            row.push(Radio::new(
+                method,
+                method,
+                Some(hop.auth_method),
+                move |method| SessionsMessage::JumpAuthMethodSelected(index, method),
+            ))
+        },
+    );
+    let mut column = Column::new().spacing(6).push(methods);
+    if hop.auth_method == AuthMethod::PublicKey {
+        column = column.push(
+            TextInput::new("Private key file", &hop.key_path)
+                .on_input(move |value| SessionsMessage::JumpKeyPathChanged(index, value))
+                .on_submit(SessionsMessage::EditorSaved)
+                .padding(8),
+        );
+    }
+    column.into()
+}
+
+fn field<'a>(
+    placeholder: &str,
+    value: &str,
+    on_input: fn(String) -> SessionsMessage,
+) -> TextInput<'a, SessionsMessage> {
+    TextInput::new(placeholder, value)
+        .on_input(on_input)
+        .on_submit(SessionsMessage::EditorSaved)
+        .padding(8)
+}
+
+fn menu_button(label: &str, message: SessionsMessage) -> Button<'_, SessionsMessage> {
+    Button::new(Text::new(label))
+        .width(Length::Fill)
+        .style(theme::Button::Secondary)
+        .on_press(message)
+}
+
+fn dialog_button(label: &str, message: SessionsMessage) -> Button<'_, SessionsMessage> {
+    Button::new(Text::new(label).horizontal_alignment(Horizontal::Center))
+        .width(Length::Fill)
+        .on_press(message)
+}
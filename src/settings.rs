@@ -1,11 +1,23 @@
+use crate::config;
+use crate::keybindings::{Action, Chord, Keybindings};
+use crate::palette::{self, Palette, Rgb};
+use crate::terminal_view::{MAX_FONT_SIZE, MIN_FONT_SIZE};
 use crate::{Icon, Message, Tab};
+use iced::keyboard::{KeyCode, Modifiers};
 use iced::{
-    widget::{Column, Container, Radio, Text},
-    Element,
+    theme,
+    widget::{
+        container, Button, Checkbox, Column, Container, PickList, Radio, Row, Scrollable, Text,
+        TextInput,
+    },
+    Alignment, Color, Command, Element, Length, Theme,
 };
 use iced_aw::style::TabBarStyles;
 use iced_aw::tab_bar::TabLabel;
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TabBarPosition {
     #[default]
     Top,
@@ -44,16 +56,59 @@ impl TabSettings {
 pub enum SettingsMessage {
     PositionSelected(TabBarPosition),
     ThemeSelected(TabBarStyles),
+    KeepaliveIntervalChanged(String),
+    KeepaliveMaxChanged(String),
+    AutoReconnectToggled(bool),
+    ReconnectMaxDelayChanged(String),
+    ScrollbackLinesChanged(String),
+    FontSizeChanged(String),
+    ColorSchemeSelected(String),
+    ImportColorScheme,
+    ColorSchemesImported(Result<Vec<Palette>, String>),
+    RemoveColorScheme,
+    /// Takes the next key press as the shortcut for the action.
+    RecordShortcut(Action),
+    ShortcutPressed(KeyCode, Modifiers),
+    ResetShortcut(Action),
 }
 
 pub struct SettingsTab {
     settings: TabSettings,
+    connection: config::Connection,
+    terminal: config::Terminal,
+    keybindings: Keybindings,
+    // The numbers as typed; the settings above only take valid ones.
+    keepalive_interval: String,
+    keepalive_max: String,
+    reconnect_max_delay: String,
+    scrollback_lines: String,
+    font_size: String,
+    import_error: Option<String>,
+    recording: Option<Action>,
+    shortcut_error: Option<String>,
 }
 
 impl SettingsTab {
     pub fn new() -> Self {
+        Self::with_settings(TabSettings::new(), &config::Config::default())
+    }
+
+    /// Starts from the tab bar settings and the other sections of `config`.
+    pub fn with_settings(settings: TabSettings, config: &config::Config) -> Self {
+        let connection = &config.connection;
         SettingsTab {
-            settings: TabSettings::new(),
+            settings,
+            keepalive_interval: connection.keepalive_interval.to_string(),
+            keepalive_max: connection.keepalive_max.to_string(),
+            reconnect_max_delay: connection.reconnect_max_delay.to_string(),
+            scrollback_lines: config.terminal.scrollback_lines.to_string(),
+            font_size: config.terminal.font_size.to_string(),
+            import_error: None,
+            recording: None,
+            shortcut_error: None,
+            connection: connection.clone(),
+            terminal: config.terminal.clone(),
+            keybindings: config.keybindings.clone(),
         }
     }
 
@@ -61,16 +116,335 @@ impl SettingsTab {
         &self.settings
     }
 
-    pub fn update(&mut self, message: SettingsMessage) {
+    pub fn connection(&self) -> &config::Connection {
+        &self.connection
+    }
+
+    pub fn terminal(&self) -> &config::Terminal {
+        &self.terminal
+    }
+
+    pub fn keybindings(&self) -> &Keybindings {
+        &self.keybindings
+    }
+
+    /// Whether key presses should come here rather than run shortcuts.
+    pub fn recording_shortcut(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// For zooming, which changes the font size from outside this tab.
+    pub fn set_font_size(&mut self, size: f32) {
+        self.terminal.font_size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.font_size = self.terminal.font_size.to_string();
+    }
+
+    pub fn update(&mut self, message: SettingsMessage) -> Command<SettingsMessage> {
         match message {
             SettingsMessage::PositionSelected(position) => {
                 self.settings.tab_bar_position = Some(position)
             }
             SettingsMessage::ThemeSelected(theme) => self.settings.tab_bar_theme = Some(theme),
+            SettingsMessage::KeepaliveIntervalChanged(value) => {
+                if let Ok(seconds) = value.trim().parse() {
+                    self.connection.keepalive_interval = seconds;
+                }
+                self.keepalive_interval = value;
+            }
+            SettingsMessage::KeepaliveMaxChanged(value) => {
+                if let Ok(count) = value.trim().parse() {
+                    self.connection.keepalive_max = count;
+                }
+                self.keepalive_max = value;
+            }
+            SettingsMessage::AutoReconnectToggled(enabled) => {
+                self.connection.auto_reconnect = enabled
+            }
+            SettingsMessage::ReconnectMaxDelayChanged(value) => {
+                match value.trim().parse() {
+                    Ok(seconds) if seconds > 0 => self.connection.reconnect_max_delay = seconds,
+                    _ => {}
+                }
+                self.reconnect_max_delay = value;
+            }
+            SettingsMessage::ScrollbackLinesChanged(value) => {
+                if let Ok(lines) = value.trim().parse() {
+                    self.terminal.scrollback_lines = lines;
+                }
+                self.scrollback_lines = value;
+            }
+            SettingsMessage::FontSizeChanged(value) => {
+                match value.trim().parse::<f32>() {
+                    Ok(size) if (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&size) => {
+                        self.terminal.font_size = size
+                    }
+                    _ => {}
+                }
+                self.font_size = value;
+            }
+            SettingsMessage::ColorSchemeSelected(name) => self.terminal.color_scheme = name,
+            SettingsMessage::ImportColorScheme => {
+                let dialog = rfd::AsyncFileDialog::new()
+                    .set_title("Import colour scheme")
+                    .add_filter(
+                        "Colour schemes",
+                        &["itermcolors", "json", "toml", "yaml", "yml"],
+                    );
+                return Command::perform(
+                    async move {
+                        match dialog.pick_file().await {
+                            Some(file) => palette::import(file.path()),
+                            None => Ok(Vec::new()),
+                        }
+                    },
+                    SettingsMessage::ColorSchemesImported,
+                );
+            }
+            SettingsMessage::ColorSchemesImported(Ok(palettes)) => {
+                self.import_error = None;
+                for mut scheme in palettes {
+                    let builtin = palette::builtins()
+                        .iter()
+                        .any(|builtin| builtin.name == scheme.name);
+                    if builtin {
+                        scheme.name = format!("{} (imported)", scheme.name);
+                    }
+                    self.terminal.color_scheme = scheme.name.clone();
+                    let imported = &mut self.terminal.imported_schemes;
+                    match imported.iter_mut().find(|other| other.name == scheme.name) {
+                        Some(other) => *other = scheme,
+                        None => imported.push(scheme),
+                    }
+                }
+            }
+            SettingsMessage::ColorSchemesImported(Err(e)) => self.import_error = Some(e),
+            SettingsMessage::RemoveColorScheme => {
+                let name = &self.terminal.color_scheme;
+                self.terminal
+                    .imported_schemes
+                    .retain(|palette| &palette.name != name);
+                self.terminal.color_scheme = String::from(palette::DEFAULT_NAME);
+            }
+            SettingsMessage::RecordShortcut(action) => {
+                self.recording = Some(action);
+                self.shortcut_error = None;
+            }
+            SettingsMessage::ShortcutPressed(key_code, modifiers) => {
+                let Some(action) = self.recording else {
+                    return Command::none();
+                };
+                let chord = match key_code {
+                    KeyCode::Escape if modifiers.is_empty() => {
+                        self.recording = None;
+                        return Command::none();
+                    }
+                    KeyCode::Backspace | KeyCode::Delete if modifiers.is_empty() => None,
+                    _ => match Chord::new(key_code, modifiers) {
+                        Some(chord) => Some(chord),
+                        // A modifier on its own, or a key with no name.
+                        None => return Command::none(),
+                    },
+                };
+                self.recording = None;
+                self.shortcut_error = self.keybindings.set(action, chord).err();
+            }
+            SettingsMessage::ResetShortcut(action) => {
+                self.recording = None;
+                self.shortcut_error = self.keybindings.reset(action).err();
+            }
         }
+        Command::none()
+    }
+
+    fn connection_view(&self) -> Element<'_, SettingsMessage> {
+        Column::new()
+            .padding(10)
+            .spacing(10)
+            .push(field(
+                "Keepalive interval in seconds (0 for none)",
+                &self.keepalive_interval,
+                SettingsMessage::KeepaliveIntervalChanged,
+            ))
+            .push(field(
+                "Unanswered keepalives before disconnecting",
+                &self.keepalive_max,
+                SettingsMessage::KeepaliveMaxChanged,
+            ))
+            .push(Checkbox::new(
+                "Reconnect dropped sessions automatically",
+                self.connection.auto_reconnect,
+                SettingsMessage::AutoReconnectToggled,
+            ))
+            .push(field(
+                "Longest wait between attempts in seconds",
+                &self.reconnect_max_delay,
+                SettingsMessage::ReconnectMaxDelayChanged,
+            ))
+            .into()
+    }
+
+    fn terminal_view(&self) -> Element<'_, SettingsMessage> {
+        Column::new()
+            .padding(10)
+            .spacing(10)
+            .push(field(
+                "Scrollback lines",
+                &self.scrollback_lines,
+                SettingsMessage::ScrollbackLinesChanged,
+            ))
+            .push(field(
+                "Font size",
+                &self.font_size,
+                SettingsMessage::FontSizeChanged,
+            ))
+            .push(self.color_scheme_view())
+            .into()
+    }
+
+    fn color_scheme_view(&self) -> Element<'_, SettingsMessage> {
+        let names: Vec<String> = palette::builtins()
+            .into_iter()
+            .chain(self.terminal.imported_schemes.iter().cloned())
+            .map(|palette| palette.name)
+            .collect();
+        let imported = self
+            .terminal
+            .imported_schemes
+            .iter()
+            .any(|palette| palette.name == self.terminal.color_scheme);
+        let mut remove = Button::new(Text::new("Remove"));
+        if imported {
+            remove = remove.on_press(SettingsMessage::RemoveColorScheme);
+        }
+        let mut column = Column::new()
+            .spacing(10)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Colour scheme").width(Length::Fixed(320.0)))
+                    .push(PickList::new(
+                        names,
+                        Some(self.terminal.color_scheme.clone()),
+                        SettingsMessage::ColorSchemeSelected,
+                    ))
+                    .push(
+                        Button::new(Text::new("Import..."))
+                            .on_press(SettingsMessage::ImportColorScheme),
+                    )
+                    .push(remove),
+            )
+            .push(preview(&self.terminal.palette()));
+        if let Some(error) = &self.import_error {
+            column = column.push(Text::new(error.as_str()).style(Color::from_rgb(0.8, 0.2, 0.2)));
+        }
+        column.into()
+    }
+
+    fn keybindings_view(&self) -> Element<'_, SettingsMessage> {
+        let mut column = Column::new().padding(10).spacing(10);
+        for action in Action::ALL {
+            let chord = match self.keybindings.chord(action) {
+                Some(chord) => chord.to_string(),
+                None => String::from("None"),
+            };
+            let mut chord = Text::new(chord).width(Length::Fixed(160.0));
+            if self.keybindings.conflict(action).is_some() {
+                chord = chord.style(Color::from_rgb(0.8, 0.2, 0.2));
+            }
+            let change = if self.recording == Some(action) {
+                Button::new(Text::new("Press keys..."))
+            } else {
+                Button::new(Text::new("Change")).on_press(SettingsMessage::RecordShortcut(action))
+            };
+            column = column.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(action.to_string()).width(Length::Fixed(160.0)))
+                    .push(chord)
+                    .push(change)
+                    .push(
+                        Button::new(Text::new("Reset"))
+                            .on_press(SettingsMessage::ResetShortcut(action)),
+                    ),
+            );
+        }
+        if self.recording.is_some() {
+            column = column.push(Text::new(
+                "Press the new shortcut, Backspace to remove it or Escape to cancel.",
+            ));
+        }
+        if let Some(error) = &self.shortcut_error {
+            column = column.push(Text::new(error.as_str()).style(Color::from_rgb(0.8, 0.2, 0.2)));
+        }
+        column.into()
     }
 }
 
+fn to_color(Rgb(r, g, b): Rgb) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+/// A filled rectangle for the colour scheme preview.
+struct Swatch(Color);
+
+impl container::StyleSheet for Swatch {
+    type Style = Theme;
+
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            background: Some(self.0.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Sample text on the scheme's background, then its sixteen colours.
+fn preview<'a>(palette: &Palette) -> Element<'a, SettingsMessage> {
+    let swatches = palette
+        .ansi
+        .iter()
+        .fold(Row::new().spacing(2), |row, &rgb| {
+            row.push(
+                Container::new(Text::new(""))
+                    .width(Length::Fixed(18.0))
+                    .height(Length::Fixed(18.0))
+                    .style(theme::Container::Custom(Box::new(Swatch(to_color(rgb))))),
+            )
+        });
+    Container::new(
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("user@host:~$").style(to_color(palette.foreground)))
+            .push(swatches),
+    )
+    .padding(8)
+    .style(theme::Container::Custom(Box::new(Swatch(to_color(
+        palette.background,
+    )))))
+    .into()
+}
+
+/// A labelled number input.
+fn field<'a>(
+    label: &str,
+    value: &str,
+    on_input: fn(String) -> SettingsMessage,
+) -> Row<'a, SettingsMessage> {
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(Text::new(label.to_string()).width(Length::Fixed(320.0)))
+        .push(
+            TextInput::new("", value)
+                .on_input(on_input)
+                .padding(4)
+                .width(Length::Fixed(80.0)),
+        )
+}
+
 impl Tab for SettingsTab {
     type Message = Message;
 
@@ -84,7 +458,7 @@ impl Tab for SettingsTab {
     }
 
     fn content(&self) -> Element<'_, Self::Message> {
-        let content: Element<'_, SettingsMessage> = Container::new(
+        let content: Element<'_, SettingsMessage> = Scrollable::new(
             Column::new()
                 .push(Text::new("TabBar position:").size(20))
                 .push(TabBarPosition::ALL.iter().cloned().fold(
@@ -114,14 +488,39 @@ impl Tab for SettingsTab {
                             .size(16),
                         )
                     }),
-                ),
+                )
+                .push(Text::new("Connections:").size(20))
+                .push(self.connection_view())
+                .push(Text::new("Terminal:").size(20))
+                .push(self.terminal_view())
+                .push(Text::new("Keyboard shortcuts:").size(20))
+                .push(self.keybindings_view()),
         )
+        .height(Length::Fill)
         .into();
 
         content.map(Message::Settings)
     }
 }
 
+/// The names tab bar styles are saved under, in `predefined_style` order.
+const STYLE_NAMES: [&str; 6] = ["default", "dark", "red", "blue", "green", "purple"];
+
+pub fn style_name(style: TabBarStyles) -> &'static str {
+    (0..STYLE_NAMES.len())
+        .find(|&index| predefined_style(index) == style)
+        .map_or(STYLE_NAMES[0], |index| STYLE_NAMES[index])
+}
+
+pub fn style_from_name(name: &str) -> TabBarStyles {
+    predefined_style(
+        STYLE_NAMES
+            .iter()
+            .position(|&style| style == name)
+            .unwrap_or_default(),
+    )
+}
+
 fn predefined_style(index: usize) -> TabBarStyles {
     match index {
         0 => TabBarStyles::Default,
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use russh_sftp::client::fs::File;
+use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::{FileAttributes, FileType, OpenFlags};
+use tokio::io::AsyncSeekExt;
+
+use crate::ssh::SshSession;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EntryKind {
+    Dir,
+    Symlink,
+    File,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Permission bits including the file type, as in `st_mode`.
+    pub mode: u32,
+    pub owner: String,
+    pub group: String,
+    /// Seconds since the epoch.
+    pub modified: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub path: String,
+    pub entries: Vec<RemoteEntry>,
+}
+
+/// An SFTP subsystem running on a channel of an existing SSH connection.
+#[derive(Clone)]
+pub struct Sftp {
+    session: Arc<SftpSession>,
+}
+
+impl fmt::Debug for Sftp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sftp").finish_non_exhaustive()
+    }
+}
+
+pub fn join(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+pub fn parent(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => String::from("/"),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+fn entry(dir: &str, name: String, attrs: &FileAttributes) -> RemoteEntry {
+    let kind = match attrs.file_type() {
+        FileType::Dir => EntryKind::Dir,
+        FileType::Symlink => EntryKind::Symlink,
+        FileType::File => EntryKind::File,
+        FileType::Other => EntryKind::Other,
+    };
+    let id_or_name = |name: &Option<String>, id: Option<u32>| match (name, id) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => id.to_string(),
+        (None, None) => String::new(),
+    };
+    RemoteEntry {
+        path: join(dir, &name),
+        name,
+        kind,
+        size: attrs.size.unwrap_or(0),
+        mode: attrs.permissions.unwrap_or(0),
+        owner: id_or_name(&attrs.user, attrs.uid),
+        group: id_or_name(&attrs.group, attrs.gid),
+        modified: attrs.mtime,
+    }
+}
+
+pub async fn open(session: SshSession) -> Result<Sftp, String> {
+    let channel = session.open_subsystem("sftp").await?;
+    let sftp = SftpSession::new(channel.into_stream())
+        .await
+        .map_err(|e| format!("Could not start SFTP: {}", e))?;
+    Ok(Sftp {
+        session: Arc::new(sftp),
+    })
+}
+
+impl Sftp {
+    /// Lists `path`, directories first. An empty path means the login
+    /// directory.
+    pub async fn list(self, path: String) -> Result<Listing, String> {
+        let path = self
+            .session
+            .canonicalize(if path.is_empty() { "." } else { path.as_str() })
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let dir = self
+            .session
+            .read_dir(path.as_str())
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut entries: Vec<RemoteEntry> = dir
+            .filter(|item| item.file_name() != "." && item.file_name() != "..")
+            .map(|item| entry(&path, item.file_name(), &item.metadata()))
+            .collect();
+        entries.sort_by(|a, b| {
+            (a.kind != EntryKind::Dir, a.name.to_lowercase())
+                .cmp(&(b.kind != EntryKind::Dir, b.name.to_lowercase()))
+        });
+        Ok(Listing { path, entries })
+    }
+
+    pub async fn rename(self, from: String, to: String) -> Result<(), String> {
+        self.session
+            .rename(from.as_str(), to.as_str())
+            .await
+            .map_err(|e| format!("Could not rename {}: {}", from, e))
+    }
+
+    pub async fn remove(self, entry: RemoteEntry) -> Result<(), String> {
+        let result = match entry.kind {
+            EntryKind::Dir => self.session.remove_dir(entry.path.as_str()).await,
+            _ => self.session.remove_file(entry.path.as_str()).await,
+        };
+        result.map_err(|e| format!("Could not delete {}: {}", entry.path, e))
+    }
+
+    pub async fn mkdir(self, path: String) -> Result<(), String> {
+        self.session
+            .create_dir(path.as_str())
+            .await
+            .map_err(|e| format!("Could not create {}: {}", path, e))
+    }
+
+    pub async fn chmod(self, path: String, mode: u32) -> Result<(), String> {
+        let attrs = FileAttributes {
+            permissions: Some(mode & 0o7777),
+            ..FileAttributes::empty()
+        };
+        self.session
+            .set_metadata(path.as_str(), attrs)
+            .await
+            .map_err(|e| format!("Could not change the mode of {}: {}", path, e))
+    }
+
+    /// Moves `from` onto `to`. A plain SFTP rename won't overwrite, so an
+    /// existing `to` is removed first.
+    pub async fn replace(&self, from: &str, to: &str) -> Result<(), String> {
+        if self.session.metadata(to).await.is_ok() {
+            self.session
+                .remove_file(to)
+                .await
+                .map_err(|e| format!("Could not replace {}: {}", to, e))?;
+        }
+        self.session
+            .rename(from, to)
+            .await
+            .map_err(|e| format!("Could not rename {} to {}: {}", from, to, e))
+    }
+
+    pub async fn size(&self, path: &str) -> Result<u64, String> {
+        let attrs = self
+            .session
+            .metadata(path)
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(attrs.size.unwrap_or(0))
+    }
+
+    /// Opens a remote file for reading, positioned at `offset`.
+    pub async fn open_read(&self, path: &str, offset: u64) -> Result<File, String> {
+        let mut file = self
+            .session
+            .open(path)
+            .await
+            .map_err(|e| format!("Could not open {}: {}", path, e))?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| format!("Could not seek in {}: {}", path, e))?;
+        Ok(file)
+    }
+
+    /// Opens a remote file for writing at `offset`, creating it if needed.
+    /// Starting at zero truncates it.
+    pub async fn open_write(&self, path: &str, offset: u64) -> Result<File, String> {
+        let mut flags = OpenFlags::CREATE | OpenFlags::WRITE;
+        if offset == 0 {
+            flags |= OpenFlags::TRUNCATE;
+        }
+        let mut file = self
+            .session
+            .open_with_flags(path, flags)
+            .await
+            .map_err(|e| format!("Could not create {}: {}", path, e))?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| format!("Could not seek in {}: {}", path, e))?;
+        Ok(file)
+    }
+}
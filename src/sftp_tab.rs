@@ -0,0 +1,528 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use iced::{
+    alignment::Horizontal,
+    theme,
+    widget::{Button, Column, Container, Row, Scrollable, Text, TextInput},
+    Alignment, Command, Element, Length,
+};
+use iced_aw::tab_bar::TabLabel;
+use iced_aw::{Card, Modal};
+
+use crate::sftp::{self, EntryKind, Listing, RemoteEntry, Sftp};
+use crate::{Icon, Message, Tab};
+
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+enum Prompt {
+    Rename(RemoteEntry, String),
+    Mkdir(String),
+    Chmod(RemoteEntry, String),
+    Delete(RemoteEntry),
+}
+
+#[derive(Debug, Clone)]
+pub enum SftpMessage {
+    PathChanged(String),
+    GoPressed,
+    UpPressed,
+    RefreshPressed,
+    Listed(Result<Listing, String>),
+    EntryClicked(usize),
+    DownloadPressed,
+    DownloadTo(String, Option<PathBuf>),
+    UploadPressed,
+    UploadFrom(String, Option<Vec<PathBuf>>),
+    RenamePressed,
+    DeletePressed,
+    MkdirPressed,
+    ChmodPressed,
+    PromptChanged(String),
+    PromptConfirmed,
+    PromptCancelled,
+    Changed(Result<(), String>),
+}
+
+pub struct SftpTab {
+    id: usize,
+    title: String,
+    sftp: Sftp,
+    path: String,
+    listing: Option<Listing>,
+    selected: Option<usize>,
+    last_click: Option<(usize, Instant)>,
+    prompt: Option<Prompt>,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl SftpTab {
+    /// Opens on the login directory; the caller runs the returned command
+    /// to fetch the first listing.
+    pub fn new(id: usize, title: String, sftp: Sftp) -> (Self, Command<SftpMessage>) {
+        let mut tab = SftpTab {
+            id,
+            title,
+            sftp,
+            path: String::new(),
+            listing: None,
+            selected: None,
+            last_click: None,
+            prompt: None,
+            loading: false,
+            error: None,
+        };
+        let command = tab.list(String::new());
+        (tab, command)
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    fn list(&mut self, path: String) -> Command<SftpMessage> {
+        self.loading = true;
+        Command::perform(self.sftp.clone().list(path), SftpMessage::Listed)
+    }
+
+    fn refresh(&mut self) -> Command<SftpMessage> {
+        let path = self
+            .listing
+            .as_ref()
+            .map(|listing| listing.path.clone())
+            .unwrap_or_default();
+        self.list(path)
+    }
+
+    fn selected_entry(&self) -> Option<&RemoteEntry> {
+        self.listing.as_ref()?.entries.get(self.selected?)
+    }
+
+    fn current_dir(&self) -> Option<String> {
+        self.listing.as_ref().map(|listing| listing.path.clone())
+    }
+
+    pub fn sftp(&self) -> &Sftp {
+        &self.sftp
+    }
+
+    /// Called when something outside the tab, such as a finished upload,
+    /// changed `dir`.
+    pub fn remote_changed(&mut self, dir: &str) -> Command<SftpMessage> {
+        match self.current_dir() {
+            Some(current) if current == dir => self.refresh(),
+            _ => Command::none(),
+        }
+    }
+
+    pub fn update(&mut self, message: SftpMessage) -> Command<SftpMessage> {
+        match message {
+            SftpMessage::PathChanged(path) => self.path = path,
+            SftpMessage::GoPressed => return self.list(self.path.trim().to_string()),
+            SftpMessage::UpPressed => {
+                if let Some(dir) = self.current_dir() {
+                    return self.list(sftp::parent(&dir));
+                }
+            }
+            SftpMessage::RefreshPressed => return self.refresh(),
+            SftpMessage::Listed(result) => {
+                self.loading = false;
+                match result {
+                    Ok(listing) => {
+                        self.path = listing.path.clone();
+                        self.listing = Some(listing);
+                        self.selected = None;
+                        self.last_click = None;
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            SftpMessage::EntryClicked(index) => {
+                let now = Instant::now();
+                let double_click = matches!(
+                    self.last_click,
+                    Some((last, at)) if last == index && now.duration_since(at) < DOUBLE_CLICK
+                );
+                self.selected = Some(index);
+                self.last_click = Some((index, now));
+                if double_click {
+                    self.last_click = None;
+                    match self.selected_entry() {
+                        Some(entry) if entry.kind == EntryKind::File => {
+                            return self.update(SftpMessage::DownloadPressed)
+                        }
+                        Some(entry) => {
+                            let path = entry.path.clone();
+                            return self.list(path);
+                        }
+                        None => {}
+                    }
+                }
+            }
+            SftpMessage::DownloadPressed => {
+                let Some(entry) = self.selected_entry() else {
+                    return Command::none();
+                };
+                if entry.kind == EntryKind::Dir {
+                    self.error = Some(String::from("Only files can be downloaded"));
+                    return Command::none();
+                }
+                let remote = entry.path.clone();
+                let mut dialog = rfd::AsyncFileDialog::new()
+                    .set_title("Save as")
+                    .set_file_name(&entry.name);
+                if let Some(dir) = dirs::download_dir() {
+                    dialog = dialog.set_directory(dir);
+                }
+                return Command::perform(
+                    async move {
+                        dialog
+                            .save_file()
+                            .await
+                            .map(|file| file.path().to_path_buf())
+                    },
+                    move |local| SftpMessage::DownloadTo(remote, local),
+                );
+            }
+            SftpMessage::UploadPressed => {
+                let Some(dir) = self.current_dir() else {
+                    return Command::none();
+                };
+                let dialog = rfd::AsyncFileDialog::new().set_title("Upload files");
+                return Command::perform(
+                    async move {
+                        dialog.pick_files().await.map(|files| {
+                            files.iter().map(|file| file.path().to_path_buf()).collect()
+                        })
+                    },
+                    move |files| SftpMessage::UploadFrom(dir, files),
+                );
+            }
+            // Queued by the application, which owns the transfers.
+            SftpMessage::DownloadTo(..) | SftpMessage::UploadFrom(..) => {}
+            SftpMessage::RenamePressed => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    let name = entry.name.clone();
+                    self.prompt = Some(Prompt::Rename(entry, name));
+                }
+            }
+            SftpMessage::DeletePressed => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    self.prompt = Some(Prompt::Delete(entry));
+                }
+            }
+            SftpMessage::MkdirPressed => {
+                if self.listing.is_some() {
+                    self.prompt = Some(Prompt::Mkdir(String::new()));
+                }
+            }
+            SftpMessage::ChmodPressed => {
+                if let Some(entry) = self.selected_entry().cloned() {
+                    let mode = format!("{:o}", entry.mode & 0o7777);
+                    self.prompt = Some(Prompt::Chmod(entry, mode));
+                }
+            }
+            SftpMessage::PromptChanged(value) => match &mut self.prompt {
+                Some(Prompt::Rename(_, text))
+                | Some(Prompt::Mkdir(text))
+                | Some(Prompt::Chmod(_, text)) => *text = value,
+                _ => {}
+            },
+            SftpMessage::PromptConfirmed => return self.confirm_prompt(),
+            SftpMessage::PromptCancelled => self.prompt = None,
+            SftpMessage::Changed(Ok(())) => return self.refresh(),
+            SftpMessage::Changed(Err(e)) => {
+                self.error = Some(e);
+                return self.refresh();
+            }
+        }
+        Command::none()
+    }
+
+    fn confirm_prompt(&mut self) -> Command<SftpMessage> {
+        let Some(prompt) = self.prompt.take() else {
+            return Command::none();
+        };
+        let sftp = self.sftp.clone();
+        match prompt {
+            Prompt::Rename(entry, name) => {
+                let name = name.trim();
+                if name.is_empty() || name.contains('/') || name == entry.name {
+                    return Command::none();
+                }
+                let to = sftp::join(&sftp::parent(&entry.path), name);
+                Command::perform(sftp.rename(entry.path, to), SftpMessage::Changed)
+            }
+            Prompt::Mkdir(name) => {
+                let name = name.trim();
+                let Some(dir) = self.current_dir() else {
+                    return Command::none();
+                };
+                if name.is_empty() || name.contains('/') {
+                    return Command::none();
+                }
+                Command::perform(sftp.mkdir(sftp::join(&dir, name)), SftpMessage::Changed)
+            }
+            Prompt::Chmod(entry, mode) => match u32::from_str_radix(mode.trim(), 8) {
+                Ok(mode) if mode <= 0o7777 => {
+                    Command::perform(sftp.chmod(entry.path, mode), SftpMessage::Changed)
+                }
+                _ => {
+                    self.error = Some(format!("Invalid mode: {}", mode.trim()));
+                    self.prompt = Some(Prompt::Chmod(entry, mode));
+                    Command::none()
+                }
+            },
+            Prompt::Delete(entry) => Command::perform(sftp.remove(entry), SftpMessage::Changed),
+        }
+    }
+
+    fn entry_row(&self, index: usize, entry: &RemoteEntry) -> Element<'_, SftpMessage> {
+        let style = if self.selected == Some(index) {
+            theme::Button::Primary
+        } else {
+            theme::Button::Text
+        };
+        let name = match entry.kind {
+            EntryKind::Dir => format!("{}/", entry.name),
+            EntryKind::Symlink => format!("{}@", entry.name),
+            _ => entry.name.clone(),
+        };
+        let size = match entry.kind {
+            EntryKind::Dir => String::new(),
+            _ => format_size(entry.size),
+        };
+        Button::new(
+            Row::new()
+                .spacing(10)
+                .push(Text::new(name).width(Length::Fill))
+                .push(
+                    Text::new(size)
+                        .width(Length::Fixed(80.0))
+                        .horizontal_alignment(Horizontal::Right),
+                )
+                .push(Text::new(mode_string(entry)).width(Length::Fixed(100.0)))
+                .push(
+                    Text::new(format!("{} {}", entry.owner, entry.group))
+                        .width(Length::Fixed(140.0)),
+                )
+                .push(
+                    Text::new(entry.modified.map(format_time).unwrap_or_default())
+                        .width(Length::Fixed(140.0)),
+                ),
+        )
+        .width(Length::Fill)
+        .padding([2, 6])
+        .style(style)
+        .on_press(SftpMessage::EntryClicked(index))
+        .into()
+    }
+
+    fn prompt_view<'a>(&self, prompt: &'a Prompt) -> Element<'a, SftpMessage> {
+        let (title, body): (String, Element<'a, SftpMessage>) = match prompt {
+            Prompt::Rename(entry, name) => (
+                format!("Rename {}", entry.name),
+                prompt_input("New name", name),
+            ),
+            Prompt::Mkdir(name) => (String::from("New folder"), prompt_input("Name", name)),
+            Prompt::Chmod(entry, mode) => (
+                format!("Change mode of {}", entry.name),
+                prompt_input("Octal mode, e.g. 644", mode),
+            ),
+            Prompt::Delete(entry) => (
+                String::from("Delete"),
+                Text::new(match entry.kind {
+                    EntryKind::Dir => format!("Delete the empty directory {}?", entry.path),
+                    _ => format!("Delete {}?", entry.path),
+                })
+                .into(),
+            ),
+        };
+        let confirm = match prompt {
+            Prompt::Delete(_) => "Delete",
+            _ => "OK",
+        };
+        Card::new(Text::new(title), body)
+            .foot(
+                Row::new()
+                    .spacing(10)
+                    .push(dialog_button("Cancel", SftpMessage::PromptCancelled))
+                    .push(dialog_button(confirm, SftpMessage::PromptConfirmed)),
+            )
+            .max_width(400.0)
+            .on_close(SftpMessage::PromptCancelled)
+            .into()
+    }
+}
+
+impl Tab for SftpTab {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        format!("SFTP: {}", self.title)
+    }
+
+    fn tab_label(&self) -> TabLabel {
+        TabLabel::IconText(Icon::User.into(), self.title())
+    }
+
+    fn content(&self) -> Element<'_, Self::Message> {
+        let selected = self.selected_entry().is_some();
+        let on_selected = |label: &'static str, message: SftpMessage| {
+            let button = Button::new(Text::new(label));
+            if selected {
+                button.on_press(message)
+            } else {
+                button
+            }
+        };
+
+        let path_bar = Row::new()
+            .spacing(6)
+            .align_items(Alignment::Center)
+            .push(Button::new(Text::new("Up")).on_press(SftpMessage::UpPressed))
+            .push(
+                TextInput::new("Remote path", &self.path)
+                    .on_input(SftpMessage::PathChanged)
+                    .on_submit(SftpMessage::GoPressed)
+                    .padding(6),
+            )
+            .push(Button::new(Text::new("Go")).on_press(SftpMessage::GoPressed))
+            .push(Button::new(Text::new("Refresh")).on_press(SftpMessage::RefreshPressed));
+
+        let actions = Row::new()
+            .spacing(6)
+            .push(Button::new(Text::new("Upload...")).on_press(SftpMessage::UploadPressed))
+            .push(on_selected("Download...", SftpMessage::DownloadPressed))
+            .push(Button::new(Text::new("New folder...")).on_press(SftpMessage::MkdirPressed))
+            .push(on_selected("Rename...", SftpMessage::RenamePressed))
+            .push(on_selected("Chmod...", SftpMessage::ChmodPressed))
+            .push(on_selected("Delete", SftpMessage::DeletePressed));
+
+        let entries = match &self.listing {
+            Some(listing) => listing
+                .entries
+                .iter()
+                .enumerate()
+                .fold(Column::new().spacing(1), |column, (index, entry)| {
+                    column.push(self.entry_row(index, entry))
+                }),
+            None => Column::new(),
+        };
+
+        let mut content = Column::new()
+            .spacing(10)
+            .padding(10)
+            .push(path_bar)
+            .push(actions)
+            .push(Scrollable::new(entries).height(Length::Fill));
+        if self.loading {
+            content = content.push(Text::new("Loading...").size(14));
+        }
+        if let Some(error) = &self.error {
+            content = content.push(
+                Text::new(error)
+                    .size(14)
+                    .style(iced::Color::from_rgb(0.8, 0.2, 0.2)),
+            );
+        }
+
+        let content = Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill);
+        let content: Element<'_, SftpMessage> =
+            Modal::new(self.prompt.is_some(), content, move || match &self.prompt {
+                Some(prompt) => self.prompt_view(prompt),
+                None => Text::new("").into(),
+            })
+            .on_esc(SftpMessage::PromptCancelled)
+            .into();
+
+        let id = self.id;
+        content.map(move |message| Message::Sftp(id, message))
+    }
+}
+
+fn prompt_input<'a>(placeholder: &str, value: &str) -> Element<'a, SftpMessage> {
+    TextInput::new(placeholder, value)
+        .on_input(SftpMessage::PromptChanged)
+        .on_submit(SftpMessage::PromptConfirmed)
+        .padding(8)
+        .into()
+}
+
+fn dialog_button(label: &str, message: SftpMessage) -> Button<'_, SftpMessage> {
+    Button::new(Text::new(label).horizontal_alignment(Horizontal::Center))
+        .width(Length::Fill)
+        .on_press(message)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// `ls -l` style, e.g. `drwxr-xr-x`.
+fn mode_string(entry: &RemoteEntry) -> String {
+    let mut mode = String::with_capacity(10);
+    mode.push(match entry.kind {
+        EntryKind::Dir => 'd',
+        EntryKind::Symlink => 'l',
+        EntryKind::File => '-',
+        EntryKind::Other => '?',
+    });
+    for (shift, special, set, unset) in [
+        (6, 0o4000, 's', 'S'),
+        (3, 0o2000, 's', 'S'),
+        (0, 0o1000, 't', 'T'),
+    ] {
+        let bits = entry.mode >> shift;
+        mode.push(if bits & 4 != 0 { 'r' } else { '-' });
+        mode.push(if bits & 2 != 0 { 'w' } else { '-' });
+        mode.push(match (bits & 1 != 0, entry.mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    mode
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC.
+fn format_time(seconds: u32) -> String {
+    let days = i64::from(seconds / 86_400);
+    let minutes = seconds % 86_400 / 60;
+
+    // Howard Hinnant's days-to-civil conversion.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
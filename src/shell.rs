@@ -3,7 +3,7 @@ use iced::widget::{
     button, checkbox, container, horizontal_space, pick_list, row, slider, svg, text, text_input,
     toggler, vertical_slider,
 };
-use iced::{alignment, theme, Color, Sandbox};
+use iced::{alignment, theme, Color, Command, Sandbox};
 
 use iced_aw::menu::{menu_tree::MenuTree, CloseCondition, ItemHeight, ItemWidth, PathHighlight};
 use iced_aw::{quad, TabLabel};
@@ -13,59 +13,422 @@ use iced::{
     widget::{Container, Text},
     Element, Length, Settings, Theme,
 };
+use std::time::{Duration, Instant};
+
+use crate::forward::{self, ForwardState, ForwardStatus};
+use crate::keybindings::Keybindings;
+use crate::palette::Palette;
+use crate::pty;
+use crate::search::Search;
+use crate::sftp_tab::format_size;
+use crate::ssh::{self, Connection, SessionEvent, SshSession};
+use crate::terminal::{Terminal, DEFAULT_COLUMNS, DEFAULT_ROWS};
+use crate::terminal_view::{Highlight, TerminalView, DEFAULT_FONT_SIZE};
 use crate::{Icon, Message, Tab};
-use std::process::Command;
 
+/// Connections listed under each forward in the forwards panel, at most.
+const MAX_LISTED_CONNECTIONS: usize = 20;
+
+/// The wait before the first reconnect, doubled after each failure.
+const FIRST_RECONNECT_DELAY: Duration = Duration::from_secs(1);
 
 pub struct ShellViewTab {
-    output: String,
-    input:  String,
-    submit_button_state: String,
+    id: usize,
+    title: String,
+    terminal: Terminal,
+    connection: Option<Connection>,
+    local: bool,
+    show_forwards: bool,
+    new_forward: String,
+    forward_error: Option<String>,
+    disconnected: Option<Disconnected>,
+    /// The longest wait between reconnects, or `None` to wait for the user.
+    reconnect_max_delay: Option<Duration>,
+    /// The search, while the find bar is open.
+    search: Option<Search>,
+    /// The terminal colour scheme.
+    palette: Palette,
+    /// The terminal font size.
+    font_size: f32,
+    /// Shortcuts; the terminal handles copy and paste and passes the rest on.
+    keybindings: Keybindings,
+}
+
+/// What reconnecting a dropped session needs, and how far it has got.
+struct Disconnected {
+    session: SshSession,
+    reason: String,
+    attempts: u32,
+    retry: Retry,
+}
+
+enum Retry {
+    At(Instant),
+    Connecting,
+    Manual,
 }
 
 #[derive(Debug, Clone)]
 pub enum ShellMessage {
-    SubmitInput,
-    InputChanged(String),
-    DataChanged(String),
+    Input(Vec<u8>),
+    Paste(String),
+    Session(SessionEvent),
+    Disconnect,
+    ToggleForwards,
+    StartForward(usize),
+    StopForward(usize),
+    NewForwardChanged(String),
+    AddForward,
+    Tick,
+    ReconnectNow,
+    CancelReconnect,
+    Reconnected(Result<Connection, String>),
+    ScrollTo(usize),
+    Resize(usize, usize),
+    /// The terminal of a pane other than the focused one was clicked.
+    Focused,
+    ToggleFind,
+    FindChanged(String),
+    FindRegexToggled(bool),
+    FindCaseToggled(bool),
+    FindNext,
+    FindPrevious,
 }
 
 impl ShellViewTab {
-    pub fn new() -> Self {
+    pub fn new(id: usize) -> Self {
+        let mut tab = ShellViewTab {
+            id,
+            title: String::from("Terminal"),
+            terminal: Terminal::new(DEFAULT_COLUMNS, DEFAULT_ROWS),
+            connection: None,
+            local: true,
+            show_forwards: false,
+            new_forward: String::new(),
+            forward_error: None,
+            disconnected: None,
+            reconnect_max_delay: None,
+            search: None,
+            palette: Palette::default(),
+            font_size: DEFAULT_FONT_SIZE,
+            keybindings: Keybindings::default(),
+        };
+        tab.start_local_shell();
+        tab
+    }
+
+    pub fn with_connection(id: usize, connection: Connection) -> Self {
         ShellViewTab {
-            output: String::new(),
-            input: String::new(),
-            submit_button_state: String::new(),
+            id,
+            title: connection.title().to_string(),
+            terminal: Terminal::new(DEFAULT_COLUMNS, DEFAULT_ROWS),
+            connection: Some(connection),
+            local: false,
+            show_forwards: false,
+            new_forward: String::new(),
+            forward_error: None,
+            disconnected: None,
+            reconnect_max_delay: None,
+            search: None,
+            palette: Palette::default(),
+            font_size: DEFAULT_FONT_SIZE,
+            keybindings: Keybindings::default(),
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Starts the local shell in a pseudo-terminal.
+    fn start_local_shell(&mut self) {
+        let scrollback = self.terminal.scrollback_limit();
+        let (columns, rows) = (self.terminal.columns(), self.terminal.rows());
+        self.terminal = Terminal::new(columns, rows);
+        self.terminal.set_scrollback_limit(scrollback);
+        match pty::spawn_shell(columns as u16, rows as u16) {
+            Ok(connection) => {
+                self.title = connection.title().to_string();
+                self.connection = Some(connection);
+            }
+            Err(e) => self.print(&format!("{}\n", e)),
+        }
+    }
+
+    /// Writes text produced locally, translating bare newlines for the
+    /// terminal the way a tty's output processing would.
+    fn print(&mut self, text: &str) {
+        self.terminal.advance(text.replace('\n', "\r\n").as_bytes());
+    }
+
+    pub fn connection(&self) -> Option<&Connection> {
+        self.connection.as_ref()
+    }
+
+    /// Whether the forwards panel is open; its counters need redrawing
+    /// while it is.
+    pub fn showing_forwards(&self) -> bool {
+        self.show_forwards && self.connection.is_some()
+    }
+
+    /// Whether this is the local shell rather than an SSH session.
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    /// Whether the SSH session has dropped, whether or not a reconnect is
+    /// under way.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.is_some()
+    }
+
+    /// Whether a reconnect countdown is running, which needs a tick every
+    /// second.
+    pub fn reconnect_pending(&self) -> bool {
+        matches!(
+            self.disconnected,
+            Some(Disconnected {
+                retry: Retry::At(_),
+                ..
+            })
+        )
+    }
+
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.terminal.set_scrollback_limit(lines);
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn set_font_size(&mut self, size: f32) {
+        self.font_size = size;
+    }
+
+    pub fn set_keybindings(&mut self, keybindings: Keybindings) {
+        self.keybindings = keybindings;
+    }
+
+    /// Fits the grid to a resized view and tells the other end of the session.
+    fn resize(&mut self, columns: usize, rows: usize) {
+        if (columns, rows) == (self.terminal.columns(), self.terminal.rows()) {
+            return;
+        }
+        self.terminal.resize(columns, rows);
+        self.send_size();
+    }
+
+    fn send_size(&self) {
+        if let Some(connection) = &self.connection {
+            connection.resize(self.terminal.columns() as u16, self.terminal.rows() as u16);
+        }
+    }
+
+    fn find_input_id(&self) -> text_input::Id {
+        text_input::Id::new(format!("find-{}", self.id))
+    }
+
+    fn find_step(&mut self, forward: bool) {
+        if let Some(search) = &mut self.search {
+            search.step(&mut self.terminal, forward);
+        }
+    }
+
+    pub fn set_reconnect_max_delay(&mut self, max_delay: Option<Duration>) {
+        self.reconnect_max_delay = max_delay;
+        if let Some(disconnected) = &mut self.disconnected {
+            if max_delay.is_none() && matches!(disconnected.retry, Retry::At(_)) {
+                disconnected.retry = Retry::Manual;
+            }
+        }
+    }
+
+    /// Shuts the session down; called when the tab is closed.
+    pub fn close(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+    }
+
+    /// Schedules the next reconnect with exponential backoff.
+    fn schedule_reconnect(&mut self) {
+        let Some(disconnected) = &mut self.disconnected else {
+            return;
+        };
+        disconnected.retry = match self.reconnect_max_delay {
+            Some(max_delay) => {
+                let delay = FIRST_RECONNECT_DELAY
+                    .saturating_mul(1 << disconnected.attempts.min(16))
+                    .min(max_delay);
+                Retry::At(Instant::now() + delay)
+            }
+            None => Retry::Manual,
+        };
+    }
+
+    fn reconnect(&mut self) -> Command<ShellMessage> {
+        let Some(disconnected) = &mut self.disconnected else {
+            return Command::none();
+        };
+        if matches!(disconnected.retry, Retry::Connecting) {
+            return Command::none();
         }
+        disconnected.retry = Retry::Connecting;
+        disconnected.attempts += 1;
+        Command::perform(
+            ssh::reconnect(disconnected.session.clone()),
+            ShellMessage::Reconnected,
+        )
     }
 
-    pub fn update(&mut self, message: ShellMessage) {
+    pub fn update(&mut self, message: ShellMessage) -> Command<ShellMessage> {
         match message {
-            ShellMessage::SubmitInput => {
-                // 处理用户输入并模拟终端命令执行
-                let output = Command::new(&self.input)
-                    .output()
-                    .expect("Failed to execute command");
-
-                if output.status.success() {
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    self.output.push_str(&format!("$ {}\n", stdout));
-                } else {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    self.output.push_str(&format!("$ {}\n", stderr));
+            ShellMessage::Input(bytes) => match &self.connection {
+                Some(connection) => {
+                    // Typing scrolls back to the bottom.
+                    self.terminal.set_display_offset(0);
+                    connection.send(bytes)
+                }
+                // Enter restarts a local shell that exited.
+                None if self.local && bytes.contains(&b'\r') => self.start_local_shell(),
+                // Enter reconnects a dropped SSH session right away.
+                None if bytes.contains(&b'\r') => return self.reconnect(),
+                None => {}
+            },
+            ShellMessage::Paste(text) if text.is_empty() => {}
+            ShellMessage::Paste(text) => {
+                let input = self.terminal.paste_input(&text);
+                return self.update(ShellMessage::Input(input));
+            }
+            // Disconnecting on purpose doesn't reconnect.
+            ShellMessage::Disconnect => {
+                if let Some(connection) = &self.connection {
+                    connection.close();
+                } else if self.disconnected.take().is_some() {
+                    self.print("[reconnect cancelled]\n");
+                }
+            }
+            ShellMessage::Session(SessionEvent::Output(data)) => {
+                self.terminal.advance(&data);
+                let response = self.terminal.take_response();
+                if let (Some(connection), false) = (&self.connection, response.is_empty()) {
+                    connection.send(response);
                 }
-               
-                // 清空输入框
-                self.input.clear();
             }
-            ShellMessage::InputChanged(value) => {
-                self.input = value;
+            ShellMessage::Session(SessionEvent::Closed(reason)) => {
+                self.connection = None;
+                match reason {
+                    Some(reason) => self.print(&format!("\n[connection closed: {}]\n", reason)),
+                    None => self.print("\n[connection closed]\n"),
+                }
+                if self.local {
+                    self.print("[press Enter to restart the shell]\n");
+                }
             }
-            ShellMessage::DataChanged(mut data) => {
-                data.truncate(100);
-                self.input = data;
+            ShellMessage::Session(SessionEvent::Lost(reason)) => {
+                let session = self
+                    .connection
+                    .take()
+                    .and_then(|connection| connection.ssh_session().cloned());
+                self.print(&format!("\n[connection lost: {}]\n", reason));
+                if let Some(session) = session {
+                    self.disconnected = Some(Disconnected {
+                        session,
+                        reason,
+                        attempts: 0,
+                        retry: Retry::Manual,
+                    });
+                    self.schedule_reconnect();
+                }
+            }
+            ShellMessage::Tick => match &self.disconnected {
+                Some(Disconnected {
+                    retry: Retry::At(at),
+                    ..
+                }) if *at <= Instant::now() => return self.reconnect(),
+                _ => {}
+            },
+            ShellMessage::ReconnectNow => return self.reconnect(),
+            ShellMessage::CancelReconnect => {
+                if let Some(disconnected) = &mut self.disconnected {
+                    if matches!(disconnected.retry, Retry::At(_)) {
+                        disconnected.retry = Retry::Manual;
+                    }
+                }
+            }
+            ShellMessage::Reconnected(Ok(connection)) => {
+                // The user disconnected while it reconnected.
+                if self.disconnected.take().is_none() {
+                    connection.close();
+                    return Command::none();
+                }
+                self.connection = Some(connection);
+                // The new session opened at the default size.
+                self.send_size();
+                self.print("[reconnected]\n");
+            }
+            ShellMessage::Reconnected(Err(e)) => {
+                if let Some(disconnected) = &mut self.disconnected {
+                    disconnected.reason = e;
+                }
+                self.schedule_reconnect();
+            }
+            ShellMessage::ScrollTo(offset) => self.terminal.set_display_offset(offset),
+            ShellMessage::Resize(columns, rows) => self.resize(columns, rows),
+            ShellMessage::Focused => {}
+            ShellMessage::ToggleFind => {
+                if self.search.take().is_none() {
+                    self.search = Some(Search::new());
+                    return text_input::focus(self.find_input_id());
+                }
+            }
+            ShellMessage::FindChanged(query) => {
+                if let Some(search) = &mut self.search {
+                    search.set_query(&mut self.terminal, query);
+                }
+            }
+            ShellMessage::FindRegexToggled(regex) => {
+                if let Some(search) = &mut self.search {
+                    search.set_regex(&mut self.terminal, regex);
+                }
+            }
+            ShellMessage::FindCaseToggled(case_sensitive) => {
+                if let Some(search) = &mut self.search {
+                    search.set_case_sensitive(&mut self.terminal, case_sensitive);
+                }
+            }
+            ShellMessage::FindNext => self.find_step(true),
+            ShellMessage::FindPrevious => self.find_step(false),
+            ShellMessage::ToggleForwards => self.show_forwards = !self.show_forwards,
+            ShellMessage::NewForwardChanged(value) => self.new_forward = value,
+            message => {
+                // Forwards can be toggled while the connection is up.
+                let session = self
+                    .connection
+                    .as_ref()
+                    .and_then(|connection| connection.ssh_session());
+                let Some(session) = session else {
+                    return Command::none();
+                };
+                match message {
+                    ShellMessage::StartForward(index) => session.start_forward(index),
+                    ShellMessage::StopForward(index) => session.stop_forward(index),
+                    ShellMessage::AddForward => {
+                        match forward::parse_forward_flag(&self.new_forward) {
+                            Ok((kind, spec)) => {
+                                session.add_forward(kind, spec);
+                                self.new_forward = String::new();
+                                self.forward_error = None;
+                            }
+                            Err(e) => self.forward_error = Some(e),
+                        }
+                    }
+                    _ => {}
+                }
             }
         }
+        Command::none()
     }
 }
 
@@ -73,7 +436,7 @@ impl Tab for ShellViewTab {
     type Message = Message;
 
     fn title(&self) -> String {
-        String::from("Terminal")
+        self.title.clone()
     }
 
     fn tab_label(&self) -> TabLabel {
@@ -82,32 +445,292 @@ impl Tab for ShellViewTab {
     }
 
     fn content(&self) -> Element<'_, Self::Message> {
-        // 显示终端输出的区域
-        let output_text = Text::new(&self.output).size(20);
-
-        // 输入区域
-        // let input_field = TextInput::new(
-        //     "",
-        //     "Enter command",
-        // )
-        // .size(30)
-        // .padding(10)
-        // .on_submit(Message::SubmitInput);
-        let input_field =
-            text_input("Type the data of your QR code here...", &self.input)
-                .on_input(ShellMessage::DataChanged)
-                .size(30)
-                .padding(15)
-                .on_submit(ShellMessage::SubmitInput);
-
-        let out_view = Column::new()
+        self.body(true)
+    }
+}
+
+/// The border of a pane, in the theme colour for the focused one.
+struct PaneBorder(bool);
+
+impl container::StyleSheet for PaneBorder {
+    type Style = Theme;
+
+    fn appearance(&self, theme: &Self::Style) -> container::Appearance {
+        let palette = theme.extended_palette();
+        container::Appearance {
+            border_width: 1.0,
+            border_color: if self.0 {
+                palette.primary.strong.color
+            } else {
+                palette.background.strong.color
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl ShellViewTab {
+    /// Shows this as one of the panes of a tab; only the `active` one takes keys.
+    pub fn pane_view(&self, active: bool) -> Element<'_, Message> {
+        Container::new(self.body(active))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(4)
+            .style(theme::Container::Custom(Box::new(PaneBorder(active))))
+            .into()
+    }
+
+    fn body(&self, active: bool) -> Element<'_, Message> {
+        // Keys typed into the terminal go straight to the session.
+        let terminal = TerminalView::new(&self.terminal, ShellMessage::Input)
+            .on_scroll(ShellMessage::ScrollTo)
+            .on_resize(ShellMessage::Resize)
+            .active(active)
+            .on_focus(|| ShellMessage::Focused)
+            .highlights(self.highlights())
+            .palette(&self.palette)
+            .keybindings(&self.keybindings)
+            .size(self.font_size);
+
+        let content: Element<'_, ShellMessage> = Container::new(terminal)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into();
+
+        let id = self.id;
+        let content = content.map(move |message| Message::Shell(id, message));
+
+        let mut column = Column::new();
+        if let Some(disconnected) = &self.disconnected {
+            column = column.push(self.disconnected_view(disconnected));
+        }
+
+        let mut toolbar = Row::new()
+            .spacing(6)
+            .padding([2, 4])
+            .push(button("Find").on_press(Message::Shell(id, ShellMessage::ToggleFind)));
+        // An SSH session can open SFTP and forwards over the same connection.
+        let session = self.connection().and_then(|connection| connection.ssh_session());
+        let forwards = session.map(|session| session.forwards().statuses());
+        if let Some(forwards) = &forwards {
+            toolbar = toolbar
+                .push(button("Open SFTP").on_press(Message::OpenSftp(id)))
+                .push(
+                    button(text(format!("Forwards ({})", forwards.len())))
+                        .on_press(Message::Shell(id, ShellMessage::ToggleForwards)),
+                );
+        }
+        column = column.push(toolbar);
+        if let Some(search) = &self.search {
+            column = column.push(self.find_view(search));
+        }
+        if let (Some(forwards), true) = (&forwards, self.show_forwards) {
+            column = column.push(self.forwards_view(forwards));
+        }
+        column.push(content).into()
+    }
+
+    /// The search matches on the visible lines.
+    fn highlights(&self) -> Vec<Highlight> {
+        let Some(search) = &self.search else {
+            return Vec::new();
+        };
+        let current = search.current();
+        let dropped = self.terminal.dropped_lines();
+        (0..self.terminal.rows())
+            .flat_map(|row| {
+                let line = self.terminal.visible_line(row);
+                search
+                    .matches_in_line(self.terminal.line(line))
+                    .into_iter()
+                    .map(move |columns| Highlight {
+                        row,
+                        current: current.map_or(false, |current| {
+                            current.line == dropped + line && current.columns == columns
+                        }),
+                        columns,
+                    })
+            })
+            .collect()
+    }
+
+    fn find_view(&self, search: &Search) -> Element<'_, Message> {
+        let id = self.id;
+        let status = match (search.error(), search.position()) {
+            (Some(error), _) => text(error).style(Color::from_rgb(0.8, 0.2, 0.2)),
+            (None, Some((_, 0))) => text("No matches"),
+            (None, Some((index, count))) => text(format!("{} of {}", index, count)),
+            (None, None) => text(""),
+        };
+        Row::new()
+            .spacing(8)
+            .padding([2, 4])
+            .align_items(alignment::Alignment::Center)
+            .push(
+                text_input("Find", search.query())
+                    .id(self.find_input_id())
+                    .on_input(move |query| Message::Shell(id, ShellMessage::FindChanged(query)))
+                    // New output is at the bottom, so Enter searches upwards.
+                    .on_submit(Message::Shell(id, ShellMessage::FindPrevious))
+                    .size(14)
+                    .padding(4)
+                    .width(Length::Fixed(300.0)),
+            )
+            .push(checkbox("Regex", search.regex(), move |regex| {
+                Message::Shell(id, ShellMessage::FindRegexToggled(regex))
+            }))
+            .push(checkbox("Match case", search.case_sensitive(), move |case| {
+                Message::Shell(id, ShellMessage::FindCaseToggled(case))
+            }))
+            .push(
+                button(text("Previous").size(14))
+                    .on_press(Message::Shell(id, ShellMessage::FindPrevious)),
+            )
+            .push(
+                button(text("Next").size(14)).on_press(Message::Shell(id, ShellMessage::FindNext)),
+            )
+            .push(status.size(14))
+            .push(horizontal_space(Length::Fill))
+            .push(
+                button(text("Close").size(14))
+                    .on_press(Message::Shell(id, ShellMessage::ToggleFind)),
+            )
+            .into()
+    }
+
+    fn disconnected_view(&self, disconnected: &Disconnected) -> Element<'_, Message> {
+        let id = self.id;
+        let (status, actions) = match disconnected.retry {
+            Retry::At(at) => {
+                let seconds = at
+                    .saturating_duration_since(Instant::now())
+                    .as_secs_f32()
+                    .ceil();
+                (
+                    format!(
+                        "Reconnecting in {}s (attempt {})",
+                        seconds,
+                        disconnected.attempts + 1
+                    ),
+                    vec![
+                        (String::from("Reconnect now"), ShellMessage::ReconnectNow),
+                        (String::from("Cancel"), ShellMessage::CancelReconnect),
+                    ],
+                )
+            }
+            Retry::Connecting => (
+                format!("Reconnecting (attempt {})...", disconnected.attempts),
+                Vec::new(),
+            ),
+            Retry::Manual => (
+                String::from("Press Enter to reconnect"),
+                vec![(String::from("Reconnect"), ShellMessage::ReconnectNow)],
+            ),
+        };
+
+        let row = Row::new()
             .spacing(10)
-            .push(output_text)
-            .push(Row::new().spacing(10).push(input_field));
+            .padding([4, 8])
+            .align_items(alignment::Alignment::Center)
+            .push(
+                text(format!("Disconnected: {}. {}", disconnected.reason, status))
+                    .style(Color::from_rgb(0.8, 0.2, 0.2))
+                    .width(Length::Fill),
+            );
+        actions
+            .into_iter()
+            .fold(row, |row, (label, message)| {
+                row.push(button(text(label).size(14)).on_press(Message::Shell(id, message)))
+            })
+            .into()
+    }
 
-        // 将内容放入居中的容器中
-        let content: Element<'_, ShellMessage> = Container::new(out_view).into();
+    fn forwards_view(&self, forwards: &[ForwardStatus]) -> Element<'_, Message> {
+        let id = self.id;
+        let list = forwards.iter().enumerate().fold(
+            Column::new().spacing(2),
+            |column, (index, forward)| {
+                let (state, running) = match &forward.state {
+                    ForwardState::Starting => (String::from("starting"), true),
+                    ForwardState::Listening => (String::from("listening"), true),
+                    ForwardState::Failed(e) => (format!("failed: {}", e), false),
+                    ForwardState::Stopped => (String::from("stopped"), false),
+                };
+                let toggle = if running {
+                    button(text("Stop").size(14))
+                        .on_press(Message::Shell(id, ShellMessage::StopForward(index)))
+                } else {
+                    button(text("Start").size(14))
+                        .on_press(Message::Shell(id, ShellMessage::StartForward(index)))
+                };
+                let column = column.push(
+                    Row::new()
+                        .spacing(16)
+                        .align_items(alignment::Alignment::Center)
+                        .push(
+                            text(format!("{} {}", forward.kind.flag(), forward.spec))
+                                .size(14)
+                                .width(Length::Fill),
+                        )
+                        .push(text(state).size(14).width(Length::Fixed(200.0)))
+                        .push(
+                            text(format!("{} connections", forward.connections.len()))
+                                .size(14)
+                                .width(Length::Fixed(120.0)),
+                        )
+                        .push(
+                            text(format!(
+                                "in {} / out {}",
+                                format_size(forward.bytes_in),
+                                format_size(forward.bytes_out)
+                            ))
+                            .size(14)
+                            .width(Length::Fixed(200.0)),
+                        )
+                        .push(toggle),
+                );
+                // The connections being forwarded, only the first few if there are many.
+                let shown = forward.connections.iter().take(MAX_LISTED_CONNECTIONS);
+                let column = shown.fold(column, |column, connection| {
+                    column.push(text(format!("    {}", connection)).size(12))
+                });
+                if forward.connections.len() > MAX_LISTED_CONNECTIONS {
+                    column.push(
+                        text(format!(
+                            "    ... {} more",
+                            forward.connections.len() - MAX_LISTED_CONNECTIONS
+                        ))
+                        .size(12),
+                    )
+                } else {
+                    column
+                }
+            },
+        );
+
+        let add = Row::new()
+            .spacing(6)
+            .push(
+                text_input(
+                    "-L 8080:localhost:80, -R 9000:localhost:3000 or -D 1080",
+                    &self.new_forward,
+                )
+                .on_input(move |value| {
+                    Message::Shell(id, ShellMessage::NewForwardChanged(value))
+                })
+                .on_submit(Message::Shell(id, ShellMessage::AddForward))
+                .size(14)
+                .padding(4),
+            )
+            .push(
+                button(text("Add").size(14))
+                    .on_press(Message::Shell(id, ShellMessage::AddForward)),
+            );
 
-        content.map(Message::Shell)
+        let mut column = Column::new().spacing(6).padding([4, 8]).push(list).push(add);
+        if let Some(error) = &self.forward_error {
+            column = column.push(text(error).size(14).style(Color::from_rgb(0.8, 0.2, 0.2)));
+        }
+        column.into()
     }
 }
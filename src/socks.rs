@@ -0,0 +1,457 @@
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Longest user id, host name or password in a SOCKS request.
+const MAX_FIELD: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Socks4,
+    Socks5,
+}
+
+/// Where a client asked to be connected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub version: Version,
+    pub host: String,
+    pub port: u16,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Reads a SOCKS4, SOCKS4a or SOCKS5 `CONNECT` request. Only SOCKS5 without
+/// authentication is offered; anything else is refused on the wire before
+/// an error is returned.
+pub async fn accept<S>(stream: &mut S) -> io::Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match stream.read_u8().await? {
+        4 => accept_socks4(stream).await,
+        5 => accept_socks5(stream).await,
+        _ => Err(invalid("not a SOCKS request")),
+    }
+}
+
+async fn read_until_nul<S>(stream: &mut S) -> io::Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut field = Vec::new();
+    loop {
+        match stream.read_u8().await? {
+            0 => return Ok(field),
+            _ if field.len() == MAX_FIELD => return Err(invalid("SOCKS4 field too long")),
+            byte => field.push(byte),
+        }
+    }
+}
+
+async fn accept_socks4<S>(stream: &mut S) -> io::Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let command = stream.read_u8().await?;
+    let port = stream.read_u16().await?;
+    let mut ip = [0; 4];
+    stream.read_exact(&mut ip).await?;
+    let _user = read_until_nul(stream).await?;
+
+    // SOCKS4a: 0.0.0.x with x != 0 means a host name follows.
+    let host = if ip[..3] == [0, 0, 0] && ip[3] != 0 {
+        String::from_utf8(read_until_nul(stream).await?)
+            .map_err(|_| invalid("SOCKS4a host name is not UTF-8"))?
+    } else {
+        Ipv4Addr::from(ip).to_string()
+    };
+
+    if command != 1 {
+        reply(stream, Version::Socks4, false).await?;
+        return Err(invalid("only SOCKS CONNECT is supported"));
+    }
+    Ok(Request {
+        version: Version::Socks4,
+        host,
+        port,
+    })
+}
+
+async fn accept_socks5<S>(stream: &mut S) -> io::Result<Request>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let count = stream.read_u8().await?;
+    let mut methods = vec![0; usize::from(count)];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&0) {
+        stream.write_all(&[5, 0xff]).await?;
+        return Err(invalid("the SOCKS client requires authentication"));
+    }
+    stream.write_all(&[5, 0]).await?;
+
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).await?;
+    let [version, command, _, address_type] = header;
+    if version != 5 {
+        return Err(invalid("bad SOCKS5 request"));
+    }
+    let host = match address_type {
+        1 => {
+            let mut ip = [0; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let mut name = vec![0; usize::from(stream.read_u8().await?)];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| invalid("SOCKS5 host name is not UTF-8"))?
+        }
+        4 => {
+            let mut ip = [0; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        _ => {
+            // Address type not supported.
+            stream.write_all(&[5, 8, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+            return Err(invalid("unknown SOCKS5 address type"));
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    if command != 1 {
+        // Command not supported.
+        stream.write_all(&[5, 7, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+        return Err(invalid("only SOCKS CONNECT is supported"));
+    }
+    Ok(Request {
+        version: Version::Socks5,
+        host,
+        port,
+    })
+}
+
+/// Tells the client whether the connection it asked for was made. The bound
+/// address is not meaningful for a tunnel, so it is left as zeros.
+pub async fn reply<S>(stream: &mut S, version: Version, success: bool) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    match version {
+        Version::Socks4 => {
+            let status = if success { 0x5a } else { 0x5b };
+            stream.write_all(&[0, status, 0, 0, 0, 0, 0, 0]).await
+        }
+        Version::Socks5 => {
+            // 5 is "connection refused", the closest fit for a failed
+            // channel open.
+            let status = if success { 0 } else { 5 };
+            stream.write_all(&[5, status, 0, 1, 0, 0, 0, 0, 0, 0]).await
+        }
+    }
+}
+
+/// Asks a SOCKS5 server to connect to `host:port`, with username and
+/// password authentication if `credentials` are given.
+pub async fn connect<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    credentials: Option<(&str, &str)>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let greeting: &[u8] = match credentials {
+        Some(_) => &[5, 2, 0, 2],
+        None => &[5, 1, 0],
+    };
+    stream.write_all(greeting).await?;
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).await?;
+    match (choice, credentials) {
+        ([5, 0], _) => {}
+        ([5, 2], Some((user, password))) => {
+            if user.len() > MAX_FIELD || password.len() > MAX_FIELD {
+                return Err(invalid("SOCKS5 username or password too long"));
+            }
+            let mut request = vec![1, user.len() as u8];
+            request.extend_from_slice(user.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+            let mut status = [0; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    "SOCKS5 authentication failed",
+                ));
+            }
+        }
+        ([5, 0xff], _) | ([5, 2], None) => {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "the SOCKS5 server requires authentication",
+            ))
+        }
+        _ => return Err(invalid("not a SOCKS5 server")),
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > MAX_FIELD {
+                return Err(invalid("host name too long for SOCKS5"));
+            }
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).await?;
+    if header[0] != 5 {
+        return Err(invalid("bad SOCKS5 reply"));
+    }
+    if header[1] != 0 {
+        return Err(io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("SOCKS5 connect failed: {}", reply_message(header[1])),
+        ));
+    }
+    // Skip the bound address.
+    let length = match header[3] {
+        1 => 4,
+        4 => 16,
+        3 => usize::from(stream.read_u8().await?),
+        _ => return Err(invalid("bad SOCKS5 reply")),
+    };
+    let mut bound = vec![0; length + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, DuplexStream};
+
+    /// Runs `accept` on one end of a pipe after `request` is written to the
+    /// other, returning its result and everything it wrote back.
+    async fn accept_bytes(request: &[u8]) -> (io::Result<Request>, Vec<u8>) {
+        let (mut client, mut server) = duplex(1024);
+        client.write_all(request).await.unwrap();
+        client.shutdown().await.unwrap();
+        let result = accept(&mut server).await;
+        drop(server);
+        let mut written = Vec::new();
+        client.read_to_end(&mut written).await.unwrap();
+        (result, written)
+    }
+
+    fn request(version: Version, host: &str, port: u16) -> Request {
+        Request {
+            version,
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[tokio::test]
+    async fn socks4() {
+        let (result, written) = accept_bytes(b"\x04\x01\x00\x50\xc0\x00\x02\x01user\0").await;
+        assert_eq!(result.unwrap(), request(Version::Socks4, "192.0.2.1", 80));
+        assert!(written.is_empty());
+
+        // Anything but CONNECT is refused.
+        let (result, written) = accept_bytes(b"\x04\x02\x00\x50\xc0\x00\x02\x01\0").await;
+        assert!(result.is_err());
+        assert_eq!(written, [0, 0x5b, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn socks4a() {
+        let (result, _) = accept_bytes(b"\x04\x01\x01\xbb\x00\x00\x00\x07\0example.com\0").await;
+        assert_eq!(
+            result.unwrap(),
+            request(Version::Socks4, "example.com", 443)
+        );
+
+        let mut long = b"\x04\x01\x00\x50\x00\x00\x00\x01\0".to_vec();
+        long.extend([b'a'; 300]);
+        long.push(0);
+        let (result, _) = accept_bytes(&long).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn socks5() {
+        let (result, written) =
+            accept_bytes(b"\x05\x02\x02\x00\x05\x01\x00\x03\x0bexample.com\x00\x16").await;
+        assert_eq!(result.unwrap(), request(Version::Socks5, "example.com", 22));
+        assert_eq!(written, [5, 0]);
+
+        let (result, _) =
+            accept_bytes(b"\x05\x01\x00\x05\x01\x00\x01\x7f\x00\x00\x01\x1f\x90").await;
+        assert_eq!(result.unwrap(), request(Version::Socks5, "127.0.0.1", 8080));
+
+        let mut ipv6 = b"\x05\x01\x00\x05\x01\x00\x04".to_vec();
+        ipv6.extend(Ipv6Addr::LOCALHOST.octets());
+        ipv6.extend(443u16.to_be_bytes());
+        let (result, _) = accept_bytes(&ipv6).await;
+        assert_eq!(result.unwrap(), request(Version::Socks5, "::1", 443));
+    }
+
+    #[tokio::test]
+    async fn socks5_refusals() {
+        // Only "no authentication" is offered.
+        let (result, written) = accept_bytes(b"\x05\x01\x02").await;
+        assert!(result.is_err());
+        assert_eq!(written, [5, 0xff]);
+
+        // UDP ASSOCIATE: command not supported.
+        let (result, written) =
+            accept_bytes(b"\x05\x01\x00\x05\x03\x00\x01\x00\x00\x00\x00\x00\x00").await;
+        assert!(result.is_err());
+        assert_eq!(written, [5, 0, 5, 7, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+        // Address type 9: not supported.
+        let (result, written) = accept_bytes(b"\x05\x01\x00\x05\x01\x00\x09").await;
+        assert!(result.is_err());
+        assert_eq!(written, [5, 0, 5, 8, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+        let (result, _) = accept_bytes(b"\x16\x03\x01").await;
+        assert!(result.is_err());
+        let (result, _) = accept_bytes(b"\x05\x01").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn replies() {
+        for (version, success, expected) in [
+            (Version::Socks4, true, &[0, 0x5a, 0, 0, 0, 0, 0, 0][..]),
+            (Version::Socks4, false, &[0, 0x5b, 0, 0, 0, 0, 0, 0][..]),
+            (Version::Socks5, true, &[5, 0, 0, 1, 0, 0, 0, 0, 0, 0][..]),
+            (Version::Socks5, false, &[5, 5, 0, 1, 0, 0, 0, 0, 0, 0][..]),
+        ] {
+            let mut written = Vec::new();
+            reply(&mut written, version, success).await.unwrap();
+            assert_eq!(written, expected);
+        }
+    }
+
+    /// `connect` against our own `accept`.
+    #[tokio::test]
+    async fn connect_round_trip() {
+        for host in ["example.com", "192.0.2.1", "2001:db8::1"] {
+            let (mut client, mut server) = duplex(1024);
+            let serve = async {
+                let request = accept(&mut server).await.unwrap();
+                reply(&mut server, request.version, true).await.unwrap();
+                request
+            };
+            let (connected, request) = tokio::join!(connect(&mut client, host, 22, None), serve);
+            connected.unwrap();
+            assert_eq!(
+                request,
+                Request {
+                    version: Version::Socks5,
+                    host: host.to_string(),
+                    port: 22,
+                }
+            );
+        }
+
+        let (mut client, mut server) = duplex(1024);
+        let serve = async {
+            let request = accept(&mut server).await.unwrap();
+            reply(&mut server, request.version, false).await.unwrap();
+        };
+        let (connected, ()) = tokio::join!(connect(&mut client, "example.com", 22, None), serve);
+        assert_eq!(connected.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+    }
+
+    /// Plays a SOCKS5 server that wants a password, replying `auth_status`.
+    async fn password_server(server: &mut DuplexStream, auth_status: u8) -> Vec<u8> {
+        let mut greeting = [0; 4];
+        server.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [5, 2, 0, 2]);
+        server.write_all(&[5, 2]).await.unwrap();
+        let mut auth = vec![0; 2 + 5 + 1 + 6];
+        server.read_exact(&mut auth).await.unwrap();
+        server.write_all(&[1, auth_status]).await.unwrap();
+        if auth_status != 0 {
+            return auth;
+        }
+        let mut request = [0; 4 + 1 + 4 + 2];
+        server.read_exact(&mut request).await.unwrap();
+        assert_eq!(request, *b"\x05\x01\x00\x03\x04host\x00\x16");
+        // A bound address given as a name, which has to be skipped.
+        server
+            .write_all(b"\x05\x00\x00\x03\x05proxy\x04\x38")
+            .await
+            .unwrap();
+        server.write_all(b"SSH-2.0-").await.unwrap();
+        auth
+    }
+
+    #[tokio::test]
+    async fn connect_with_password() {
+        let (mut client, mut server) = duplex(1024);
+        let credentials = Some(("alice", "secret"));
+        let (connected, auth) = tokio::join!(
+            connect(&mut client, "host", 22, credentials),
+            password_server(&mut server, 0)
+        );
+        connected.unwrap();
+        assert_eq!(auth, b"\x01\x05alice\x06secret");
+        // Nothing past the reply was consumed.
+        let mut banner = [0; 8];
+        client.read_exact(&mut banner).await.unwrap();
+        assert_eq!(&banner, b"SSH-2.0-");
+
+        let (mut client, mut server) = duplex(1024);
+        let (connected, _) = tokio::join!(
+            connect(&mut client, "host", 22, credentials),
+            password_server(&mut server, 1)
+        );
+        assert_eq!(connected.unwrap_err().kind(), ErrorKind::PermissionDenied);
+
+        // Without credentials, a server that insists is an error.
+        let (mut client, mut server) = duplex(1024);
+        server.write_all(&[5, 0xff]).await.unwrap();
+        let error = connect(&mut client, "host", 22, None).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,834 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use iced::futures::SinkExt;
+use iced::{subscription, Subscription};
+use russh::client;
+use russh::{Channel, ChannelMsg, Disconnect};
+use russh_keys::agent::client::AgentClient;
+use russh_keys::key;
+use russh_keys::PublicKeyBase64;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UnixStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::forward::{ForwardKind, ForwardState, Forwards};
+use crate::known_hosts::{self, HostKeyStatus};
+use crate::proxy::{self, Proxy, Transport};
+use crate::ssh_config::ForwardSpec;
+use crate::terminal::{DEFAULT_COLUMNS, DEFAULT_ROWS};
+
+static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct ConnectParams {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth: Auth,
+    pub forward_agent: bool,
+    pub keepalive: Keepalive,
+    /// Started as soon as the shell is open.
+    pub local_forwards: Vec<ForwardSpec>,
+    pub remote_forwards: Vec<ForwardSpec>,
+    pub dynamic_forwards: Vec<ForwardSpec>,
+    /// How to reach the host when connecting to it directly rather than
+    /// through a jump host.
+    pub proxy: Option<Proxy>,
+    /// Hosts to go through first, in order, each with its own credentials.
+    /// Their own `jumps` and forwards are ignored.
+    pub jumps: Vec<ConnectParams>,
+}
+
+/// Requests sent while the connection is idle. Once `max` in a row go
+/// unanswered the server is taken to be gone and the connection is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    pub interval: Option<Duration>,
+    pub max: usize,
+}
+
+#[derive(Clone)]
+pub enum Auth {
+    Password(String),
+    PublicKey {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+    Agent,
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Password(_) => write!(f, "Password"),
+            Auth::PublicKey { path, .. } => write!(f, "PublicKey({})", path.display()),
+            Auth::Agent => write!(f, "Agent"),
+        }
+    }
+}
+
+async fn load_private_key(
+    path: PathBuf,
+    passphrase: Option<String>,
+) -> Result<key::KeyPair, String> {
+    // Encrypted OpenSSH keys go through bcrypt_pbkdf, which is slow on purpose.
+    tokio::task::spawn_blocking(move || {
+        russh_keys::load_secret_key(&path, passphrase.as_deref()).map_err(|e| match e {
+            russh_keys::Error::KeyIsEncrypted => {
+                format!("{} is encrypted, enter its passphrase", path.display())
+            }
+            e => format!("Could not load {}: {}", path.display(), e),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+async fn connect_agent() -> Result<AgentClient<UnixStream>, String> {
+    AgentClient::connect_env()
+        .await
+        .map_err(|e| format!("Could not reach ssh-agent via $SSH_AUTH_SOCK: {}", e))
+}
+
+type HostKeyReport = (String, Vec<u8>, oneshot::Sender<bool>);
+
+pub struct ClientHandler {
+    forward_agent: bool,
+    forwards: Forwards,
+    host_key: Option<oneshot::Sender<HostKeyReport>>,
+    verified_key: Option<Vec<u8>>,
+}
+
+#[async_trait]
+impl client::Handler for ClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &key::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let presented = server_public_key.public_key_bytes();
+        // Re-keying must present the same key the user already agreed to.
+        if let Some(verified) = &self.verified_key {
+            return Ok(*verified == presented);
+        }
+        let Some(report) = self.host_key.take() else {
+            return Ok(false);
+        };
+
+        let (decision_tx, decision_rx) = oneshot::channel();
+        // `name()` is the negotiated signature algorithm, `rsa-sha2-256`
+        // and so on for RSA, which known_hosts lines never use.
+        let key_type = known_hosts::key_type(&presented)
+            .unwrap_or_else(|| server_public_key.name().to_string());
+        if report
+            .send((key_type, presented.clone(), decision_tx))
+            .is_err()
+        {
+            return Ok(false);
+        }
+        let trusted = decision_rx.await.unwrap_or(false);
+        if trusted {
+            self.verified_key = Some(presented);
+        }
+        Ok(trusted)
+    }
+
+    async fn server_channel_open_agent_forward(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        // The server only opens these after we asked for forwarding, but a
+        // hostile one could try anyway.
+        if !self.forward_agent {
+            let _ = channel.close().await;
+            return Ok(());
+        }
+
+        tokio::spawn(async move {
+            let Some(socket) = std::env::var_os("SSH_AUTH_SOCK") else {
+                let _ = channel.close().await;
+                return;
+            };
+            match UnixStream::connect(socket).await {
+                Ok(mut agent) => {
+                    let mut stream = channel.into_stream();
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut agent).await;
+                }
+                Err(_) => {
+                    let _ = channel.close().await;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        // Only ports we asked for are accepted.
+        if let Err(channel) = self.forwards.forwarded(connected_port, channel) {
+            let _ = channel.close().await;
+        }
+        Ok(())
+    }
+}
+
+/// An SSH transport that finished the handshake, shared between the
+/// async commands that drive authentication and channel setup.
+#[derive(Clone)]
+pub struct SshSession {
+    handle: Arc<tokio::sync::Mutex<client::Handle<ClientHandler>>>,
+    params: ConnectParams,
+    forwards: Forwards,
+    /// The jump host this connection is tunnelled through, kept alive for
+    /// as long as this one is.
+    via: Option<Box<SshSession>>,
+    /// The host key the user accepted, which a reconnect must see again.
+    host_key: Vec<u8>,
+}
+
+impl fmt::Debug for SshSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshSession")
+            .field("host", &self.params.host)
+            .field("port", &self.params.port)
+            .finish()
+    }
+}
+
+struct PendingConnection {
+    decision: oneshot::Sender<bool>,
+    forwards: Forwards,
+    connecting: JoinHandle<Result<client::Handle<ClientHandler>, russh::Error>>,
+    params: ConnectParams,
+    via: Option<SshSession>,
+}
+
+/// The server's host key, held while the handshake waits for the user to
+/// decide whether to trust it. Nothing has been sent to the server yet.
+#[derive(Clone)]
+pub struct HostKeyCheck {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub randomart: String,
+    pub status: HostKeyStatus,
+    key: Vec<u8>,
+    pending: Arc<Mutex<Option<PendingConnection>>>,
+}
+
+impl fmt::Debug for HostKeyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostKeyCheck")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("fingerprint", &self.fingerprint)
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+pub async fn connect(params: ConnectParams) -> Result<HostKeyCheck, String> {
+    let stream = transport(&params, None).await?;
+    handshake(params, stream, None).await
+}
+
+/// Connects to `params` through a `direct-tcpip` channel of an
+/// authenticated jump host.
+pub async fn connect_via(jump: SshSession, params: ConnectParams) -> Result<HostKeyCheck, String> {
+    let stream = transport(&params, Some(&jump)).await?;
+    handshake(params, stream, Some(jump)).await
+}
+
+/// Opens a byte stream to the host in `params`, through `via` if given and
+/// otherwise directly or through the configured proxy.
+async fn transport(
+    params: &ConnectParams,
+    via: Option<&SshSession>,
+) -> Result<Box<dyn Transport>, String> {
+    match via {
+        Some(jump) => {
+            let channel = jump
+                .open_direct_tcpip(
+                    &params.host,
+                    params.port,
+                    SocketAddr::from(([127, 0, 0, 1], 0)),
+                )
+                .await?;
+            Ok(Box::new(channel.into_stream()))
+        }
+        None => {
+            proxy::connect(
+                params.proxy.as_ref(),
+                &params.host,
+                params.port,
+                &params.username,
+            )
+            .await
+        }
+    }
+}
+
+fn client_config(params: &ConnectParams) -> Arc<client::Config> {
+    Arc::new(client::Config {
+        keepalive_interval: params.keepalive.interval,
+        keepalive_max: params.keepalive.max,
+        ..Default::default()
+    })
+}
+
+async fn handshake<S>(
+    params: ConnectParams,
+    stream: S,
+    via: Option<SshSession>,
+) -> Result<HostKeyCheck, String>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let config = client_config(&params);
+    let (report_tx, report_rx) = oneshot::channel();
+    let forwards = Forwards::default();
+    let handler = ClientHandler {
+        forward_agent: params.forward_agent,
+        forwards: forwards.clone(),
+        host_key: Some(report_tx),
+        verified_key: None,
+    };
+    let connecting = tokio::spawn(client::connect_stream(config, stream, handler));
+
+    let Ok((key_type, key, decision)) = report_rx.await else {
+        // The handler was dropped before the key exchange got that far.
+        return Err(match connecting.await {
+            Ok(Err(e)) => format!(
+                "Connection to {}:{} failed: {}",
+                params.host, params.port, e
+            ),
+            _ => format!(
+                "Connection to {}:{} closed during key exchange",
+                params.host, params.port
+            ),
+        });
+    };
+
+    let status = match known_hosts::default_path() {
+        Some(path) => known_hosts::check(&path, &params.host, params.port, &key_type, &key),
+        None => HostKeyStatus::Unknown,
+    };
+
+    Ok(HostKeyCheck {
+        host: params.host.clone(),
+        port: params.port,
+        fingerprint: known_hosts::fingerprint(&key),
+        randomart: known_hosts::randomart(&key_type, &key),
+        key_type,
+        status,
+        key,
+        pending: Arc::new(Mutex::new(Some(PendingConnection {
+            decision,
+            forwards,
+            connecting,
+            params,
+            via,
+        }))),
+    })
+}
+
+impl HostKeyCheck {
+    pub async fn accept(self, save: bool) -> Result<SshSession, String> {
+        let pending = self.pending.lock().unwrap().take();
+        let Some(pending) = pending else {
+            return Err(String::from("The host key was already answered"));
+        };
+
+        if save {
+            if let Some(path) = known_hosts::default_path() {
+                if let Err(e) =
+                    known_hosts::append(&path, &self.host, self.port, &self.key_type, &self.key)
+                {
+                    let _ = pending.decision.send(false);
+                    return Err(format!("Could not save to {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        let _ = pending.decision.send(true);
+        let handle = pending
+            .connecting
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Connection to {}:{} failed: {}", self.host, self.port, e))?;
+
+        Ok(SshSession {
+            handle: Arc::new(tokio::sync::Mutex::new(handle)),
+            params: pending.params,
+            forwards: pending.forwards,
+            via: pending.via.map(Box::new),
+            host_key: self.key,
+        })
+    }
+
+    pub fn reject(&self) {
+        if let Some(pending) = self.pending.lock().unwrap().take() {
+            let _ = pending.decision.send(false);
+        }
+    }
+}
+
+/// Opens a new shell with the same settings as `session`, through the same
+/// jump hosts, each on a fresh connection. Every host must present the key
+/// that was accepted the first time, so nothing needs asking.
+pub async fn reconnect(session: SshSession) -> Result<Connection, String> {
+    let reopened = reopen_all(&session).await?;
+
+    // Bring back the forwards that were running, including ones added
+    // after connecting, instead of the ones first configured.
+    let mut params = reopened.params.clone();
+    let statuses = session.forwards.statuses();
+    let running = |kind| {
+        statuses
+            .iter()
+            .filter(|status| status.kind == kind && status.state != ForwardState::Stopped)
+            .map(|status| status.spec.clone())
+            .collect()
+    };
+    params.local_forwards = running(ForwardKind::Local);
+    params.remote_forwards = running(ForwardKind::Remote);
+    params.dynamic_forwards = running(ForwardKind::Dynamic);
+    SshSession { params, ..reopened }.open_shell().await
+}
+
+/// Opens a second shell like `session`'s on connections of its own. The
+/// port forwards are left out, since `session` still holds their ports.
+pub async fn duplicate(session: SshSession) -> Result<Connection, String> {
+    let mut reopened = reopen_all(&session).await?;
+    reopened.params.local_forwards.clear();
+    reopened.params.remote_forwards.clear();
+    reopened.params.dynamic_forwards.clear();
+    reopened.open_shell().await
+}
+
+/// Connects again to `session`'s host through the same jump hosts.
+async fn reopen_all(session: &SshSession) -> Result<SshSession, String> {
+    let mut hops = Vec::new();
+    let mut current = Some(session);
+    while let Some(hop) = current {
+        hops.push(hop);
+        current = hop.via.as_deref();
+    }
+
+    let mut via = None;
+    for hop in hops.into_iter().rev() {
+        via = Some(reopen(hop, via).await?);
+    }
+    via.ok_or_else(|| String::from("Nothing to reconnect"))
+}
+
+async fn reopen(previous: &SshSession, via: Option<SshSession>) -> Result<SshSession, String> {
+    let params = previous.params.clone();
+    let stream = transport(&params, via.as_ref()).await?;
+    let forwards = Forwards::default();
+    let handler = ClientHandler {
+        forward_agent: params.forward_agent,
+        forwards: forwards.clone(),
+        host_key: None,
+        verified_key: Some(previous.host_key.clone()),
+    };
+    let handle = client::connect_stream(client_config(&params), stream, handler)
+        .await
+        .map_err(|e| match e {
+            russh::Error::UnknownKey => format!(
+                "The host key of {}:{} has changed, reconnect from the login page",
+                params.host, params.port
+            ),
+            e => format!(
+                "Connection to {}:{} failed: {}",
+                params.host, params.port, e
+            ),
+        })?;
+    SshSession {
+        handle: Arc::new(tokio::sync::Mutex::new(handle)),
+        params,
+        forwards,
+        via: via.map(Box::new),
+        host_key: previous.host_key.clone(),
+    }
+    .authenticate()
+    .await
+}
+
+impl SshSession {
+    pub fn username(&self) -> &str {
+        &self.params.username
+    }
+
+    pub async fn authenticate(self) -> Result<SshSession, String> {
+        let username = &self.params.username;
+        let authenticated = match &self.params.auth {
+            Auth::Password(password) => self
+                .handle
+                .lock()
+                .await
+                .authenticate_password(username, password)
+                .await
+                .map_err(|e| format!("Authentication failed: {}", e))?,
+            Auth::PublicKey { path, passphrase } => {
+                let key = load_private_key(path.clone(), passphrase.clone()).await?;
+                self.handle
+                    .lock()
+                    .await
+                    .authenticate_publickey(username, Arc::new(key))
+                    .await
+                    .map_err(|e| format!("Authentication failed: {}", e))?
+            }
+            Auth::Agent => {
+                let mut agent = connect_agent().await?;
+                let identities = agent
+                    .request_identities()
+                    .await
+                    .map_err(|e| format!("Could not list ssh-agent identities: {}", e))?;
+                if identities.is_empty() {
+                    return Err(String::from("ssh-agent holds no identities"));
+                }
+
+                let mut handle = self.handle.lock().await;
+                let mut authenticated = false;
+                for identity in identities {
+                    let (returned, result) =
+                        handle.authenticate_future(username, identity, agent).await;
+                    agent = returned;
+                    if result.map_err(|e| format!("ssh-agent could not sign: {}", e))? {
+                        authenticated = true;
+                        break;
+                    }
+                }
+                authenticated
+            }
+        };
+
+        if authenticated {
+            Ok(self)
+        } else {
+            Err(format!(
+                "Permission denied for {}@{}",
+                self.params.username, self.params.host
+            ))
+        }
+    }
+
+    /// Opens a session channel running `name`, e.g. the sftp subsystem.
+    pub(crate) async fn open_subsystem(&self, name: &str) -> Result<Channel<client::Msg>, String> {
+        let channel = self
+            .handle
+            .lock()
+            .await
+            .channel_open_session()
+            .await
+            .map_err(|e| format!("Could not open a session channel: {}", e))?;
+        channel
+            .request_subsystem(true, name)
+            .await
+            .map_err(|e| format!("{} subsystem request failed: {}", name, e))?;
+        Ok(channel)
+    }
+
+    /// Opens a `direct-tcpip` channel to `host:port` as seen from the server,
+    /// on behalf of a local client at `originator`.
+    pub(crate) async fn open_direct_tcpip(
+        &self,
+        host: &str,
+        port: u16,
+        originator: SocketAddr,
+    ) -> Result<Channel<client::Msg>, String> {
+        self.handle
+            .lock()
+            .await
+            .channel_open_direct_tcpip(
+                host,
+                port.into(),
+                originator.ip().to_string(),
+                originator.port().into(),
+            )
+            .await
+            .map_err(|e| format!("Could not open a channel to {}:{}: {}", host, port, e))
+    }
+
+    /// Asks the server to listen on `address:port` and open a
+    /// `forwarded-tcpip` channel back to us for every connection.
+    pub(crate) async fn request_remote_forward(
+        &self,
+        address: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let accepted = self
+            .handle
+            .lock()
+            .await
+            .tcpip_forward(address, port.into())
+            .await
+            .map_err(|e| format!("Remote forward request failed: {}", e))?;
+        if accepted {
+            Ok(())
+        } else {
+            Err(format!(
+                "The server refused to listen on {}:{}",
+                address, port
+            ))
+        }
+    }
+
+    pub(crate) async fn cancel_remote_forward(
+        &self,
+        address: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        self.handle
+            .lock()
+            .await
+            .cancel_tcpip_forward(address, port.into())
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Could not cancel the remote forward: {}", e))
+    }
+
+    pub fn forwards(&self) -> &Forwards {
+        &self.forwards
+    }
+
+    /// Adds a forward to the live connection and starts it.
+    pub fn add_forward(&self, kind: ForwardKind, spec: ForwardSpec) {
+        let index = self.forwards.add(kind, spec);
+        self.forwards.start(self, index);
+    }
+
+    pub fn start_forward(&self, index: usize) {
+        self.forwards.start(self, index);
+    }
+
+    pub fn stop_forward(&self, index: usize) {
+        self.forwards.stop(self, index);
+    }
+
+    pub async fn open_shell(self) -> Result<Connection, String> {
+        let channel = self
+            .handle
+            .lock()
+            .await
+            .channel_open_session()
+            .await
+            .map_err(|e| format!("Could not open a session channel: {}", e))?;
+        channel
+            .request_pty(
+                false,
+                "xterm-256color",
+                DEFAULT_COLUMNS as u32,
+                DEFAULT_ROWS as u32,
+                0,
+                0,
+                &[],
+            )
+            .await
+            .map_err(|e| format!("PTY request failed: {}", e))?;
+        if self.params.forward_agent {
+            channel
+                .agent_forward(false)
+                .await
+                .map_err(|e| format!("Agent forwarding request failed: {}", e))?;
+        }
+        channel
+            .request_shell(true)
+            .await
+            .map_err(|e| format!("Shell request failed: {}", e))?;
+
+        let title = format!("{}@{}", self.params.username, self.params.host);
+        let (mut connection, input, events) = Connection::channel(title);
+        connection.session = Some(self.clone());
+        for spec in &self.params.local_forwards {
+            self.add_forward(ForwardKind::Local, spec.clone());
+        }
+        for spec in &self.params.remote_forwards {
+            self.add_forward(ForwardKind::Remote, spec.clone());
+        }
+        for spec in &self.params.dynamic_forwards {
+            self.add_forward(ForwardKind::Dynamic, spec.clone());
+        }
+
+        tokio::spawn(run_session(self, channel, input, events));
+
+        Ok(connection)
+    }
+
+    /// Closes this connection, then the jump hosts it went through.
+    async fn disconnect(&self) {
+        self.forwards.stop_all();
+        let mut session = Some(self);
+        while let Some(current) = session {
+            let _ = current
+                .handle
+                .lock()
+                .await
+                .disconnect(Disconnect::ByApplication, "", "English")
+                .await;
+            session = current.via.as_deref();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    Output(Vec<u8>),
+    Closed(Option<String>),
+    /// The connection went away under the shell, e.g. the server stopped
+    /// answering keepalives.
+    Lost(String),
+}
+
+pub(crate) enum SessionInput {
+    Data(Vec<u8>),
+    Resize { columns: u16, rows: u16 },
+    Close,
+}
+
+/// A live interactive shell. Input goes in through `send`, output comes back
+/// through the iced subscription returned by `subscription`.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    id: usize,
+    title: String,
+    input: mpsc::UnboundedSender<SessionInput>,
+    events: Arc<Mutex<Option<mpsc::UnboundedReceiver<SessionEvent>>>>,
+    session: Option<SshSession>,
+}
+
+impl Connection {
+    /// A connection plus the ends its backend task talks through.
+    pub(crate) fn channel(
+        title: String,
+    ) -> (
+        Connection,
+        mpsc::UnboundedReceiver<SessionInput>,
+        mpsc::UnboundedSender<SessionEvent>,
+    ) {
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let connection = Connection {
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            title,
+            input: input_tx,
+            events: Arc::new(Mutex::new(Some(event_rx))),
+            session: None,
+        };
+        (connection, input_rx, event_tx)
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The SSH transport behind this shell, for opening more channels on it.
+    /// `None` for local shells.
+    pub fn ssh_session(&self) -> Option<&SshSession> {
+        self.session.as_ref()
+    }
+
+    pub fn send(&self, data: impl Into<Vec<u8>>) {
+        let _ = self.input.send(SessionInput::Data(data.into()));
+    }
+
+    /// Tells the other end the terminal is now `columns` by `rows`.
+    pub fn resize(&self, columns: u16, rows: u16) {
+        let _ = self.input.send(SessionInput::Resize { columns, rows });
+    }
+
+    pub fn close(&self) {
+        let _ = self.input.send(SessionInput::Close);
+    }
+
+    pub fn subscription(&self) -> Subscription<(usize, SessionEvent)> {
+        let id = self.id;
+        let events = self.events.clone();
+
+        subscription::channel(id, 100, move |mut output| async move {
+            let receiver = events.lock().unwrap().take();
+            if let Some(mut receiver) = receiver {
+                while let Some(event) = receiver.recv().await {
+                    let _ = output.send((id, event)).await;
+                }
+            }
+
+            loop {
+                iced::futures::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+async fn run_session(
+    session: SshSession,
+    mut channel: Channel<client::Msg>,
+    mut input: mpsc::UnboundedReceiver<SessionInput>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+) {
+    let lost = loop {
+        tokio::select! {
+            message = channel.wait() => match message {
+                Some(ChannelMsg::Data { data }) => {
+                    let _ = events.send(SessionEvent::Output(data.to_vec()));
+                }
+                Some(ChannelMsg::ExtendedData { data, .. }) => {
+                    let _ = events.send(SessionEvent::Output(data.to_vec()));
+                }
+                Some(ChannelMsg::ExitStatus { exit_status }) if exit_status != 0 => {
+                    let _ = events.send(SessionEvent::Output(
+                        format!("\r\n[exit status {}]\r\n", exit_status).into_bytes(),
+                    ));
+                }
+                Some(ChannelMsg::Close) => break None,
+                None => break Some(String::from("the connection was lost")),
+                Some(_) => {}
+            },
+            input = input.recv() => match input {
+                Some(SessionInput::Data(data)) => {
+                    if let Err(e) = channel.data(&data[..]).await {
+                        break Some(e.to_string());
+                    }
+                }
+                Some(SessionInput::Resize { columns, rows }) => {
+                    let size = channel.window_change(columns.into(), rows.into(), 0, 0);
+                    if let Err(e) = size.await {
+                        break Some(e.to_string());
+                    }
+                }
+                Some(SessionInput::Close) | None => {
+                    let _ = channel.close().await;
+                    break None;
+                }
+            },
+        }
+    };
+
+    session.disconnect().await;
+    let _ = events.send(match lost {
+        Some(reason) => SessionEvent::Lost(reason),
+        None => SessionEvent::Closed(None),
+    });
+}
@@ -0,0 +1,854 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A dynamic (`-D`) forward has no fixed target: its `host` is empty and
+/// `host_port` is 0.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardSpec {
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    pub bind_port: u16,
+    pub host: String,
+    pub host_port: u16,
+}
+
+/// The `-L` command line form, `[bind_address:]port:host:hostport`, or
+/// `[bind_address:]port` for a dynamic forward.
+impl fmt::Display for ForwardSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(address) = &self.bind_address {
+            write!(f, "{}:", bracket(address))?;
+        }
+        if self.is_dynamic() {
+            return write!(f, "{}", self.bind_port);
+        }
+        write!(
+            f,
+            "{}:{}:{}",
+            self.bind_port,
+            bracket(&self.host),
+            self.host_port
+        )
+    }
+}
+
+impl ForwardSpec {
+    pub fn is_dynamic(&self) -> bool {
+        self.host.is_empty()
+    }
+}
+
+/// One hop of a `ProxyJump` list, `[user@]host[:port]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpSpec {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl fmt::Display for JumpSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{}@", user)?;
+        }
+        write!(f, "{}", bracket(&self.host))?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+fn bracket(host: &str) -> String {
+    if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+/// Settings for one host as `ssh` would resolve them: the first value found
+/// for a keyword wins, except for the keywords that accumulate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostConfig {
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_files: Vec<PathBuf>,
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    pub local_forwards: Vec<ForwardSpec>,
+    pub remote_forwards: Vec<ForwardSpec>,
+    pub dynamic_forwards: Vec<ForwardSpec>,
+    pub server_alive_interval: Option<u64>,
+    pub server_alive_count_max: Option<usize>,
+}
+
+struct Resolver<'a> {
+    alias: &'a str,
+    local_user: String,
+    config: HostConfig,
+    identity_files: Vec<String>,
+}
+
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("config"))
+}
+
+/// Resolves `alias` against the user's `~/.ssh/config`, then the system-wide
+/// `/etc/ssh/ssh_config`.
+pub fn resolve(alias: &str) -> HostConfig {
+    let mut paths: Vec<PathBuf> = user_config_path().into_iter().collect();
+    paths.push(PathBuf::from("/etc/ssh/ssh_config"));
+    resolve_files(alias, &paths)
+}
+
+pub fn resolve_files(alias: &str, paths: &[PathBuf]) -> HostConfig {
+    let mut resolver = Resolver {
+        alias,
+        local_user: std::env::var("USER").unwrap_or_default(),
+        config: HostConfig::default(),
+        identity_files: Vec::new(),
+    };
+    for path in paths {
+        // Relative includes resolve against ~/.ssh for the user file and
+        // /etc/ssh for the system one, which is where each top-level file lives.
+        let base = path.parent().unwrap_or(Path::new("/"));
+        resolver.read_file(path, base, 0);
+    }
+    resolver.finish()
+}
+
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            '\\' if quoted => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Splits `Keyword value`, `Keyword=value` and `Keyword = value`, leaving
+/// the value as written.
+fn split_keyword(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = line[..end].to_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+    Some((keyword, rest.trim_start()))
+}
+
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p.eq_ignore_ascii_case(n) => {
+            wildcard_match(&pattern[1..], &name[1..])
+        }
+        _ => false,
+    }
+}
+
+/// A negated pattern that matches vetoes the whole list.
+fn patterns_match<'p>(patterns: impl IntoIterator<Item = &'p str>, name: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated.as_bytes(), name.as_bytes()) {
+                return false;
+            }
+        } else if wildcard_match(pattern.as_bytes(), name.as_bytes()) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ if path == "~" => dirs::home_dir().unwrap_or_else(|| PathBuf::from(path)),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn parse_port(value: &str) -> Option<u16> {
+    value.parse().ok().filter(|&port| port != 0)
+}
+
+/// Splits `host:port`, `[v6::addr]:port` or a bare `port`.
+pub fn split_host_port(value: &str) -> Option<(Option<String>, u16)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, port) = rest.split_once("]:")?;
+        return Some((Some(host.to_string()), parse_port(port)?));
+    }
+    match value.rsplit_once(':') {
+        Some((host, port)) => Some((Some(host.to_string()), parse_port(port)?)),
+        None => Some((None, parse_port(value)?)),
+    }
+}
+
+pub fn parse_forward(listen: &str, target: &str) -> Option<ForwardSpec> {
+    let (bind_address, bind_port) = split_host_port(listen)?;
+    let (host, host_port) = split_host_port(target)?;
+    Some(ForwardSpec {
+        bind_address: bind_address.filter(|address| !address.is_empty()),
+        bind_port,
+        host: host.filter(|host| !host.is_empty())?,
+        host_port,
+    })
+}
+
+fn parse_jump(value: &str) -> Option<JumpSpec> {
+    let (user, host_port) = match value.rsplit_once('@') {
+        Some((user, host_port)) => (Some(user.to_string()), host_port),
+        None => (None, value),
+    };
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']')? {
+            (host, "") => (host, None),
+            (host, port) => (host, Some(parse_port(port.strip_prefix(':')?)?)),
+        },
+        None => match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(parse_port(port)?)),
+            None => (host_port, None),
+        },
+    };
+    if host.is_empty() || user.as_deref() == Some("") {
+        return None;
+    }
+    Some(JumpSpec {
+        user,
+        host: host.to_string(),
+        port,
+    })
+}
+
+/// Parses a comma-separated `ProxyJump` value, first hop first.
+pub fn parse_proxy_jump(value: &str) -> Option<Vec<JumpSpec>> {
+    value.split(',').map(|hop| parse_jump(hop.trim())).collect()
+}
+
+pub fn format_proxy_jump(hops: &[JumpSpec]) -> String {
+    hops.iter()
+        .map(JumpSpec::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses `[bind_address:]port` as `ssh -D` and `DynamicForward` take it.
+/// An IPv6 bind address has to be in brackets.
+pub fn parse_dynamic_arg(arg: &str) -> Option<ForwardSpec> {
+    let fields = split_fields(arg)?;
+    let (bind_address, port) = match &fields[..] {
+        [port] => (None, port),
+        [address, port] => (Some(address.clone()), port),
+        _ => return None,
+    };
+    Some(ForwardSpec {
+        bind_address: bind_address.filter(|address| !address.is_empty()),
+        bind_port: parse_port(port)?,
+        host: String::new(),
+        host_port: 0,
+    })
+}
+
+/// Splits on colons outside `[...]`, dropping the brackets.
+fn split_fields(value: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut bracketed = false;
+    for c in value.chars() {
+        match c {
+            '[' if current.is_empty() && !bracketed => bracketed = true,
+            ']' if bracketed => bracketed = false,
+            ':' if !bracketed => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    if bracketed {
+        return None;
+    }
+    fields.push(current);
+    Some(fields)
+}
+
+/// Parses the single-argument form `ssh -L` takes.
+pub fn parse_forward_arg(arg: &str) -> Option<ForwardSpec> {
+    let fields = split_fields(arg)?;
+    let (bind_address, rest) = match fields.len() {
+        3 => (None, &fields[..]),
+        4 => (Some(fields[0].clone()), &fields[1..]),
+        _ => return None,
+    };
+    if rest[1].is_empty() {
+        return None;
+    }
+    Some(ForwardSpec {
+        bind_address: bind_address.filter(|address| !address.is_empty()),
+        bind_port: parse_port(&rest[0])?,
+        host: rest[1].clone(),
+        host_port: parse_port(&rest[2])?,
+    })
+}
+
+impl Resolver<'_> {
+    fn current_host(&self) -> String {
+        match &self.config.host_name {
+            Some(host_name) => host_name.replace("%h", self.alias),
+            None => self.alias.to_string(),
+        }
+    }
+
+    fn read_file(&mut self, path: &Path, base: &Path, depth: usize) {
+        if depth > MAX_INCLUDE_DEPTH {
+            return;
+        }
+        if let Ok(contents) = fs::read_to_string(path) {
+            self.read_contents(&contents, base, depth);
+        }
+    }
+
+    fn read_contents(&mut self, contents: &str, base: &Path, depth: usize) {
+        let mut active = true;
+        for line in contents.lines() {
+            let Some((keyword, value)) = split_keyword(line) else {
+                continue;
+            };
+            let args = split_args(value);
+            match keyword.as_str() {
+                "host" => active = patterns_match(args.iter().map(String::as_str), self.alias),
+                "match" => active = self.match_criteria(&args),
+                "include" if active => {
+                    for pattern in &args {
+                        self.include(pattern, base, depth);
+                    }
+                }
+                // The command goes to the shell as written, quotes and all.
+                "proxycommand" if active && !value.is_empty() => {
+                    let config = &mut self.config;
+                    if config.proxy_command.is_none() && config.proxy_jump.is_none() {
+                        config.proxy_command = Some(value.to_string());
+                    }
+                }
+                _ if active => self.apply(&keyword, &args),
+                _ => {}
+            }
+        }
+    }
+
+    fn include(&mut self, pattern: &str, base: &Path, depth: usize) {
+        let mut pattern = expand_tilde(pattern);
+        if pattern.is_relative() {
+            pattern = base.join(pattern);
+        }
+        let Ok(paths) = glob::glob(&pattern.to_string_lossy()) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = paths.flatten().collect();
+        paths.sort();
+        for path in paths {
+            self.read_file(&path, base, depth + 1);
+        }
+    }
+
+    fn match_criteria(&self, args: &[String]) -> bool {
+        let mut args = args.iter();
+        let mut matched = true;
+
+        while let Some(criterion) = args.next() {
+            let lowered = criterion.to_lowercase();
+            let (negate, name) = match lowered.strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, lowered.as_str()),
+            };
+            let result = match name {
+                "all" => true,
+                // Only meaningful with hostname canonicalisation, which we
+                // don't do; treat them as a single pass like `ssh -G`.
+                "canonical" | "final" => true,
+                "host" | "originalhost" | "user" | "localuser" | "exec" => {
+                    let Some(value) = args.next() else {
+                        return false;
+                    };
+                    let list = || value.split(',');
+                    match name {
+                        "host" => patterns_match(list(), &self.current_host()),
+                        "originalhost" => patterns_match(list(), self.alias),
+                        "user" => match &self.config.user {
+                            Some(user) => patterns_match(list(), user),
+                            None => patterns_match(list(), &self.local_user),
+                        },
+                        "localuser" => patterns_match(list(), &self.local_user),
+                        // Running arbitrary commands while filling in a form is
+                        // not something we want to do.
+                        _ => false,
+                    }
+                }
+                _ => return false,
+            };
+            matched &= result != negate;
+        }
+        matched
+    }
+
+    fn apply(&mut self, keyword: &str, args: &[String]) {
+        let Some(value) = args.first() else {
+            return;
+        };
+        let config = &mut self.config;
+        match keyword {
+            "hostname" => {
+                config.host_name.get_or_insert_with(|| value.clone());
+            }
+            "user" => {
+                config.user.get_or_insert_with(|| value.clone());
+            }
+            "port" if config.port.is_none() => {
+                config.port = parse_port(value);
+            }
+            "identityfile" => self.identity_files.push(value.clone()),
+            // Whichever of ProxyJump and ProxyCommand comes first wins.
+            "proxyjump" if config.proxy_command.is_none() => {
+                config.proxy_jump.get_or_insert_with(|| value.clone());
+            }
+            "localforward" => {
+                if let Some(forward) = args.get(1).and_then(|target| parse_forward(value, target)) {
+                    config.local_forwards.push(forward);
+                }
+            }
+            "remoteforward" => {
+                if let Some(forward) = args.get(1).and_then(|target| parse_forward(value, target)) {
+                    config.remote_forwards.push(forward);
+                }
+            }
+            "dynamicforward" => {
+                if let Some(forward) = parse_dynamic_arg(value) {
+                    config.dynamic_forwards.push(forward);
+                }
+            }
+            "serveraliveinterval" if config.server_alive_interval.is_none() => {
+                config.server_alive_interval = value.parse().ok();
+            }
+            "serveralivecountmax" if config.server_alive_count_max.is_none() => {
+                config.server_alive_count_max = value.parse().ok();
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> HostConfig {
+        let host = self.current_host();
+        let user = self
+            .config
+            .user
+            .clone()
+            .unwrap_or_else(|| self.local_user.clone());
+        let port = self.config.port.unwrap_or(22).to_string();
+        let home = dirs::home_dir()
+            .map(|home| home.display().to_string())
+            .unwrap_or_default();
+
+        self.config.host_name = Some(host.clone());
+        if self.config.proxy_jump.as_deref() == Some("none") {
+            self.config.proxy_jump = None;
+        }
+        if self.config.proxy_command.as_deref() == Some("none") {
+            self.config.proxy_command = None;
+        }
+        self.config.identity_files = self
+            .identity_files
+            .iter()
+            .map(|file| {
+                let mut expanded = String::new();
+                let mut chars = file.chars();
+                while let Some(c) = chars.next() {
+                    if c != '%' {
+                        expanded.push(c);
+                        continue;
+                    }
+                    match chars.next() {
+                        Some('h') => expanded.push_str(&host),
+                        Some('r') => expanded.push_str(&user),
+                        Some('u') => expanded.push_str(&self.local_user),
+                        Some('p') => expanded.push_str(&port),
+                        Some('d') => expanded.push_str(&home),
+                        Some(other) => expanded.push(other),
+                        None => {}
+                    }
+                }
+                expand_tilde(&expanded)
+            })
+            .collect();
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_str(alias: &str, contents: &str) -> HostConfig {
+        let mut resolver = Resolver {
+            alias,
+            local_user: "me".to_string(),
+            config: HostConfig::default(),
+            identity_files: Vec::new(),
+        };
+        resolver.read_contents(contents, Path::new("/nonexistent"), 0);
+        resolver.finish()
+    }
+
+    fn forward(
+        bind_address: Option<&str>,
+        bind_port: u16,
+        host: &str,
+        host_port: u16,
+    ) -> ForwardSpec {
+        ForwardSpec {
+            bind_address: bind_address.map(str::to_string),
+            bind_port,
+            host: host.to_string(),
+            host_port,
+        }
+    }
+
+    fn jump(user: Option<&str>, host: &str, port: Option<u16>) -> JumpSpec {
+        JumpSpec {
+            user: user.map(str::to_string),
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    /// A fresh directory for config files that include each other.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("my-ssh-config-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const CONFIG: &str = r#"
+# Comments and blank lines are skipped.
+
+Host web web.example.com
+    HostName web.example.com
+    User alice
+    Port 2222
+
+Host *.internal !bastion.internal
+    ProxyJump bob@bastion.internal:2200
+    User internal
+
+Host db
+    HostName=db.internal
+    LocalForward 5432 localhost:5432
+    LocalForward 127.0.0.1:8080 [::1]:80
+
+Match originalhost db host db.internal
+    User dba
+
+Match host *.example.com user alice
+    IdentityFile ~/.ssh/web
+
+Match localuser me !host web.example.com
+    ServerAliveInterval 30
+
+Match exec "true"
+    User never
+
+Host *
+    User fallback
+    Port 22
+    ServerAliveInterval 60
+    ServerAliveCountMax 5
+"#;
+
+    #[test]
+    fn hosts_and_first_value_wins() {
+        let table: &[(&str, &str, Option<&str>, Option<u16>)] = &[
+            ("web", "web.example.com", Some("alice"), Some(2222)),
+            (
+                "web.example.com",
+                "web.example.com",
+                Some("alice"),
+                Some(2222),
+            ),
+            ("app.internal", "app.internal", Some("internal"), Some(22)),
+            (
+                "bastion.internal",
+                "bastion.internal",
+                Some("fallback"),
+                Some(22),
+            ),
+            ("db", "db.internal", Some("dba"), Some(22)),
+            ("other", "other", Some("fallback"), Some(22)),
+        ];
+        for &(alias, host_name, user, port) in table {
+            let config = resolve_str(alias, CONFIG);
+            assert_eq!(config.host_name.as_deref(), Some(host_name), "{}", alias);
+            assert_eq!(config.user.as_deref(), user, "{}", alias);
+            assert_eq!(config.port, port, "{}", alias);
+        }
+    }
+
+    #[test]
+    fn match_criteria() {
+        // `Match host` sees the HostName set so far, `originalhost` the alias.
+        let table: &[(&str, Option<u64>, usize)] = &[
+            ("web", Some(60), 1),
+            ("db", Some(30), 0),
+            ("other", Some(30), 0),
+        ];
+        for &(alias, interval, identity_files) in table {
+            let config = resolve_str(alias, CONFIG);
+            assert_eq!(config.server_alive_interval, interval, "{}", alias);
+            assert_eq!(config.server_alive_count_max, Some(5), "{}", alias);
+            assert_eq!(config.identity_files.len(), identity_files, "{}", alias);
+        }
+        assert_eq!(resolve_str("x", "Match bogus\nUser bogus\n").user, None);
+        assert_eq!(
+            resolve_str("x", "Match all\nUser all\n").user.as_deref(),
+            Some("all")
+        );
+        assert_eq!(resolve_str("x", "Match !all\nUser none\n").user, None);
+        assert_eq!(
+            resolve_str("x", "Match user me\nUser me\n").user.as_deref(),
+            Some("me")
+        );
+    }
+
+    #[test]
+    fn forwards_and_proxies() {
+        let config = resolve_str("db", CONFIG);
+        assert_eq!(
+            config.local_forwards,
+            [
+                forward(None, 5432, "localhost", 5432),
+                forward(Some("127.0.0.1"), 8080, "::1", 80),
+            ]
+        );
+        assert_eq!(config.proxy_jump, None);
+
+        let config = resolve_str("app.internal", CONFIG);
+        assert_eq!(
+            config.proxy_jump.as_deref(),
+            Some("bob@bastion.internal:2200")
+        );
+
+        // Whichever of ProxyJump and ProxyCommand comes first wins, and
+        // `none` turns them off.
+        let config = resolve_str("x", "ProxyCommand nc -X 5 %h %p\nProxyJump jump\n");
+        assert_eq!(config.proxy_command.as_deref(), Some("nc -X 5 %h %p"));
+        assert_eq!(config.proxy_jump, None);
+        let config = resolve_str("x", "ProxyJump none\nProxyJump jump\n");
+        assert_eq!(config.proxy_jump, None);
+
+        let config = resolve_str(
+            "x",
+            "DynamicForward 1080\nRemoteForward 9000 localhost:22\n",
+        );
+        assert_eq!(config.dynamic_forwards, [forward(None, 1080, "", 0)]);
+        assert_eq!(
+            config.remote_forwards,
+            [forward(None, 9000, "localhost", 22)]
+        );
+    }
+
+    #[test]
+    fn identity_file_tokens() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        let config = resolve_str(
+            "alias",
+            "HostName real.example.com\nUser alice\nPort 2222\n\
+             IdentityFile %d/keys/%r@%h:%p-%u-%%\nIdentityFile ~/.ssh/id_ed25519\n",
+        );
+        assert_eq!(
+            config.identity_files,
+            [
+                PathBuf::from(format!("{}/keys/alice@real.example.com:2222-me-%", home)),
+                PathBuf::from(home).join(".ssh/id_ed25519"),
+            ]
+        );
+
+        // `%h` in HostName is the alias.
+        let config = resolve_str("web", "HostName %h.example.com\n");
+        assert_eq!(config.host_name.as_deref(), Some("web.example.com"));
+    }
+
+    #[test]
+    fn includes() {
+        let dir = scratch_dir("include");
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("conf.d/a.conf"), "Host b\n    User from-a\n").unwrap();
+        fs::write(
+            dir.join("conf.d/b.conf"),
+            "Host *\n    User from-b\n    Port 2200\n",
+        )
+        .unwrap();
+        fs::write(dir.join("conf.d/c.txt"), "Port 9999\n").unwrap();
+        fs::write(dir.join("hidden"), "User hidden\n").unwrap();
+        fs::write(
+            dir.join("config"),
+            "Host other\n    Include hidden\nHost *\n    Include conf.d/*.conf\n",
+        )
+        .unwrap();
+
+        let config = resolve_files("b", &[dir.join("config")]);
+        assert_eq!(config.user.as_deref(), Some("from-a"));
+        assert_eq!(config.port, Some(2200));
+        let config = resolve_files("c", &[dir.join("config")]);
+        assert_eq!(config.user.as_deref(), Some("from-b"));
+        let config = resolve_files("other", &[dir.join("config")]);
+        assert_eq!(config.user.as_deref(), Some("hidden"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_depth() {
+        let dir = scratch_dir("depth");
+        // Each file includes the next; only the last sets a port.
+        let chain = |length: usize| {
+            for depth in 0..length {
+                fs::write(
+                    dir.join(depth.to_string()),
+                    format!("Include {}\n", depth + 1),
+                )
+                .unwrap();
+            }
+            fs::write(dir.join(length.to_string()), "Port 2222\n").unwrap();
+            resolve_files("x", &[dir.join("0")]).port
+        };
+        assert_eq!(chain(MAX_INCLUDE_DEPTH), Some(2222));
+        assert_eq!(chain(MAX_INCLUDE_DEPTH + 1), None);
+
+        // A file including itself stops at the limit.
+        fs::write(dir.join("loop"), "Include loop\nUser loop\n").unwrap();
+        let config = resolve_files("x", &[dir.join("loop")]);
+        assert_eq!(config.user.as_deref(), Some("loop"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn forward_args() {
+        let table: &[(&str, Option<ForwardSpec>)] = &[
+            (
+                "8080:localhost:80",
+                Some(forward(None, 8080, "localhost", 80)),
+            ),
+            ("*:8080:db:5432", Some(forward(Some("*"), 8080, "db", 5432))),
+            (":8080:db:5432", Some(forward(None, 8080, "db", 5432))),
+            (
+                "[::1]:8080:[fe80::1]:80",
+                Some(forward(Some("::1"), 8080, "fe80::1", 80)),
+            ),
+            ("8080:localhost", None),
+            ("8080::80", None),
+            ("0:localhost:80", None),
+            ("8080:localhost:99999", None),
+            ("[::1:8080:localhost:80", None),
+        ];
+        for (arg, expected) in table {
+            assert_eq!(&parse_forward_arg(arg), expected, "{}", arg);
+        }
+        for (arg, expected) in table
+            .iter()
+            .filter_map(|(arg, spec)| Some((arg, spec.as_ref()?)))
+        {
+            assert_eq!(
+                parse_forward_arg(&expected.to_string()).as_ref(),
+                Some(expected),
+                "{}",
+                arg
+            );
+        }
+
+        assert_eq!(parse_dynamic_arg("1080"), Some(forward(None, 1080, "", 0)));
+        assert_eq!(
+            parse_dynamic_arg("localhost:1080"),
+            Some(forward(Some("localhost"), 1080, "", 0))
+        );
+        assert_eq!(
+            parse_dynamic_arg("[::1]:1080"),
+            Some(forward(Some("::1"), 1080, "", 0))
+        );
+        assert_eq!(parse_dynamic_arg("socks"), None);
+        assert_eq!(parse_dynamic_arg("::1:1080"), None);
+        assert_eq!(parse_dynamic_arg("1080:web:80"), None);
+    }
+
+    #[test]
+    fn proxy_jumps() {
+        let table: &[(&str, Option<Vec<JumpSpec>>)] = &[
+            ("jump", Some(vec![jump(None, "jump", None)])),
+            (
+                "alice@one:2222,[::1]:22,two",
+                Some(vec![
+                    jump(Some("alice"), "one", Some(2222)),
+                    jump(None, "::1", Some(22)),
+                    jump(None, "two", None),
+                ]),
+            ),
+            ("a@b@host", Some(vec![jump(Some("a@b"), "host", None)])),
+            ("[::1]", Some(vec![jump(None, "::1", None)])),
+            ("@host", None),
+            ("host:", None),
+            ("host:0", None),
+            ("one,,two", None),
+            ("[::1]22", None),
+        ];
+        for (value, expected) in table {
+            let hops = parse_proxy_jump(value);
+            assert_eq!(&hops, expected, "{}", value);
+            if let Some(hops) = hops {
+                assert_eq!(parse_proxy_jump(&format_proxy_jump(&hops)), Some(hops));
+            }
+        }
+    }
+}
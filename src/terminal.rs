@@ -0,0 +1,1437 @@
+use std::collections::VecDeque;
+
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Parser, Perform};
+
+pub const DEFAULT_COLUMNS: usize = 80;
+pub const DEFAULT_ROWS: usize = 24;
+pub const DEFAULT_SCROLLBACK: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Color {
+    #[default]
+    Default,
+    /// The 16 palette colours, then the 6x6x6 cube and the grey ramp.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags {
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: Underline,
+    pub strikethrough: bool,
+    pub inverse: bool,
+    /// The right half of a double-width character; drawn by its left half.
+    pub wide_spacer: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub flags: Flags,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            c: ' ',
+            fg: Color::Default,
+            bg: Color::Default,
+            flags: Flags::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Ascii,
+    LineDrawing,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    row: usize,
+    column: usize,
+    template: Cell,
+    origin_mode: bool,
+    charset: Charset,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor {
+            row: 0,
+            column: 0,
+            template: Cell::default(),
+            origin_mode: false,
+            charset: Charset::Ascii,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modes {
+    pub application_cursor: bool,
+    pub autowrap: bool,
+    pub insert: bool,
+    pub cursor_visible: bool,
+    pub bracketed_paste: bool,
+}
+
+struct Screen {
+    columns: usize,
+    rows: usize,
+    grid: Vec<Vec<Cell>>,
+    alternate_grid: Vec<Vec<Cell>>,
+    alternate_active: bool,
+    cursor: Cursor,
+    saved_cursor: Cursor,
+    alternate_saved_cursor: Cursor,
+    wrap_pending: bool,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    tab_stops: Vec<bool>,
+    modes: Modes,
+    title: Option<String>,
+    last_printed: Option<char>,
+    response: Vec<u8>,
+    scrollback: Scrollback,
+}
+
+/// Lines that scrolled off the top of the main screen, oldest first.
+struct Scrollback {
+    lines: VecDeque<Vec<Cell>>,
+    limit: usize,
+    /// How many lines have been dropped off the front since the start, so
+    /// a line keeps the same number while it moves through the buffer.
+    dropped: usize,
+    /// How far back the view is scrolled; 0 shows the live screen.
+    display_offset: usize,
+}
+
+impl Scrollback {
+    fn new(limit: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::new(),
+            limit,
+            dropped: 0,
+            display_offset: 0,
+        }
+    }
+
+    fn push(&mut self, line: Vec<Cell>) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.lines.len() == self.limit {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.lines.push_back(line);
+        // Keep a scrolled-back view on the same text as output arrives.
+        if self.display_offset > 0 {
+            self.display_offset = (self.display_offset + 1).min(self.lines.len());
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.lines.len() > self.limit {
+            self.lines.pop_front();
+            self.dropped += 1;
+        }
+        self.display_offset = self.display_offset.min(self.lines.len());
+    }
+
+    fn clear(&mut self) {
+        self.dropped += self.lines.len();
+        self.lines.clear();
+        self.display_offset = 0;
+    }
+}
+
+/// A VT100/xterm screen: feed it the bytes a session prints with `advance`
+/// and read back the cell grid.
+///
+/// Lines are numbered from the oldest one still in the scrollback to the
+/// bottom of the screen.
+pub struct Terminal {
+    parser: Parser,
+    screen: Screen,
+}
+
+fn blank_grid(columns: usize, rows: usize) -> Vec<Vec<Cell>> {
+    vec![vec![Cell::default(); columns]; rows]
+}
+
+fn default_tab_stops(columns: usize) -> Vec<bool> {
+    (0..columns).map(|column| column % 8 == 0).collect()
+}
+
+fn line_drawing(c: char) -> char {
+    match c {
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'q' => '─',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'a' => '▒',
+        'f' => '°',
+        'g' => '±',
+        'o' => '⎺',
+        's' => '⎽',
+        '`' => '◆',
+        '~' => '·',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        c => c,
+    }
+}
+
+impl Terminal {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        Terminal {
+            parser: Parser::new(),
+            screen: Screen {
+                columns,
+                rows,
+                grid: blank_grid(columns, rows),
+                alternate_grid: blank_grid(columns, rows),
+                alternate_active: false,
+                cursor: Cursor::default(),
+                saved_cursor: Cursor::default(),
+                alternate_saved_cursor: Cursor::default(),
+                wrap_pending: false,
+                scroll_top: 0,
+                scroll_bottom: rows - 1,
+                tab_stops: default_tab_stops(columns),
+                modes: Modes {
+                    autowrap: true,
+                    cursor_visible: true,
+                    ..Modes::default()
+                },
+                title: None,
+                last_printed: None,
+                response: Vec::new(),
+                scrollback: Scrollback::new(DEFAULT_SCROLLBACK),
+            },
+        }
+    }
+
+    pub fn scrollback_limit(&self) -> usize {
+        self.screen.scrollback.limit
+    }
+
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.screen.scrollback.limit = lines;
+        self.screen.scrollback.trim();
+    }
+
+    /// Changes the size of the screen. Lines the main screen loses off the
+    /// top to keep the cursor in view go to the scrollback, and come back
+    /// from it when the screen grows again. Lines are cut or padded, not
+    /// rewrapped.
+    pub fn resize(&mut self, columns: usize, rows: usize) {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        if (columns, rows) == (self.screen.columns, self.screen.rows) {
+            return;
+        }
+        self.screen.resize(columns, rows);
+    }
+
+    pub fn advance(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.parser.advance(&mut self.screen, *byte);
+        }
+    }
+
+    /// Replies the terminal owes the remote side, e.g. cursor position reports.
+    pub fn take_response(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.screen.response)
+    }
+
+    /// What pasting `text` sends: line breaks as Enter, and bracketed if the
+    /// application asked for it, so it can tell a paste from typing.
+    pub fn paste_input(&self, text: &str) -> Vec<u8> {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if self.modes().bracketed_paste {
+            // An end marker inside the text would let the rest of it run
+            // as typed input.
+            let text = text.replace("\x1b[201~", "");
+            format!("\x1b[200~{}\x1b[201~", text).into_bytes()
+        } else {
+            text.into_bytes()
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.screen.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.screen.rows
+    }
+
+    pub fn row(&self, row: usize) -> &[Cell] {
+        &self.screen.grid[row]
+    }
+
+    pub fn scrollback_len(&self) -> usize {
+        self.screen.scrollback.lines.len()
+    }
+
+    /// Scrollback and screen lines together.
+    pub fn line_count(&self) -> usize {
+        self.scrollback_len() + self.screen.rows
+    }
+
+    pub fn line(&self, line: usize) -> &[Cell] {
+        let scrollback = &self.screen.scrollback.lines;
+        match scrollback.get(line) {
+            Some(cells) => cells,
+            None => &self.screen.grid[line - scrollback.len()],
+        }
+    }
+
+    /// How many lines have left the scrollback for good. Adding this to a
+    /// line number gives one that doesn't change as older lines are dropped.
+    pub fn dropped_lines(&self) -> usize {
+        self.screen.scrollback.dropped
+    }
+
+    pub fn display_offset(&self) -> usize {
+        self.screen.scrollback.display_offset
+    }
+
+    pub fn set_display_offset(&mut self, offset: usize) {
+        self.screen.scrollback.display_offset = offset.min(self.scrollback_len());
+    }
+
+    /// The line shown on row `row` of the view, taking scrolling into account.
+    pub fn visible_line(&self, row: usize) -> usize {
+        self.scrollback_len() - self.display_offset() + row
+    }
+
+    /// Scrolls the view so `line` is on screen, centred if it wasn't.
+    pub fn reveal_line(&mut self, line: usize) {
+        let first = self.visible_line(0);
+        if (first..first + self.screen.rows).contains(&line) {
+            return;
+        }
+        let offset = (self.scrollback_len() + self.screen.rows / 2).saturating_sub(line);
+        self.set_display_offset(offset);
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.screen.cursor.row, self.screen.cursor.column)
+    }
+
+    pub fn modes(&self) -> Modes {
+        self.screen.modes
+    }
+
+    /// Whether a full-screen program has switched to the alternate screen,
+    /// which has no scrollback.
+    pub fn alternate_screen(&self) -> bool {
+        self.screen.alternate_active
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.screen.title.as_deref()
+    }
+}
+
+/// Cuts or pads a line to `columns`, blanking a wide character whose right
+/// half would be cut off.
+fn fit_line(line: &mut Vec<Cell>, columns: usize) {
+    if line.get(columns).is_some_and(|cell| cell.flags.wide_spacer) {
+        line[columns - 1] = Cell::default();
+    }
+    line.resize(columns, Cell::default());
+}
+
+/// Changes the number of rows of the main screen, moving lines between it
+/// and the scrollback so the cursor's line stays put.
+fn resize_main(
+    grid: &mut Vec<Vec<Cell>>,
+    cursor: &mut Cursor,
+    scrollback: &mut Scrollback,
+    rows: usize,
+) {
+    if rows < grid.len() {
+        // Rows below the cursor go first, then the ones at the top.
+        let scrolled = (cursor.row + 1).saturating_sub(rows);
+        for line in grid.drain(..scrolled) {
+            scrollback.push(line);
+        }
+        grid.truncate(rows);
+        cursor.row -= scrolled;
+    } else {
+        let restored = (rows - grid.len()).min(scrollback.lines.len());
+        for _ in 0..restored {
+            if let Some(line) = scrollback.lines.pop_back() {
+                grid.insert(0, line);
+            }
+        }
+        scrollback.display_offset = scrollback.display_offset.min(scrollback.lines.len());
+        grid.resize(rows, Vec::new());
+        cursor.row += restored;
+    }
+}
+
+impl Screen {
+    fn resize(&mut self, columns: usize, rows: usize) {
+        // While a full-screen program runs, the main screen and its cursor
+        // are the ones put aside.
+        let (main, main_cursor, alternate) = if self.alternate_active {
+            (
+                &mut self.alternate_grid,
+                &mut self.saved_cursor,
+                &mut self.grid,
+            )
+        } else {
+            (&mut self.grid, &mut self.cursor, &mut self.alternate_grid)
+        };
+        resize_main(main, main_cursor, &mut self.scrollback, rows);
+        alternate.resize(rows, Vec::new());
+        for line in self.grid.iter_mut().chain(&mut self.alternate_grid) {
+            fit_line(line, columns);
+        }
+
+        for cursor in [
+            &mut self.cursor,
+            &mut self.saved_cursor,
+            &mut self.alternate_saved_cursor,
+        ] {
+            cursor.row = cursor.row.min(rows - 1);
+            cursor.column = cursor.column.min(columns - 1);
+        }
+        let old_columns = self.tab_stops.len();
+        self.tab_stops.resize(columns, false);
+        for column in old_columns..columns {
+            self.tab_stops[column] = column % 8 == 0;
+        }
+        self.columns = columns;
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.wrap_pending = false;
+    }
+
+    fn blank(&self) -> Cell {
+        // Erasing fills with the current background (xterm's BCE behaviour).
+        Cell {
+            bg: self.cursor.template.bg,
+            ..Cell::default()
+        }
+    }
+
+    fn goto(&mut self, row: usize, column: usize) {
+        let (top, bottom) = if self.cursor.origin_mode {
+            (self.scroll_top, self.scroll_bottom)
+        } else {
+            (0, self.rows - 1)
+        };
+        self.cursor.row = (top + row).min(bottom);
+        self.cursor.column = column.min(self.columns - 1);
+        self.wrap_pending = false;
+    }
+
+    /// Scrolls the region up. Lines leaving the top of the whole main
+    /// screen go to the scrollback if `history` is set; deleted lines
+    /// never scrolled off, so they don't.
+    fn scroll_up(&mut self, count: usize, history: bool) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let count = count.min(bottom - top + 1);
+        let blank = self.blank();
+        self.grid[top..=bottom].rotate_left(count);
+        // Only whole-screen scrolling on the main screen feeds the history;
+        // full-screen programs scrolling a region would fill it with junk.
+        let keep = history && top == 0 && !self.alternate_active;
+        for row in &mut self.grid[bottom + 1 - count..=bottom] {
+            if keep {
+                self.scrollback.push(row.clone());
+            }
+            row.fill(blank);
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let count = count.min(bottom - top + 1);
+        let blank = self.blank();
+        self.grid[top..=bottom].rotate_right(count);
+        for row in &mut self.grid[top..top + count] {
+            row.fill(blank);
+        }
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1, true);
+        } else if self.cursor.row < self.rows - 1 {
+            self.cursor.row += 1;
+        }
+        self.wrap_pending = false;
+    }
+
+    fn reverse_index(&mut self) {
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+        self.wrap_pending = false;
+    }
+
+    fn carriage_return(&mut self) {
+        self.cursor.column = 0;
+        self.wrap_pending = false;
+    }
+
+    fn put_char(&mut self, c: char) {
+        let c = match self.cursor.charset {
+            Charset::LineDrawing => line_drawing(c),
+            Charset::Ascii => c,
+        };
+        let width = match c.width() {
+            Some(0) | None => return,
+            Some(width) => width.min(2),
+        };
+
+        if self.wrap_pending && self.modes.autowrap {
+            self.carriage_return();
+            self.linefeed();
+        }
+        if width == 2 && self.cursor.column + 1 >= self.columns {
+            if !self.modes.autowrap {
+                return;
+            }
+            // A wide character never straddles the right margin.
+            let blank = self.blank();
+            let row = self.cursor.row;
+            self.grid[row][self.cursor.column] = blank;
+            self.carriage_return();
+            self.linefeed();
+        }
+
+        let (row, column) = (self.cursor.row, self.cursor.column);
+        if self.modes.insert {
+            let line = &mut self.grid[row];
+            line[column..].rotate_right(width);
+        }
+        self.grid[row][column] = Cell {
+            c,
+            ..self.cursor.template
+        };
+        if width == 2 {
+            self.grid[row][column + 1] = Cell {
+                c: ' ',
+                flags: Flags {
+                    wide_spacer: true,
+                    ..self.cursor.template.flags
+                },
+                ..self.cursor.template
+            };
+        }
+
+        if column + width >= self.columns {
+            self.cursor.column = self.columns - 1;
+            self.wrap_pending = true;
+        } else {
+            self.cursor.column = column + width;
+        }
+        self.last_printed = Some(c);
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        let blank = self.blank();
+        let (row, column) = (self.cursor.row, self.cursor.column);
+        match mode {
+            0 => {
+                self.grid[row][column..].fill(blank);
+                for line in &mut self.grid[row + 1..] {
+                    line.fill(blank);
+                }
+            }
+            1 => {
+                self.grid[row][..=column].fill(blank);
+                for line in &mut self.grid[..row] {
+                    line.fill(blank);
+                }
+            }
+            2 => {
+                for line in &mut self.grid {
+                    line.fill(blank);
+                }
+            }
+            // xterm's "erase saved lines" only clears the scrollback.
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        let blank = self.blank();
+        let (row, column) = (self.cursor.row, self.cursor.column);
+        let line = &mut self.grid[row];
+        match mode {
+            0 => line[column..].fill(blank),
+            1 => line[..=column].fill(blank),
+            2 => line.fill(blank),
+            _ => {}
+        }
+        self.wrap_pending = false;
+    }
+
+    fn in_scroll_region(&self) -> bool {
+        (self.scroll_top..=self.scroll_bottom).contains(&self.cursor.row)
+    }
+
+    fn insert_lines(&mut self, count: usize) {
+        if !self.in_scroll_region() {
+            return;
+        }
+        let top = self.scroll_top;
+        self.scroll_top = self.cursor.row;
+        self.scroll_down(count);
+        self.scroll_top = top;
+        self.carriage_return();
+    }
+
+    fn delete_lines(&mut self, count: usize) {
+        if !self.in_scroll_region() {
+            return;
+        }
+        let top = self.scroll_top;
+        self.scroll_top = self.cursor.row;
+        self.scroll_up(count, false);
+        self.scroll_top = top;
+        self.carriage_return();
+    }
+
+    fn insert_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let line = &mut self.grid[self.cursor.row][column..];
+        let count = count.min(line.len());
+        line.rotate_right(count);
+        line[..count].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn delete_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let line = &mut self.grid[self.cursor.row][column..];
+        let count = count.min(line.len());
+        line.rotate_left(count);
+        let len = line.len();
+        line[len - count..].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn erase_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let end = (column + count).min(self.columns);
+        self.grid[self.cursor.row][column..end].fill(blank);
+        self.wrap_pending = false;
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = if bottom == 0 {
+            self.rows
+        } else {
+            bottom.min(self.rows)
+        };
+        let top = top.max(1);
+        if top < bottom {
+            self.scroll_top = top - 1;
+            self.scroll_bottom = bottom - 1;
+            self.goto(0, 0);
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        if self.alternate_active {
+            self.alternate_saved_cursor = self.cursor;
+        } else {
+            self.saved_cursor = self.cursor;
+        }
+    }
+
+    fn restore_cursor(&mut self) {
+        self.cursor = if self.alternate_active {
+            self.alternate_saved_cursor
+        } else {
+            self.saved_cursor
+        };
+        self.cursor.row = self.cursor.row.min(self.rows - 1);
+        self.cursor.column = self.cursor.column.min(self.columns - 1);
+        self.wrap_pending = false;
+    }
+
+    fn set_alternate_screen(&mut self, enable: bool, save_cursor: bool) {
+        if enable == self.alternate_active {
+            return;
+        }
+        if enable && save_cursor {
+            self.save_cursor();
+        }
+        std::mem::swap(&mut self.grid, &mut self.alternate_grid);
+        self.alternate_active = enable;
+        if enable {
+            let blank = self.blank();
+            for line in &mut self.grid {
+                line.fill(blank);
+            }
+        } else if save_cursor {
+            self.restore_cursor();
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+    }
+
+    fn reset(&mut self) {
+        let (columns, rows) = (self.columns, self.rows);
+        let response = std::mem::take(&mut self.response);
+        let scrollback = std::mem::replace(&mut self.scrollback, Scrollback::new(0));
+        *self = Terminal::new(columns, rows).screen;
+        self.response = response;
+        self.scrollback = scrollback;
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enable: bool) {
+        match mode {
+            1 => self.modes.application_cursor = enable,
+            6 => {
+                self.cursor.origin_mode = enable;
+                self.goto(0, 0);
+            }
+            7 => self.modes.autowrap = enable,
+            25 => self.modes.cursor_visible = enable,
+            47 | 1047 => self.set_alternate_screen(enable, false),
+            1048 if enable => self.save_cursor(),
+            1048 => self.restore_cursor(),
+            1049 => self.set_alternate_screen(enable, true),
+            2004 => self.modes.bracketed_paste = enable,
+            _ => {}
+        }
+    }
+
+    fn set_graphic_rendition(&mut self, params: &Params) {
+        let template = &mut self.cursor.template;
+        if params.is_empty() {
+            *template = Cell::default();
+            return;
+        }
+
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            match param[0] {
+                0 => *template = Cell::default(),
+                1 => template.flags.bold = true,
+                2 => template.flags.dim = true,
+                3 => template.flags.italic = true,
+                // `4:n` picks the style, and `4:0` turns it off.
+                4 => {
+                    template.flags.underline = match param.get(1) {
+                        Some(0) => Underline::None,
+                        Some(2) => Underline::Double,
+                        Some(3) => Underline::Curly,
+                        Some(4) => Underline::Dotted,
+                        Some(5) => Underline::Dashed,
+                        _ => Underline::Single,
+                    }
+                }
+                7 => template.flags.inverse = true,
+                9 => template.flags.strikethrough = true,
+                21 => template.flags.underline = Underline::Double,
+                22 => {
+                    template.flags.bold = false;
+                    template.flags.dim = false;
+                }
+                23 => template.flags.italic = false,
+                24 => template.flags.underline = Underline::None,
+                27 => template.flags.inverse = false,
+                29 => template.flags.strikethrough = false,
+                n @ 30..=37 => template.fg = Color::Indexed((n - 30) as u8),
+                39 => template.fg = Color::Default,
+                n @ 40..=47 => template.bg = Color::Indexed((n - 40) as u8),
+                49 => template.bg = Color::Default,
+                n @ 90..=97 => template.fg = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => template.bg = Color::Indexed((n - 100 + 8) as u8),
+                38 => {
+                    if let Some(color) = extended_color(param, &mut params) {
+                        template.fg = color;
+                    }
+                }
+                48 => {
+                    if let Some(color) = extended_color(param, &mut params) {
+                        template.bg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn report(&mut self, reply: String) {
+        self.response.extend_from_slice(reply.as_bytes());
+    }
+}
+
+/// The colour an SGR 38 or 48 sets: `5;n` for a 256-colour index or
+/// `2;r;g;b` for a true colour. The arguments are either colon
+/// subparameters of `param` or, in the older form, the parameters after it,
+/// which are consumed so they aren't read as attributes.
+fn extended_color<'a>(
+    param: &[u16],
+    params: &mut impl Iterator<Item = &'a [u16]>,
+) -> Option<Color> {
+    let args = if param.len() > 1 {
+        param[1..].to_vec()
+    } else {
+        let mut args = vec![params.next()?[0]];
+        let count = match args[0] {
+            5 => 1,
+            2 => 3,
+            _ => return None,
+        };
+        for _ in 0..count {
+            args.push(params.next()?[0]);
+        }
+        args
+    };
+    let channel = |value: &u16| (*value).min(255) as u8;
+    match args.as_slice() {
+        [5, index, ..] => Some(Color::Indexed(channel(index))),
+        [2, r, g, b] => Some(Color::Rgb(channel(r), channel(g), channel(b))),
+        // The colon form may put a colour space id before the channels.
+        [2, _, r, g, b, ..] => Some(Color::Rgb(channel(r), channel(g), channel(b))),
+        _ => None,
+    }
+}
+
+fn arg(params: &Params, index: usize, default: u16) -> u16 {
+    match params.iter().nth(index).map(|param| param[0]) {
+        Some(0) | None => default,
+        Some(value) => value,
+    }
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        self.put_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x08 => {
+                self.cursor.column = self.cursor.column.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            0x09 => {
+                let next = (self.cursor.column + 1..self.columns)
+                    .find(|&column| self.tab_stops[column])
+                    .unwrap_or(self.columns - 1);
+                self.cursor.column = next;
+            }
+            0x0a..=0x0c => self.linefeed(),
+            0x0d => self.carriage_return(),
+            0x0e => self.cursor.charset = Charset::LineDrawing,
+            0x0f => self.cursor.charset = Charset::Ascii,
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if let [b"0" | b"2", title, ..] = params {
+            self.title = Some(String::from_utf8_lossy(title).into_owned());
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let private = intermediates.first() == Some(&b'?');
+        let count = arg(params, 0, 1) as usize;
+        let (row, column) = (self.cursor.row, self.cursor.column);
+
+        match (action, intermediates) {
+            ('h', _) | ('l', _) => {
+                let enable = action == 'h';
+                for param in params.iter() {
+                    match (private, param[0]) {
+                        (true, mode) => self.set_private_mode(mode, enable),
+                        (false, 4) => self.modes.insert = enable,
+                        _ => {}
+                    }
+                }
+            }
+            ('@', []) => self.insert_chars(count),
+            // CPL and CNL move like CUU and CUD, stopping at the margins,
+            // then go to the start of the line.
+            ('A', []) | ('F', []) => {
+                let top = if row >= self.scroll_top {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                self.cursor.row = row.saturating_sub(count).max(top);
+                self.wrap_pending = false;
+                if action == 'F' {
+                    self.carriage_return();
+                }
+            }
+            ('B', []) | ('e', []) | ('E', []) => {
+                let bottom = if row <= self.scroll_bottom {
+                    self.scroll_bottom
+                } else {
+                    self.rows - 1
+                };
+                self.cursor.row = (row + count).min(bottom);
+                self.wrap_pending = false;
+                if action == 'E' {
+                    self.carriage_return();
+                }
+            }
+            ('C', []) | ('a', []) => {
+                self.cursor.column = (column + count).min(self.columns - 1);
+                self.wrap_pending = false;
+            }
+            ('D', []) => {
+                self.cursor.column = column.saturating_sub(count);
+                self.wrap_pending = false;
+            }
+            ('G', []) | ('`', []) => {
+                self.cursor.column = (count - 1).min(self.columns - 1);
+                self.wrap_pending = false;
+            }
+            ('H', []) | ('f', []) => {
+                let row = arg(params, 0, 1) as usize - 1;
+                let column = arg(params, 1, 1) as usize - 1;
+                self.goto(row, column);
+            }
+            ('J', _) => self.erase_in_display(arg(params, 0, 0)),
+            ('K', _) => self.erase_in_line(arg(params, 0, 0)),
+            ('L', []) => self.insert_lines(count),
+            ('M', []) => self.delete_lines(count),
+            ('P', []) => self.delete_chars(count),
+            ('S', []) => self.scroll_up(count, true),
+            ('T', []) => self.scroll_down(count),
+            ('X', []) => self.erase_chars(count),
+            ('b', []) => {
+                if let Some(c) = self.last_printed {
+                    for _ in 0..count {
+                        self.put_char(c);
+                    }
+                }
+            }
+            ('c', []) => self.report(String::from("\x1b[?1;2c")),
+            ('c', [b'>']) => self.report(String::from("\x1b[>0;0;0c")),
+            ('d', []) => {
+                let column = self.cursor.column;
+                let origin = if self.cursor.origin_mode {
+                    self.scroll_top
+                } else {
+                    0
+                };
+                self.goto(count - 1, column);
+                self.cursor.row = self.cursor.row.max(origin);
+            }
+            ('g', []) => match arg(params, 0, 0) {
+                0 => self.tab_stops[column] = false,
+                3 => self.tab_stops.fill(false),
+                _ => {}
+            },
+            ('m', []) => self.set_graphic_rendition(params),
+            ('n', []) => match arg(params, 0, 0) {
+                5 => self.report(String::from("\x1b[0n")),
+                6 => {
+                    let row = if self.cursor.origin_mode {
+                        row.saturating_sub(self.scroll_top)
+                    } else {
+                        row
+                    };
+                    self.report(format!("\x1b[{};{}R", row + 1, column + 1));
+                }
+                _ => {}
+            },
+            ('r', []) => {
+                let top = arg(params, 0, 1) as usize;
+                let bottom = arg(params, 1, 0) as usize;
+                self.set_scroll_region(top, bottom);
+            }
+            ('s', []) => self.save_cursor(),
+            ('u', []) => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], ignore: bool, byte: u8) {
+        if ignore {
+            return;
+        }
+        match (intermediates, byte) {
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'D') => self.linefeed(),
+            ([], b'E') => {
+                self.carriage_return();
+                self.linefeed();
+            }
+            ([], b'H') => self.tab_stops[self.cursor.column] = true,
+            ([], b'M') => self.reverse_index(),
+            ([], b'c') => self.reset(),
+            ([b'('], b'0') => self.cursor.charset = Charset::LineDrawing,
+            ([b'('], _) => self.cursor.charset = Charset::Ascii,
+            ([b'#'], b'8') => {
+                // DECALN, the screen alignment test.
+                for line in &mut self.grid {
+                    line.fill(Cell {
+                        c: 'E',
+                        ..Cell::default()
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A line as text, without trailing blanks.
+    fn text(cells: &[Cell]) -> String {
+        let text: String = cells.iter().map(|cell| cell.c).collect();
+        text.trim_end().to_string()
+    }
+
+    fn lines(terminal: &Terminal) -> Vec<String> {
+        (0..terminal.line_count())
+            .map(|line| text(terminal.line(line)))
+            .collect()
+    }
+
+    fn screen(terminal: &Terminal) -> Vec<String> {
+        (0..terminal.rows())
+            .map(|row| text(terminal.row(row)))
+            .collect()
+    }
+
+    fn report(terminal: &mut Terminal, query: &str) -> String {
+        terminal.advance(query.as_bytes());
+        String::from_utf8(terminal.take_response()).unwrap()
+    }
+
+    /// A terminal `rows` high that has printed the lines `1` to `count`.
+    fn printed(rows: usize, limit: usize, count: usize) -> Terminal {
+        let mut terminal = Terminal::new(10, rows);
+        terminal.set_scrollback_limit(limit);
+        let output: Vec<String> = (1..=count).map(|n| n.to_string()).collect();
+        terminal.advance(output.join("\r\n").as_bytes());
+        terminal
+    }
+
+    #[test]
+    fn scrollback_eviction() {
+        let terminal = printed(3, 5, 10);
+        assert_eq!(lines(&terminal), ["3", "4", "5", "6", "7", "8", "9", "10"]);
+        assert_eq!(terminal.scrollback_len(), 5);
+        assert_eq!(terminal.dropped_lines(), 2);
+
+        let mut terminal = printed(3, 5, 4);
+        assert_eq!(lines(&terminal), ["1", "2", "3", "4"]);
+        assert_eq!(terminal.dropped_lines(), 0);
+
+        // Lowering the limit drops the oldest lines.
+        let mut full = printed(3, 5, 10);
+        full.set_scrollback_limit(2);
+        assert_eq!(lines(&full), ["6", "7", "8", "9", "10"]);
+        assert_eq!(full.dropped_lines(), 5);
+
+        // Erasing saved lines counts them as dropped too.
+        terminal.advance(b"\x1b[3J");
+        assert_eq!(lines(&terminal), ["2", "3", "4"]);
+        assert_eq!(terminal.dropped_lines(), 1);
+
+        let terminal = printed(3, 0, 10);
+        assert_eq!(terminal.scrollback_len(), 0);
+        assert_eq!(terminal.dropped_lines(), 0);
+    }
+
+    /// Only whole-screen scrolling on the main screen is kept.
+    #[test]
+    fn scrollback_sources() {
+        let mut terminal = Terminal::new(10, 3);
+        // A scroll region from row 2 down.
+        terminal.advance(b"\x1b[2;3r\x1b[2H1\r\n2\r\n3\r\n4\x1b[r");
+        assert_eq!(terminal.scrollback_len(), 0);
+
+        terminal.advance(b"\x1b[?1049h1\r\n2\r\n3\r\n4\x1b[?1049l");
+        assert_eq!(terminal.scrollback_len(), 0);
+
+        // Deleting lines at the top removes them rather than scrolling them
+        // off, while SU scrolls.
+        terminal.advance(b"\x1b[2J\x1b[Ha\r\nb\x1b[H\x1b[2M");
+        assert_eq!(screen(&terminal), ["", "", ""]);
+        assert_eq!(terminal.scrollback_len(), 0);
+        assert_eq!(terminal.dropped_lines(), 0);
+        terminal.advance(b"c\x1b[S");
+        assert_eq!(lines(&terminal), ["c", "", "", ""]);
+    }
+
+    #[test]
+    fn display_offset() {
+        let mut terminal = printed(3, 100, 10);
+        assert_eq!(terminal.display_offset(), 0);
+        assert_eq!(terminal.visible_line(0), 7);
+
+        terminal.set_display_offset(100);
+        assert_eq!(terminal.display_offset(), 7);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "1");
+
+        // A scrolled-back view stays on the same text as output arrives.
+        terminal.set_display_offset(2);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "6");
+        terminal.advance(b"\r\n11\r\n12");
+        assert_eq!(terminal.display_offset(), 4);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "6");
+
+        // Even as it reaches the top of a full scrollback.
+        let mut terminal = printed(3, 5, 10);
+        terminal.set_display_offset(4);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "4");
+        terminal.advance(b"\r\n11");
+        assert_eq!(terminal.display_offset(), 5);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "4");
+        terminal.advance(b"\r\n12");
+        assert_eq!(terminal.display_offset(), 5);
+        assert_eq!(text(terminal.line(terminal.visible_line(0))), "5");
+
+        // Shrinking or clearing the scrollback pulls the view in.
+        terminal.set_scrollback_limit(2);
+        assert_eq!(terminal.display_offset(), 2);
+        terminal.advance(b"\x1b[3J");
+        assert_eq!(terminal.display_offset(), 0);
+    }
+
+    #[test]
+    fn reveal_line() {
+        let mut terminal = printed(4, 100, 20);
+        // Already on screen: nothing moves.
+        terminal.reveal_line(17);
+        assert_eq!(terminal.display_offset(), 0);
+
+        terminal.reveal_line(5);
+        assert_eq!(terminal.visible_line(2), 5);
+        terminal.reveal_line(6);
+        assert_eq!(terminal.visible_line(2), 5);
+
+        terminal.reveal_line(0);
+        assert_eq!(terminal.display_offset(), 16);
+        assert_eq!(terminal.visible_line(0), 0);
+    }
+
+    #[test]
+    fn resize_rows() {
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"a\r\nb\r\nc\r\nd");
+
+        // Shrinking keeps the cursor's line, pushing the top ones back.
+        terminal.resize(10, 2);
+        assert_eq!(terminal.scrollback_len(), 2);
+        assert_eq!(lines(&terminal), ["a", "b", "c", "d"]);
+        assert_eq!(terminal.cursor(), (1, 1));
+
+        // Growing brings them back before adding blank rows.
+        terminal.resize(10, 6);
+        assert_eq!(terminal.scrollback_len(), 0);
+        assert_eq!(lines(&terminal), ["a", "b", "c", "d", "", ""]);
+        assert_eq!(terminal.cursor(), (3, 1));
+
+        // Rows below the cursor are the first to go.
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"top\r\n\r\nbottom\x1b[H");
+        terminal.resize(10, 2);
+        assert_eq!(terminal.scrollback_len(), 0);
+        assert_eq!(lines(&terminal), ["top", ""]);
+        assert_eq!(terminal.cursor(), (0, 0));
+
+        // Lines coming back pull a scrolled-back view in.
+        let mut terminal = printed(3, 100, 10);
+        terminal.set_display_offset(7);
+        terminal.resize(10, 6);
+        assert_eq!(terminal.scrollback_len(), 4);
+        assert_eq!(terminal.display_offset(), 4);
+    }
+
+    #[test]
+    fn resize_columns() {
+        let mut terminal = Terminal::new(10, 2);
+        terminal.advance("hello\r\nab中".as_bytes());
+        terminal.resize(3, 2);
+        assert_eq!(lines(&terminal), ["hel", "ab"]);
+        assert!(terminal.row(1).iter().all(|cell| !cell.flags.wide_spacer));
+        assert_eq!(terminal.cursor(), (1, 2));
+
+        // Cut text does not come back.
+        terminal.resize(10, 2);
+        assert_eq!(lines(&terminal), ["hel", "ab"]);
+        assert_eq!(terminal.row(1).len(), 10);
+
+        terminal.resize(0, 0);
+        assert_eq!((terminal.columns(), terminal.rows()), (1, 1));
+    }
+
+    /// While a full-screen program runs, the main screen underneath is the
+    /// one whose lines move to the scrollback.
+    #[test]
+    fn resize_alternate_screen() {
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"a\r\nb\r\nc\r\nd\x1b[?1049h\x1b[Hvim");
+        terminal.resize(10, 2);
+        assert_eq!(terminal.scrollback_len(), 2);
+        assert_eq!(text(terminal.row(0)), "vim");
+
+        terminal.advance(b"\x1b[?1049l");
+        assert_eq!(lines(&terminal), ["a", "b", "c", "d"]);
+        assert_eq!(terminal.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn extended_colours() {
+        let mut terminal = Terminal::new(10, 1);
+        terminal.advance(
+            b"\x1b[38;5;196ma\x1b[48;2;1;2;3mb\x1b[38:2::10:20:30;48:5:17mc\x1b[38:2:4:5:6md",
+        );
+        let cells = terminal.row(0);
+        assert_eq!(
+            (cells[0].fg, cells[0].bg),
+            (Color::Indexed(196), Color::Default)
+        );
+        assert_eq!(
+            (cells[1].fg, cells[1].bg),
+            (Color::Indexed(196), Color::Rgb(1, 2, 3))
+        );
+        assert_eq!(
+            (cells[2].fg, cells[2].bg),
+            (Color::Rgb(10, 20, 30), Color::Indexed(17))
+        );
+        assert_eq!(cells[3].fg, Color::Rgb(4, 5, 6));
+
+        // Arguments of the semicolon form are not read as attributes, and
+        // out of range channels are clamped.
+        let mut terminal = Terminal::new(10, 1);
+        terminal.advance(b"\x1b[38;5;1;4ma\x1b[38;2;300;3;1mb\x1b[0;38;7;1mc");
+        let cells = terminal.row(0);
+        assert_eq!(cells[0].fg, Color::Indexed(1));
+        assert_eq!(cells[0].flags.underline, Underline::Single);
+        assert!(!cells[0].flags.bold);
+        assert_eq!(cells[1].fg, Color::Rgb(255, 3, 1));
+        assert!(!cells[1].flags.dim);
+        // An unknown colour kind is ignored.
+        assert_eq!(cells[2].fg, Color::Default);
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let mut terminal = Terminal::new(10, 5);
+        let mut moves = |sequence: &str| {
+            terminal.advance(sequence.as_bytes());
+            terminal.cursor()
+        };
+        assert_eq!(moves("\x1b[3;4H"), (2, 3));
+        assert_eq!(moves("\x1b[2A"), (0, 3));
+        assert_eq!(moves("\x1b[A"), (0, 3));
+        assert_eq!(moves("\x1b[10B"), (4, 3));
+        assert_eq!(moves("\x1b[99;99H"), (4, 9));
+        assert_eq!(moves("\x1b[2D"), (4, 7));
+        assert_eq!(moves("\x1b[5C"), (4, 9));
+        assert_eq!(moves("\x1b[2F"), (2, 0));
+        assert_eq!(moves("\x1b[E"), (3, 0));
+        assert_eq!(moves("\x1b[5G"), (3, 4));
+        assert_eq!(moves("\x1b[2d"), (1, 4));
+        assert_eq!(moves("\x1b[H"), (0, 0));
+        assert_eq!(report(&mut terminal, "\x1b[3;7H\x1b[6n"), "\x1b[3;7R");
+        assert_eq!(report(&mut terminal, "\x1b[5n"), "\x1b[0n");
+    }
+
+    #[test]
+    fn scroll_region_movement() {
+        let mut terminal = Terminal::new(10, 12);
+        terminal.advance(b"\x1b[5;10r");
+        assert_eq!(terminal.cursor(), (0, 0));
+        // Moves stop at the margins from inside the region...
+        terminal.advance(b"\x1b[7H\x1b[9A");
+        assert_eq!(terminal.cursor(), (4, 0));
+        terminal.advance(b"\x1b[9E");
+        assert_eq!(terminal.cursor(), (9, 0));
+        terminal.advance(b"\x1b[9F");
+        assert_eq!(terminal.cursor(), (4, 0));
+        // ...and from outside it, on the side away from the region, only
+        // at the edges of the screen.
+        terminal.advance(b"\x1b[2H\x1b[9F");
+        assert_eq!(terminal.cursor(), (0, 0));
+        terminal.advance(b"\x1b[12H\x1b[9B");
+        assert_eq!(terminal.cursor(), (11, 0));
+        terminal.advance(b"\x1b[12H\x1b[9A");
+        assert_eq!(terminal.cursor(), (4, 0));
+
+        // In origin mode, positions count from the top margin.
+        terminal.advance(b"\x1b[?6h");
+        assert_eq!(terminal.cursor(), (4, 0));
+        assert_eq!(report(&mut terminal, "\x1b[10F\x1b[6n"), "\x1b[1;1R");
+        assert_eq!(report(&mut terminal, "\x1b[99;3H\x1b[6n"), "\x1b[6;3R");
+        assert_eq!(terminal.cursor(), (9, 2));
+    }
+
+    #[test]
+    fn graphic_rendition() {
+        let mut terminal = Terminal::new(10, 1);
+        terminal.advance(b"\x1b[1;3;4;7;9;31;42mA\x1b[22;23;24;27;29;39;49mB");
+        terminal.advance(b"\x1b[2;4:3;91;103mC\x1b[mD\x1b[21mE\x1b[4:0mF");
+        let cells = terminal.row(0);
+        let flags = Flags {
+            bold: true,
+            italic: true,
+            underline: Underline::Single,
+            inverse: true,
+            strikethrough: true,
+            ..Flags::default()
+        };
+        assert_eq!(cells[0].flags, flags);
+        assert_eq!(
+            (cells[0].fg, cells[0].bg),
+            (Color::Indexed(1), Color::Indexed(2))
+        );
+        assert_eq!(
+            cells[1],
+            Cell {
+                c: 'B',
+                ..Cell::default()
+            }
+        );
+        assert!(cells[2].flags.dim);
+        assert_eq!(cells[2].flags.underline, Underline::Curly);
+        assert_eq!(
+            (cells[2].fg, cells[2].bg),
+            (Color::Indexed(9), Color::Indexed(11))
+        );
+        assert_eq!(
+            cells[3],
+            Cell {
+                c: 'D',
+                ..Cell::default()
+            }
+        );
+        assert_eq!(cells[4].flags.underline, Underline::Double);
+        assert_eq!(cells[5].flags.underline, Underline::None);
+    }
+
+    #[test]
+    fn scroll_regions() {
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"top\x1b[4Hbottom\x1b[2;3r\x1b[2Ha\r\nb\r\nc");
+        assert_eq!(screen(&terminal), ["top", "b", "c", "bottom"]);
+        assert_eq!(terminal.scrollback_len(), 0);
+
+        // Reverse index at the top margin scrolls the region down.
+        terminal.advance(b"\x1b[2H\x1bMz");
+        assert_eq!(screen(&terminal), ["top", "z", "b", "bottom"]);
+
+        // A region that is too small is ignored, leaving the old one.
+        terminal.advance(b"\x1b[3;3r\x1b[3H\n");
+        assert_eq!(screen(&terminal), ["top", "b", "", "bottom"]);
+    }
+
+    #[test]
+    fn insert_and_delete_lines() {
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"a\r\nb\r\nc\r\nd\x1b[2;2H\x1b[L");
+        assert_eq!(screen(&terminal), ["a", "", "b", "c"]);
+        assert_eq!(terminal.cursor(), (1, 0));
+        terminal.advance(b"\x1b[2M");
+        assert_eq!(screen(&terminal), ["a", "c", "", ""]);
+
+        // Only inside the scroll region, and only down to its bottom.
+        terminal.advance(b"\x1b[1;2r\x1b[3H\x1b[L\x1b[1H\x1b[9L");
+        assert_eq!(screen(&terminal), ["", "", "", ""]);
+        let mut terminal = Terminal::new(10, 4);
+        terminal.advance(b"a\r\nb\r\nc\r\nd\x1b[1;2r\x1b[4H\x1b[M\x1b[1H\x1b[L");
+        assert_eq!(screen(&terminal), ["", "a", "c", "d"]);
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut terminal = Terminal::new(5, 3);
+        terminal.advance(b"abcde");
+        // The wrap waits for the next character.
+        assert_eq!(terminal.cursor(), (0, 4));
+        terminal.advance(b"fg");
+        assert_eq!(screen(&terminal), ["abcde", "fg", ""]);
+
+        terminal.advance(b"\x1b[Habcde\rX");
+        assert_eq!(screen(&terminal), ["Xbcde", "fg", ""]);
+
+        // A wide character that doesn't fit goes to the next line whole.
+        terminal.advance("\x1b[3Habcd中".as_bytes());
+        assert_eq!(screen(&terminal), ["fg", "abcd", "中"]);
+        assert!(terminal.row(2)[1].flags.wide_spacer);
+
+        let mut terminal = Terminal::new(5, 2);
+        terminal.advance(b"\x1b[?7labcdefg");
+        assert_eq!(screen(&terminal), ["abcdg", ""]);
+    }
+
+    #[test]
+    fn alternate_screen() {
+        let mut terminal = Terminal::new(10, 3);
+        terminal.advance(b"main\x1b[?1049h");
+        assert!(terminal.alternate_screen());
+        assert_eq!(screen(&terminal), ["", "", ""]);
+        assert_eq!(terminal.cursor(), (0, 4));
+
+        terminal.advance(b"\x1b[3Halt\x1b[?1049l");
+        assert!(!terminal.alternate_screen());
+        assert_eq!(screen(&terminal), ["main", "", ""]);
+        assert_eq!(terminal.cursor(), (0, 4));
+
+        // 47 switches screens without saving the cursor.
+        terminal.advance(b"\x1b[?47h\x1b[2Hx\x1b[?47l");
+        assert_eq!(screen(&terminal), ["main", "", ""]);
+        assert_eq!(terminal.cursor(), (1, 1));
+    }
+}
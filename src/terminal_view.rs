@@ -0,0 +1,854 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use iced_native::alignment;
+use iced_native::event::{self, Event};
+use iced_native::keyboard::{self, KeyCode, Modifiers};
+use iced_native::layout::{self, Layout};
+use iced_native::mouse;
+use iced_native::renderer;
+use iced_native::text;
+use iced_native::widget::tree::{self, Tree};
+use iced_native::{Clipboard, Color, Element, Font, Length, Point, Rectangle, Shell, Size, Widget};
+
+use crate::clipboard;
+use crate::keybindings::{Action, Chord, Keybindings};
+use crate::palette::{Palette, Rgb};
+use crate::selection::{self, Selection, SelectionMode};
+use crate::terminal::{self, Cell, Terminal, Underline};
+
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+pub const MIN_FONT_SIZE: f32 = 6.0;
+pub const MAX_FONT_SIZE: f32 = 48.0;
+const SCROLLBAR_WIDTH: f32 = 8.0;
+const MIN_THUMB_HEIGHT: f32 = 16.0;
+/// Room for a double-width character.
+const MIN_COLUMNS: usize = 2;
+/// Lines scrolled per notch of the mouse wheel.
+const WHEEL_LINES: f32 = 3.0;
+/// Clicks on the same cell closer together than this select a word, then
+/// a line.
+const MULTI_CLICK: Duration = Duration::from_millis(500);
+
+const SCROLLBAR: Color = Color::from_rgb(0.35, 0.35, 0.35);
+const MATCH: Color = Color::from_rgb(0.45, 0.4, 0.12);
+const CURRENT_MATCH: Color = Color::from_rgb(0.95, 0.6, 0.1);
+/// For schemes that don't choose a selection colour.
+const SELECTION: Color = Color::from_rgb(0.22, 0.33, 0.5);
+/// How far dim text is blended towards the background.
+const DIM: f32 = 0.4;
+
+/// Cells are drawn in DejaVu Sans Mono, which unlike the default font is
+/// monospaced and has bold and italic faces.
+const REGULAR: Font = Font::External {
+    name: "DejaVu Sans Mono",
+    bytes: include_bytes!("../fonts/DejaVuSansMono.ttf"),
+};
+const BOLD: Font = Font::External {
+    name: "DejaVu Sans Mono Bold",
+    bytes: include_bytes!("../fonts/DejaVuSansMono-Bold.ttf"),
+};
+const ITALIC: Font = Font::External {
+    name: "DejaVu Sans Mono Oblique",
+    bytes: include_bytes!("../fonts/DejaVuSansMono-Oblique.ttf"),
+};
+const BOLD_ITALIC: Font = Font::External {
+    name: "DejaVu Sans Mono Bold Oblique",
+    bytes: include_bytes!("../fonts/DejaVuSansMono-BoldOblique.ttf"),
+};
+
+/// Cells to mark on one row of the view, e.g. search matches.
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub row: usize,
+    pub columns: Range<usize>,
+    pub current: bool,
+}
+
+/// Draws a [`Terminal`] and turns keystrokes into the bytes a terminal
+/// would send.
+pub struct TerminalView<'a, Message> {
+    terminal: &'a Terminal,
+    on_input: Box<dyn Fn(Vec<u8>) -> Message + 'a>,
+    on_scroll: Option<Box<dyn Fn(usize) -> Message + 'a>>,
+    on_resize: Option<Box<dyn Fn(usize, usize) -> Message + 'a>>,
+    on_focus: Option<Box<dyn Fn() -> Message + 'a>>,
+    active: bool,
+    highlights: Vec<Highlight>,
+    palette: Cow<'a, Palette>,
+    keybindings: Cow<'a, Keybindings>,
+    size: f32,
+}
+
+#[derive(Debug)]
+struct State {
+    focused: bool,
+    modifiers: Modifiers,
+    /// Where on the scrollbar thumb it was grabbed, while it is dragged.
+    thumb_grab: Option<f32>,
+    selection: Option<Selection>,
+    /// Whether the left button is down and extending `selection`.
+    selecting: bool,
+    last_click: Option<(Instant, selection::Point)>,
+    clicks: u8,
+    /// Whether the character typed by a shortcut's key press is still to
+    /// come and must not reach the session.
+    swallow_character: bool,
+    was_active: bool,
+}
+
+impl<'a, Message> TerminalView<'a, Message> {
+    pub fn new(terminal: &'a Terminal, on_input: impl Fn(Vec<u8>) -> Message + 'a) -> Self {
+        TerminalView {
+            terminal,
+            on_input: Box::new(on_input),
+            on_scroll: None,
+            on_resize: None,
+            on_focus: None,
+            active: true,
+            highlights: Vec::new(),
+            palette: Cow::Owned(Palette::default()),
+            keybindings: Cow::Owned(Keybindings::default()),
+            size: DEFAULT_FONT_SIZE,
+        }
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Lets the view be scrolled back through the scrollback; `on_scroll`
+    /// gets the new display offset.
+    pub fn on_scroll(mut self, on_scroll: impl Fn(usize) -> Message + 'a) -> Self {
+        self.on_scroll = Some(Box::new(on_scroll));
+        self
+    }
+
+    /// Asks for the terminal to be resized to fit the view; `on_resize`
+    /// gets the columns and rows that fit.
+    pub fn on_resize(mut self, on_resize: impl Fn(usize, usize) -> Message + 'a) -> Self {
+        self.on_resize = Some(Box::new(on_resize));
+        self
+    }
+
+    /// Whether this is the pane keys go to, when there are several.
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Asks for an inactive pane to be made the active one when clicked.
+    pub fn on_focus(mut self, on_focus: impl Fn() -> Message + 'a) -> Self {
+        self.on_focus = Some(Box::new(on_focus));
+        self
+    }
+
+    pub fn highlights(mut self, highlights: Vec<Highlight>) -> Self {
+        self.highlights = highlights;
+        self
+    }
+
+    pub fn palette(mut self, palette: &'a Palette) -> Self {
+        self.palette = Cow::Borrowed(palette);
+        self
+    }
+
+    /// Copy and paste are done here; other bound chords are left for the
+    /// application instead of going to the session.
+    pub fn keybindings(mut self, keybindings: &'a Keybindings) -> Self {
+        self.keybindings = Cow::Borrowed(keybindings);
+        self
+    }
+
+    /// The scrollbar's track and thumb, if there is anything to scroll.
+    fn scrollbar(&self, bounds: Rectangle) -> Option<(Rectangle, Rectangle)> {
+        let history = self.terminal.scrollback_len();
+        if history == 0 || self.on_scroll.is_none() || self.terminal.alternate_screen() {
+            return None;
+        }
+        let track = Rectangle {
+            x: bounds.x + bounds.width - SCROLLBAR_WIDTH,
+            width: SCROLLBAR_WIDTH,
+            ..bounds
+        };
+        let visible = self.terminal.rows() as f32 / self.terminal.line_count() as f32;
+        let height = (track.height * visible)
+            .max(MIN_THUMB_HEIGHT)
+            .min(track.height);
+        let first = self.terminal.visible_line(0) as f32 / history as f32;
+        let thumb = Rectangle {
+            y: track.y + (track.height - height) * first,
+            height,
+            ..track
+        };
+        Some((track, thumb))
+    }
+
+    /// The display offset that puts the top of the thumb at `y`.
+    fn offset_for_thumb(&self, track: Rectangle, thumb: Rectangle, y: f32) -> usize {
+        let history = self.terminal.scrollback_len();
+        let available = (track.height - thumb.height).max(1.0);
+        let first = ((y - track.y) / available).clamp(0.0, 1.0) * history as f32;
+        history - first.round() as usize
+    }
+
+    fn cell_size<Renderer: text::Renderer<Font = Font>>(&self, renderer: &Renderer) -> (f32, f32) {
+        renderer.measure("M", self.size, REGULAR, Size::INFINITY)
+    }
+
+    /// The columns and rows that fit in `bounds` beside the scrollbar, or
+    /// `None` while the view is too small to hold a cell, e.g. hidden.
+    fn grid_size<Renderer: text::Renderer<Font = Font>>(
+        &self,
+        renderer: &Renderer,
+        bounds: Rectangle,
+    ) -> Option<(usize, usize)> {
+        let (cell_width, cell_height) = self.cell_size(renderer);
+        let scrollbar = if self.on_scroll.is_some() {
+            SCROLLBAR_WIDTH
+        } else {
+            0.0
+        };
+        let columns = ((bounds.width - scrollbar) / cell_width).floor();
+        let rows = (bounds.height / cell_height).floor();
+        if columns < 1.0 || rows < 1.0 {
+            return None;
+        }
+        Some(((columns as usize).max(MIN_COLUMNS), rows as usize))
+    }
+
+    /// The cell under `position`, clamped to the grid.
+    fn point_at<Renderer: text::Renderer<Font = Font>>(
+        &self,
+        renderer: &Renderer,
+        bounds: Rectangle,
+        position: Point,
+    ) -> selection::Point {
+        let (cell_width, cell_height) = self.cell_size(renderer);
+        let row = ((position.y - bounds.y) / cell_height).max(0.0) as usize;
+        let column = ((position.x - bounds.x) / cell_width).max(0.0) as usize;
+        let row = row.min(self.terminal.rows() - 1);
+        selection::Point {
+            line: self.terminal.dropped_lines() + self.terminal.visible_line(row),
+            column: column.min(self.terminal.columns() - 1),
+        }
+    }
+
+    fn scroll_by(&self, lines: f32) -> usize {
+        let offset = self.terminal.display_offset() as f32 + lines.round();
+        offset.clamp(0.0, self.terminal.scrollback_len() as f32) as usize
+    }
+
+    fn color(&self, color: terminal::Color, bold: bool, default: Rgb) -> Color {
+        let rgb = match color {
+            terminal::Color::Default => default,
+            terminal::Color::Indexed(index) => {
+                // Bold brightens the eight basic colours, as xterm does.
+                let index = if bold && index < 8 { index + 8 } else { index };
+                self.palette.indexed(index)
+            }
+            terminal::Color::Rgb(r, g, b) => Rgb(r, g, b),
+        };
+        to_color(rgb)
+    }
+
+    fn cell_colors(&self, cell: &Cell) -> (Color, Color) {
+        let mut fg = self.color(cell.fg, cell.flags.bold, self.palette.foreground);
+        let bg = self.color(cell.bg, false, self.palette.background);
+        if cell.flags.dim {
+            fg = blend(fg, bg, DIM);
+        }
+        if cell.flags.inverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
+    }
+}
+
+fn to_color(Rgb(r, g, b): Rgb) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+/// `from` moved `amount` of the way to `to`.
+fn blend(from: Color, to: Color, amount: f32) -> Color {
+    let mix = |from: f32, to: f32| from + (to - from) * amount;
+    Color::from_rgb(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+/// The one-pixel lines an underline of `style` is drawn with, under a cell
+/// `width` wide whose bottom edge is at `bottom`. Dotted, dashed and curly
+/// lines are patterned from `left`, the edge of the view, so they run on
+/// from one cell to the next.
+fn underline(style: Underline, left: f32, x: f32, bottom: f32, width: f32) -> Vec<Rectangle> {
+    let line = |x: f32, y: f32, width: f32| Rectangle {
+        x,
+        y,
+        width,
+        height: 1.0,
+    };
+    let (step, curly) = match style {
+        Underline::None => return Vec::new(),
+        Underline::Single => return vec![line(x, bottom - 1.0, width)],
+        Underline::Double => {
+            return vec![line(x, bottom - 1.0, width), line(x, bottom - 3.0, width)]
+        }
+        Underline::Dotted => (1, false),
+        Underline::Dashed => (3, false),
+        Underline::Curly => (2, true),
+    };
+    let start = (x - left).round() as i32;
+    let end = (x + width - left).round() as i32;
+    (start..end)
+        .filter_map(|pixel| {
+            let x = left + pixel as f32;
+            let even = pixel / step % 2 == 0;
+            match (curly, even) {
+                (_, true) => Some(line(x, bottom - 1.0, 1.0)),
+                (true, false) => Some(line(x, bottom - 2.0, 1.0)),
+                (false, false) => None,
+            }
+        })
+        .collect()
+}
+
+fn letter(key_code: KeyCode) -> Option<u8> {
+    let letter = match key_code {
+        KeyCode::A => b'a',
+        KeyCode::B => b'b',
+        KeyCode::C => b'c',
+        KeyCode::D => b'd',
+        KeyCode::E => b'e',
+        KeyCode::F => b'f',
+        KeyCode::G => b'g',
+        KeyCode::H => b'h',
+        KeyCode::I => b'i',
+        KeyCode::J => b'j',
+        KeyCode::K => b'k',
+        KeyCode::L => b'l',
+        KeyCode::M => b'm',
+        KeyCode::N => b'n',
+        KeyCode::O => b'o',
+        KeyCode::P => b'p',
+        KeyCode::Q => b'q',
+        KeyCode::R => b'r',
+        KeyCode::S => b's',
+        KeyCode::T => b't',
+        KeyCode::U => b'u',
+        KeyCode::V => b'v',
+        KeyCode::W => b'w',
+        KeyCode::X => b'x',
+        KeyCode::Y => b'y',
+        KeyCode::Z => b'z',
+        _ => return None,
+    };
+    Some(letter)
+}
+
+/// The xterm modifier parameter: 1 plus shift, alt and control bits.
+fn modifier_param(modifiers: Modifiers) -> u8 {
+    1 + modifiers.shift() as u8 + 2 * modifiers.alt() as u8 + 4 * modifiers.control() as u8
+}
+
+/// Cursor-style keys: `ESC [ X` (or `ESC O X` in application mode), and
+/// `ESC [ 1 ; m X` when modified.
+fn cursor_key(final_byte: char, modifiers: Modifiers, application: bool) -> Vec<u8> {
+    let param = modifier_param(modifiers);
+    if param > 1 {
+        format!("\x1b[1;{}{}", param, final_byte).into_bytes()
+    } else if application {
+        format!("\x1bO{}", final_byte).into_bytes()
+    } else {
+        format!("\x1b[{}", final_byte).into_bytes()
+    }
+}
+
+/// Editing and function keys: `ESC [ n ~`, or `ESC [ n ; m ~` when modified.
+fn tilde_key(number: u8, modifiers: Modifiers) -> Vec<u8> {
+    let param = modifier_param(modifiers);
+    if param > 1 {
+        format!("\x1b[{};{}~", number, param).into_bytes()
+    } else {
+        format!("\x1b[{}~", number).into_bytes()
+    }
+}
+
+/// Bytes for keys that don't produce a character of their own. Printable
+/// input arrives separately as `CharacterReceived`.
+fn encode_key(
+    key_code: KeyCode,
+    modifiers: Modifiers,
+    application_cursor: bool,
+) -> Option<Vec<u8>> {
+    let alt_prefixed = |bytes: &[u8]| {
+        let mut encoded = Vec::with_capacity(bytes.len() + 1);
+        if modifiers.alt() {
+            encoded.push(0x1b);
+        }
+        encoded.extend_from_slice(bytes);
+        encoded
+    };
+
+    let bytes = match key_code {
+        KeyCode::Enter | KeyCode::NumpadEnter => alt_prefixed(b"\r"),
+        KeyCode::Backspace if modifiers.control() => alt_prefixed(b"\x08"),
+        KeyCode::Backspace => alt_prefixed(b"\x7f"),
+        KeyCode::Tab if modifiers.shift() => b"\x1b[Z".to_vec(),
+        KeyCode::Tab => alt_prefixed(b"\t"),
+        KeyCode::Escape => alt_prefixed(b"\x1b"),
+        KeyCode::Up => cursor_key('A', modifiers, application_cursor),
+        KeyCode::Down => cursor_key('B', modifiers, application_cursor),
+        KeyCode::Right => cursor_key('C', modifiers, application_cursor),
+        KeyCode::Left => cursor_key('D', modifiers, application_cursor),
+        KeyCode::Home => cursor_key('H', modifiers, application_cursor),
+        KeyCode::End => cursor_key('F', modifiers, application_cursor),
+        KeyCode::Insert => tilde_key(2, modifiers),
+        KeyCode::Delete => tilde_key(3, modifiers),
+        KeyCode::PageUp => tilde_key(5, modifiers),
+        KeyCode::PageDown => tilde_key(6, modifiers),
+        // F1-F4 are SS3 keys, so they never use the `ESC [` form unmodified.
+        KeyCode::F1 => cursor_key('P', modifiers, true),
+        KeyCode::F2 => cursor_key('Q', modifiers, true),
+        KeyCode::F3 => cursor_key('R', modifiers, true),
+        KeyCode::F4 => cursor_key('S', modifiers, true),
+        KeyCode::F5 => tilde_key(15, modifiers),
+        KeyCode::F6 => tilde_key(17, modifiers),
+        KeyCode::F7 => tilde_key(18, modifiers),
+        KeyCode::F8 => tilde_key(19, modifiers),
+        KeyCode::F9 => tilde_key(20, modifiers),
+        KeyCode::F10 => tilde_key(21, modifiers),
+        KeyCode::F11 => tilde_key(23, modifiers),
+        KeyCode::F12 => tilde_key(24, modifiers),
+        _ if modifiers.control() => {
+            let control = match key_code {
+                KeyCode::Space | KeyCode::Key2 => 0x00,
+                KeyCode::LBracket | KeyCode::Key3 => 0x1b,
+                KeyCode::Backslash | KeyCode::Key4 => 0x1c,
+                KeyCode::RBracket | KeyCode::Key5 => 0x1d,
+                KeyCode::Key6 => 0x1e,
+                KeyCode::Minus | KeyCode::Key7 => 0x1f,
+                KeyCode::Key8 => 0x7f,
+                key_code => letter(key_code)? & 0x1f,
+            };
+            alt_prefixed(&[control])
+        }
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for TerminalView<'a, Message>
+where
+    Renderer: text::Renderer<Font = Font>,
+{
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State {
+            focused: true,
+            modifiers: Modifiers::default(),
+            thumb_grab: None,
+            selection: None,
+            selecting: false,
+            last_click: None,
+            clicks: 0,
+            swallow_character: false,
+            was_active: true,
+        })
+    }
+
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(Length::Fill).height(Length::Fill);
+        layout::Node::new(limits.resolve(Size::ZERO))
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+
+        // Any event may come after the window or a split was resized, and
+        // the layout is the only place the new size is known.
+        if let Some(on_resize) = &self.on_resize {
+            if let Some((columns, rows)) = self.grid_size(renderer, bounds) {
+                if (columns, rows) != (self.terminal.columns(), self.terminal.rows()) {
+                    shell.publish(on_resize(columns, rows));
+                }
+            }
+        }
+
+        // Becoming the active pane, e.g. from the keyboard, takes the keys
+        // back from whichever widget had them.
+        if self.active && !state.was_active {
+            state.focused = true;
+        }
+        state.was_active = self.active;
+
+        if let Some(on_scroll) = &self.on_scroll {
+            let scrollbar = self.scrollbar(bounds);
+            let offset = match event {
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                    match scrollbar {
+                        Some((track, thumb)) if track.contains(cursor_position) => {
+                            // Clicking the track jumps there with the thumb
+                            // centred on the pointer.
+                            let grab = if thumb.contains(cursor_position) {
+                                cursor_position.y - thumb.y
+                            } else {
+                                thumb.height / 2.0
+                            };
+                            state.thumb_grab = Some(grab);
+                            Some(self.offset_for_thumb(track, thumb, cursor_position.y - grab))
+                        }
+                        _ => None,
+                    }
+                }
+                Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                    match (scrollbar, state.thumb_grab) {
+                        (Some((track, thumb)), Some(grab)) => {
+                            Some(self.offset_for_thumb(track, thumb, position.y - grab))
+                        }
+                        _ => None,
+                    }
+                }
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                    if state.thumb_grab.is_some() =>
+                {
+                    state.thumb_grab = None;
+                    return event::Status::Captured;
+                }
+                Event::Mouse(mouse::Event::WheelScrolled { delta })
+                    if bounds.contains(cursor_position) && scrollbar.is_some() =>
+                {
+                    let lines = match delta {
+                        mouse::ScrollDelta::Lines { y, .. } => y * WHEEL_LINES,
+                        mouse::ScrollDelta::Pixels { y, .. } => y / self.cell_size(renderer).1,
+                    };
+                    Some(self.scroll_by(lines))
+                }
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: key_code @ (KeyCode::PageUp | KeyCode::PageDown),
+                    modifiers,
+                }) if state.focused && self.active && modifiers.shift() && scrollbar.is_some() => {
+                    let page = self.terminal.rows() as f32 - 1.0;
+                    Some(self.scroll_by(if key_code == KeyCode::PageUp {
+                        page
+                    } else {
+                        -page
+                    }))
+                }
+                _ => None,
+            };
+            if let Some(offset) = offset {
+                if offset != self.terminal.display_offset() {
+                    shell.publish(on_scroll(offset));
+                }
+                return event::Status::Captured;
+            }
+        }
+
+        let bytes = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(button)) => {
+                state.focused = bounds.contains(cursor_position);
+                if !state.focused {
+                    return event::Status::Ignored;
+                }
+                if let (false, Some(on_focus)) = (self.active, &self.on_focus) {
+                    shell.publish(on_focus());
+                }
+                match button {
+                    mouse::Button::Left => {
+                        let point = self.point_at(renderer, bounds, cursor_position);
+                        state.clicks = match state.last_click {
+                            Some((at, last)) if last == point && at.elapsed() < MULTI_CLICK => {
+                                state.clicks % 3 + 1
+                            }
+                            _ => 1,
+                        };
+                        state.last_click = Some((Instant::now(), point));
+                        let mode = match state.clicks {
+                            2 => SelectionMode::Word,
+                            3 => SelectionMode::Line,
+                            _ if state.modifiers.alt() => SelectionMode::Block,
+                            _ => SelectionMode::Simple,
+                        };
+                        state.selection = Some(Selection::new(mode, point));
+                        state.selecting = true;
+                        return event::Status::Captured;
+                    }
+                    mouse::Button::Middle => match clipboard::primary() {
+                        Some(text) => self.terminal.paste_input(&text),
+                        None => return event::Status::Captured,
+                    },
+                    _ => return event::Status::Ignored,
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) if state.selecting => {
+                let point = self.point_at(renderer, bounds, position);
+                if let Some(selection) = &mut state.selection {
+                    selection.extend(point);
+                }
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if state.selecting => {
+                state.selecting = false;
+                match state.selection {
+                    Some(selection) if selection.is_empty() => state.selection = None,
+                    Some(selection) => clipboard::set_primary(selection.text(self.terminal)),
+                    None => {}
+                }
+                return event::Status::Captured;
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+                return event::Status::Ignored;
+            }
+            _ if !state.focused || !self.active => return event::Status::Ignored,
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            }) => {
+                let action = Chord::new(key_code, modifiers)
+                    .and_then(|chord| self.keybindings.action(chord));
+                state.swallow_character = action.is_some();
+                // Ctrl+Insert and Shift+Insert also work, as in xterm.
+                let insert = key_code == KeyCode::Insert;
+                let copy = action == Some(Action::Copy)
+                    || insert && modifiers.control() && !modifiers.shift();
+                let paste = action == Some(Action::Paste)
+                    || insert && modifiers.shift() && !modifiers.control();
+                if action.is_some() && !copy && !paste {
+                    return event::Status::Ignored;
+                }
+                if copy {
+                    if let Some(selection) = &state.selection {
+                        clipboard.write(selection.text(self.terminal));
+                    }
+                    return event::Status::Captured;
+                }
+                if paste {
+                    match clipboard.read() {
+                        Some(text) => self.terminal.paste_input(&text),
+                        None => return event::Status::Captured,
+                    }
+                } else {
+                    let application_cursor = self.terminal.modes().application_cursor;
+                    match encode_key(key_code, modifiers, application_cursor) {
+                        Some(bytes) => bytes,
+                        None => return event::Status::Ignored,
+                    }
+                }
+            }
+            // Control characters were already handled as key presses.
+            Event::Keyboard(keyboard::Event::CharacterReceived(c)) if !c.is_control() => {
+                if std::mem::take(&mut state.swallow_character) {
+                    return event::Status::Ignored;
+                }
+                if state.modifiers.control() && !state.modifiers.alt() {
+                    return event::Status::Ignored;
+                }
+                let mut bytes = Vec::new();
+                if state.modifiers.alt() {
+                    bytes.push(0x1b);
+                }
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                bytes
+            }
+            _ => return event::Status::Ignored,
+        };
+
+        if !bytes.is_empty() {
+            shell.publish((self.on_input)(bytes));
+        }
+        event::Status::Captured
+    }
+
+    fn mouse_interaction(
+        &self,
+        _tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let bounds = layout.bounds();
+        match self.scrollbar(bounds) {
+            Some((track, _)) if track.contains(cursor_position) => mouse::Interaction::Idle,
+            _ if bounds.contains(cursor_position) => mouse::Interaction::Text,
+            _ => mouse::Interaction::default(),
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Renderer::Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+        let focused = state.focused && self.active;
+        let bounds = layout.bounds();
+        let (cell_width, cell_height) = self.cell_size(renderer);
+
+        let quad = |x: f32, y: f32, width: f32, height: f32| renderer::Quad {
+            bounds: Rectangle {
+                x,
+                y,
+                width,
+                height,
+            },
+            border_radius: 0.0.into(),
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        };
+
+        let background = to_color(self.palette.background);
+        let selection_color = self.palette.selection.map_or(SELECTION, to_color);
+        renderer.fill_quad(
+            quad(bounds.x, bounds.y, bounds.width, bounds.height),
+            background,
+        );
+
+        let (cursor_row, cursor_column) = self.terminal.cursor();
+        // The cursor moves down the view, and eventually off it, as the
+        // view scrolls back.
+        let cursor_row = cursor_row + self.terminal.display_offset();
+        let show_cursor = self.terminal.modes().cursor_visible;
+
+        for row in 0..self.terminal.rows() {
+            let y = bounds.y + row as f32 * cell_height;
+            if y >= bounds.y + bounds.height {
+                break;
+            }
+            let line = self.terminal.visible_line(row);
+            let cells = self.terminal.line(line);
+            // Lines from before a resize may be wider than the screen.
+            let cells = &cells[..cells.len().min(self.terminal.columns())];
+            for (column, cell) in cells.iter().enumerate() {
+                if cell.flags.wide_spacer {
+                    continue;
+                }
+                let x = bounds.x + column as f32 * cell_width;
+                let is_cursor = show_cursor && row == cursor_row && column == cursor_column;
+                let (mut fg, mut bg) = self.cell_colors(cell);
+                if is_cursor && focused {
+                    match self.palette.cursor {
+                        Some(cursor) => (fg, bg) = (background, to_color(cursor)),
+                        None => std::mem::swap(&mut fg, &mut bg),
+                    }
+                }
+                let highlight = self
+                    .highlights
+                    .iter()
+                    .find(|highlight| highlight.row == row && highlight.columns.contains(&column));
+                match highlight {
+                    Some(highlight) if highlight.current => {
+                        bg = CURRENT_MATCH;
+                        fg = background;
+                    }
+                    Some(_) => bg = MATCH,
+                    None => {}
+                }
+                let point = selection::Point {
+                    line: self.terminal.dropped_lines() + line,
+                    column,
+                };
+                if let Some(selection) = &state.selection {
+                    if selection.contains(self.terminal, point) {
+                        bg = selection_color;
+                    }
+                }
+
+                let wide = cells
+                    .get(column + 1)
+                    .map_or(false, |next| next.flags.wide_spacer);
+                let width = if wide { cell_width * 2.0 } else { cell_width };
+                if bg != background {
+                    renderer.fill_quad(quad(x, y, width, cell_height), bg);
+                }
+                if is_cursor && !focused {
+                    renderer.fill_quad(
+                        renderer::Quad {
+                            border_width: 1.0,
+                            border_color: self.palette.cursor.map_or(fg, to_color),
+                            ..quad(x, y, width, cell_height)
+                        },
+                        Color::TRANSPARENT,
+                    );
+                }
+                for line in underline(cell.flags.underline, bounds.x, x, y + cell_height, width) {
+                    renderer.fill_quad(quad(line.x, line.y, line.width, line.height), fg);
+                }
+                if cell.flags.strikethrough {
+                    renderer.fill_quad(quad(x, y + (cell_height / 2.0).round(), width, 1.0), fg);
+                }
+                if cell.c != ' ' {
+                    let font = match (cell.flags.bold, cell.flags.italic) {
+                        (false, false) => REGULAR,
+                        (true, false) => BOLD,
+                        (false, true) => ITALIC,
+                        (true, true) => BOLD_ITALIC,
+                    };
+                    renderer.fill_text(text::Text {
+                        content: cell.c.encode_utf8(&mut [0; 4]),
+                        bounds: Rectangle {
+                            x,
+                            y,
+                            width,
+                            height: cell_height,
+                        },
+                        size: self.size,
+                        color: fg,
+                        font,
+                        horizontal_alignment: alignment::Horizontal::Left,
+                        vertical_alignment: alignment::Vertical::Top,
+                    });
+                }
+            }
+        }
+
+        if let Some((_, thumb)) = self.scrollbar(bounds) {
+            renderer.fill_quad(
+                renderer::Quad {
+                    border_radius: (SCROLLBAR_WIDTH / 2.0).into(),
+                    ..quad(thumb.x, thumb.y, thumb.width, thumb.height)
+                },
+                SCROLLBAR,
+            );
+        }
+    }
+}
+
+impl<'a, Message, Renderer> From<TerminalView<'a, Message>> for Element<'a, Message, Renderer>
+where
+    Message: 'a,
+    Renderer: text::Renderer<Font = Font> + 'a,
+{
+    fn from(view: TerminalView<'a, Message>) -> Self {
+        Element::new(view)
+    }
+}
@@ -0,0 +1,524 @@
+use std::ffi::OsString;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use iced::futures::channel::mpsc;
+use iced::futures::SinkExt;
+use iced::{
+    subscription, theme,
+    widget::{Button, Column, ProgressBar, Row, Scrollable, Text},
+    Alignment, Element, Length, Subscription,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::sftp::{self, Sftp};
+use crate::sftp_tab::format_size;
+
+/// How many transfers run at the same time; the rest wait their turn.
+const MAX_RUNNING: usize = 3;
+const CHUNK_SIZE: usize = 64 * 1024;
+const REPORT_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Queued,
+    Running,
+    Paused,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub enum TransferEvent {
+    /// The copy has started, `offset` bytes in if it picked up a partial file.
+    Started {
+        size: u64,
+        offset: u64,
+    },
+    Progress(u64),
+    Finished(Result<(), String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum TransfersMessage {
+    /// An event from attempt number `.1` of transfer `.0`.
+    Event(usize, usize, TransferEvent),
+    Pause(usize),
+    /// Carries on with a paused or failed transfer from where it stopped.
+    Resume(usize),
+    Cancel(usize),
+    /// Starts a cancelled transfer again from the beginning.
+    Restart(usize),
+    Remove(usize),
+    ClearFinished,
+}
+
+struct Transfer {
+    id: usize,
+    direction: Direction,
+    sftp: Sftp,
+    local: PathBuf,
+    remote: String,
+    state: State,
+    /// Bumped every time the transfer is (re)started, so that events from
+    /// an earlier attempt are recognised and dropped.
+    attempt: usize,
+    /// Whether the next attempt picks up the partial file an earlier one
+    /// left behind.
+    resume: bool,
+    size: Option<u64>,
+    done: u64,
+    /// Bytes per second, smoothed.
+    rate: Option<f64>,
+    last_sample: Option<(Instant, u64)>,
+}
+
+impl Transfer {
+    fn name(&self) -> String {
+        match self.direction {
+            Direction::Upload => self.remote.clone(),
+            Direction::Download => self.local.display().to_string(),
+        }
+    }
+
+    fn record(&mut self, done: u64) {
+        let now = Instant::now();
+        if let Some((at, before)) = self.last_sample {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = done.saturating_sub(before) as f64 / elapsed;
+                self.rate = Some(match self.rate {
+                    Some(old) => old * 0.7 + rate * 0.3,
+                    None => rate,
+                });
+            }
+        }
+        self.last_sample = Some((now, done));
+        self.done = done;
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        let rate = self.rate.filter(|&rate| rate > 0.0)?;
+        let remaining = self.size?.saturating_sub(self.done);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    fn status(&self) -> String {
+        let progress = match self.size {
+            Some(size) => format!("{} / {}", format_size(self.done), format_size(size)),
+            None => format_size(self.done),
+        };
+        match &self.state {
+            State::Queued if self.resume => format!("queued at {}", progress),
+            State::Queued => String::from("queued"),
+            State::Running => {
+                let mut status = progress;
+                if let Some(rate) = self.rate {
+                    status.push_str(&format!(", {}/s", format_size(rate as u64)));
+                }
+                if let Some(eta) = self.eta() {
+                    status.push_str(&format!(", {} left", format_duration(eta)));
+                }
+                status
+            }
+            State::Paused => format!("paused at {}", progress),
+            State::Done => format!("done, {}", format_size(self.done)),
+            State::Failed(e) => format!("failed at {}: {}", progress, e),
+            State::Cancelled => format!("cancelled at {}", progress),
+        }
+    }
+}
+
+/// Every upload and download in the application, whichever SFTP tab
+/// started it. Running transfers are subscriptions: cancelling one drops its
+/// subscription, which stops the copy.
+pub struct TransferQueue {
+    transfers: Vec<Transfer>,
+    next_id: usize,
+}
+
+impl TransferQueue {
+    pub fn new() -> Self {
+        TransferQueue {
+            transfers: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    pub fn enqueue(&mut self, direction: Direction, sftp: Sftp, local: PathBuf, remote: String) {
+        self.transfers.push(Transfer {
+            id: self.next_id,
+            direction,
+            sftp,
+            local,
+            remote,
+            state: State::Queued,
+            attempt: 0,
+            resume: false,
+            size: None,
+            done: 0,
+            rate: None,
+            last_sample: None,
+        });
+        self.next_id += 1;
+        self.start_queued();
+    }
+
+    fn start_queued(&mut self) {
+        let running = self
+            .transfers
+            .iter()
+            .filter(|transfer| transfer.state == State::Running)
+            .count();
+        for transfer in self
+            .transfers
+            .iter_mut()
+            .filter(|transfer| transfer.state == State::Queued)
+            .take(MAX_RUNNING.saturating_sub(running))
+        {
+            transfer.state = State::Running;
+            transfer.attempt += 1;
+            transfer.rate = None;
+            transfer.last_sample = None;
+        }
+    }
+
+    /// Returns the remote directory an upload has just finished in, so that
+    /// file browsers showing it can refresh.
+    pub fn update(&mut self, message: TransfersMessage) -> Option<String> {
+        let mut changed = None;
+        match message {
+            TransfersMessage::Event(id, attempt, event) => {
+                let Some(transfer) = self.transfers.iter_mut().find(|transfer| {
+                    transfer.id == id
+                        && transfer.attempt == attempt
+                        && transfer.state == State::Running
+                }) else {
+                    return None;
+                };
+                match event {
+                    TransferEvent::Started { size, offset } => {
+                        transfer.size = Some(size);
+                        transfer.record(offset);
+                    }
+                    TransferEvent::Progress(done) => transfer.record(done),
+                    TransferEvent::Finished(Ok(())) => {
+                        transfer.state = State::Done;
+                        if transfer.direction == Direction::Upload {
+                            changed = Some(sftp::parent(&transfer.remote));
+                        }
+                    }
+                    TransferEvent::Finished(Err(e)) => transfer.state = State::Failed(e),
+                }
+            }
+            TransfersMessage::Pause(id) => {
+                if let Some(transfer) = self.transfers.iter_mut().find(|t| t.id == id) {
+                    if matches!(transfer.state, State::Queued | State::Running) {
+                        transfer.state = State::Paused;
+                    }
+                }
+            }
+            TransfersMessage::Resume(id) => {
+                if let Some(transfer) = self.transfers.iter_mut().find(|t| t.id == id) {
+                    if matches!(transfer.state, State::Paused | State::Failed(_)) {
+                        transfer.state = State::Queued;
+                        transfer.resume = true;
+                    }
+                }
+            }
+            TransfersMessage::Cancel(id) => {
+                if let Some(transfer) = self.transfers.iter_mut().find(|t| t.id == id) {
+                    if matches!(
+                        transfer.state,
+                        State::Queued | State::Running | State::Paused
+                    ) {
+                        transfer.state = State::Cancelled;
+                    }
+                }
+            }
+            TransfersMessage::Restart(id) => {
+                if let Some(transfer) = self.transfers.iter_mut().find(|t| t.id == id) {
+                    if transfer.state == State::Cancelled {
+                        transfer.state = State::Queued;
+                        transfer.resume = false;
+                        transfer.size = None;
+                        transfer.done = 0;
+                    }
+                }
+            }
+            TransfersMessage::Remove(id) => self.transfers.retain(|t| t.id != id),
+            TransfersMessage::ClearFinished => self.transfers.retain(|t| t.state != State::Done),
+        }
+        self.start_queued();
+        changed
+    }
+
+    pub fn subscription(&self) -> Subscription<TransfersMessage> {
+        Subscription::batch(
+            self.transfers
+                .iter()
+                .filter(|transfer| transfer.state == State::Running)
+                .map(|transfer| {
+                    let (id, attempt) = (transfer.id, transfer.attempt);
+                    let job = Job {
+                        sftp: transfer.sftp.clone(),
+                        direction: transfer.direction,
+                        local: transfer.local.clone(),
+                        remote: transfer.remote.clone(),
+                        resume: transfer.resume,
+                    };
+                    subscription::channel((id, attempt), 100, move |output| async move {
+                        let mut reporter = Reporter {
+                            id,
+                            attempt,
+                            output,
+                        };
+                        let result = job.run(&mut reporter).await;
+                        reporter.send(TransferEvent::Finished(result)).await;
+
+                        loop {
+                            iced::futures::future::pending::<()>().await;
+                        }
+                    })
+                }),
+        )
+    }
+
+    pub fn view(&self) -> Element<'_, TransfersMessage> {
+        let list = self
+            .transfers
+            .iter()
+            .fold(Column::new().spacing(4), |column, transfer| {
+                column.push(transfer_row(transfer))
+            });
+        Column::new()
+            .spacing(4)
+            .padding([4, 8])
+            .push(
+                Row::new()
+                    .align_items(Alignment::Center)
+                    .push(Text::new("Transfers").size(16).width(Length::Fill))
+                    .push(small_button(
+                        "Clear finished",
+                        TransfersMessage::ClearFinished,
+                    )),
+            )
+            .push(Scrollable::new(list).height(Length::Fixed(140.0)))
+            .into()
+    }
+}
+
+fn transfer_row(transfer: &Transfer) -> Element<'_, TransfersMessage> {
+    let arrow = match transfer.direction {
+        Direction::Upload => "↑",
+        Direction::Download => "↓",
+    };
+    let fraction = match transfer.size {
+        Some(0) if transfer.state == State::Done => 1.0,
+        Some(size) if size > 0 => transfer.done as f32 / size as f32,
+        _ => 0.0,
+    };
+
+    let mut row = Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(
+            Column::new()
+                .width(Length::Fill)
+                .push(Text::new(format!("{} {}", arrow, transfer.name())).size(14))
+                .push(Text::new(transfer.status()).size(12)),
+        )
+        .push(
+            ProgressBar::new(0.0..=1.0, fraction)
+                .width(Length::Fixed(160.0))
+                .height(Length::Fixed(8.0)),
+        );
+    let id = transfer.id;
+    row = match transfer.state {
+        State::Queued | State::Running => row
+            .push(small_button("Pause", TransfersMessage::Pause(id)))
+            .push(small_button("Cancel", TransfersMessage::Cancel(id))),
+        State::Paused => row
+            .push(small_button("Resume", TransfersMessage::Resume(id)))
+            .push(small_button("Cancel", TransfersMessage::Cancel(id))),
+        State::Failed(_) => row
+            .push(small_button("Retry", TransfersMessage::Resume(id)))
+            .push(small_button("Remove", TransfersMessage::Remove(id))),
+        State::Cancelled => row
+            .push(small_button("Restart", TransfersMessage::Restart(id)))
+            .push(small_button("Remove", TransfersMessage::Remove(id))),
+        State::Done => row.push(small_button("Remove", TransfersMessage::Remove(id))),
+    };
+    row.into()
+}
+
+struct Reporter {
+    id: usize,
+    attempt: usize,
+    output: mpsc::Sender<TransfersMessage>,
+}
+
+impl Reporter {
+    async fn send(&mut self, event: TransferEvent) {
+        let _ = self
+            .output
+            .send(TransfersMessage::Event(self.id, self.attempt, event))
+            .await;
+    }
+}
+
+struct Job {
+    sftp: Sftp,
+    direction: Direction,
+    local: PathBuf,
+    remote: String,
+    resume: bool,
+}
+
+impl Job {
+    async fn run(&self, reporter: &mut Reporter) -> Result<(), String> {
+        match self.direction {
+            Direction::Download => self.download(reporter).await,
+            Direction::Upload => self.upload(reporter).await,
+        }
+    }
+
+    /// Downloads into `<name>.part` and renames it once complete, so an
+    /// interrupted download never looks like a finished file.
+    async fn download(&self, reporter: &mut Reporter) -> Result<(), String> {
+        let remote = &self.remote;
+        let size = self.sftp.size(remote).await?;
+        let part = partial_path(&self.local);
+        let offset = if self.resume {
+            tokio::fs::metadata(&part)
+                .await
+                .map_or(0, |metadata| metadata.len())
+                .min(size)
+        } else {
+            0
+        };
+
+        let local_error = |e: std::io::Error| format!("{}: {}", part.display(), e);
+        let mut target = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&part)
+            .await
+            .map_err(local_error)?;
+        target.set_len(offset).await.map_err(local_error)?;
+        target
+            .seek(SeekFrom::Start(offset))
+            .await
+            .map_err(local_error)?;
+        let mut source = self.sftp.open_read(remote, offset).await?;
+
+        reporter.send(TransferEvent::Started { size, offset }).await;
+        copy(&mut source, &mut target, offset, reporter)
+            .await
+            .map_err(|e| format!("Downloading {} failed: {}", remote, e))?;
+        let _ = source.shutdown().await;
+        target.sync_all().await.map_err(local_error)?;
+        tokio::fs::rename(&part, &self.local)
+            .await
+            .map_err(|e| format!("Could not create {}: {}", self.local.display(), e))
+    }
+
+    /// Uploads to `<name>.part` and renames it once complete, the same as
+    /// downloads.
+    async fn upload(&self, reporter: &mut Reporter) -> Result<(), String> {
+        let remote = &self.remote;
+        let part = format!("{}.part", remote);
+        let local_error = |e: std::io::Error| format!("{}: {}", self.local.display(), e);
+        let mut source = tokio::fs::File::open(&self.local)
+            .await
+            .map_err(local_error)?;
+        let size = source.metadata().await.map_err(local_error)?.len();
+        let offset = if self.resume {
+            self.sftp.size(&part).await.unwrap_or(0).min(size)
+        } else {
+            0
+        };
+        source
+            .seek(SeekFrom::Start(offset))
+            .await
+            .map_err(local_error)?;
+        let mut target = self.sftp.open_write(&part, offset).await?;
+
+        reporter.send(TransferEvent::Started { size, offset }).await;
+        copy(&mut source, &mut target, offset, reporter)
+            .await
+            .map_err(|e| format!("Uploading to {} failed: {}", remote, e))?;
+        target
+            .shutdown()
+            .await
+            .map_err(|e| format!("Could not close {}: {}", part, e))?;
+        self.sftp.replace(&part, remote).await
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+async fn copy<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    mut done: u64,
+    reporter: &mut Reporter,
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut reported = Instant::now();
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        done += read as u64;
+        if reported.elapsed() >= REPORT_INTERVAL {
+            reporter.send(TransferEvent::Progress(done)).await;
+            reported = Instant::now();
+        }
+    }
+    writer.flush().await?;
+    reporter.send(TransferEvent::Progress(done)).await;
+    Ok(())
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=3599 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        _ => format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    }
+}
+
+fn small_button(label: &str, message: TransfersMessage) -> Button<'_, TransfersMessage> {
+    Button::new(Text::new(label).size(14))
+        .padding([2, 8])
+        .style(theme::Button::Secondary)
+        .on_press(message)
+}